@@ -0,0 +1,26 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+  // Generate messages and the gRPC service from `proto`, the generated files are included by `src/proto/mod.rs`.
+  tonic_build::configure()
+    .build_client(false)
+    .type_attribute(".raid_finder.messages.RaidBoss", "#[derive(serde::Serialize)]")
+    .type_attribute(".raid_finder.messages.RaidTweet", "#[derive(serde::Serialize)]")
+    .type_attribute(".raid_finder.messages.Translation", "#[derive(serde::Serialize)]")
+    .field_attribute(
+      ".raid_finder.messages.RaidTweet.tweet_id",
+      "#[serde(serialize_with = \"crate::proto::serialize_u64_as_string\")]",
+    )
+    .compile(
+      &[
+        "proto/messages/raid_boss.proto",
+        "proto/messages/raid_boss_raw.proto",
+        "proto/messages/raid_tweet.proto",
+        "proto/messages/raid_tweets_page.proto",
+        "proto/messages/translation.proto",
+        "proto/messages/image_fingerprint.proto",
+        "proto/services/raid_finder.proto",
+      ],
+      &["proto/messages", "proto/services"],
+    )?;
+
+  Ok(())
+}
@@ -0,0 +1,97 @@
+use crate::{common::migration::MigrationStore, Result};
+use async_trait::async_trait;
+use std::{collections::HashMap, sync::Mutex};
+
+/// Value bytes with its TTL.
+type Entry = (Vec<u8>, Option<usize>);
+
+///
+/// An in-process stand-in of redis which only keeps values in memory.
+/// It implements the same store traits as `client::redis::Redis` so tests can run without a redis server.
+/// TTLs are only recorded, keys never expire.
+///
+#[derive(Default)]
+pub struct MemoryStore {
+  values: Mutex<HashMap<String, Entry>>,
+}
+
+impl MemoryStore {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn insert<S, V>(&self, key: S, value: V, ttl: Option<usize>)
+  where
+    S: Into<String>,
+    V: Into<Vec<u8>>,
+  {
+    self.values.lock().unwrap().insert(key.into(), (value.into(), ttl));
+  }
+
+  pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+    self.values.lock().unwrap().get(key).map(|value| value.0.clone())
+  }
+
+  pub fn ttl_of(&self, key: &str) -> Option<usize> {
+    self.values.lock().unwrap().get(key).and_then(|value| value.1)
+  }
+
+  pub fn len(&self) -> usize {
+    self.values.lock().unwrap().len()
+  }
+}
+
+#[async_trait]
+impl MigrationStore for MemoryStore {
+  async fn scan(&self, pattern: &str) -> Result<Vec<String>> {
+    let mut keys = self
+      .values
+      .lock()
+      .unwrap()
+      .keys()
+      .filter(|key| glob_match(pattern.as_bytes(), key.as_bytes()))
+      .cloned()
+      .collect::<Vec<_>>();
+    keys.sort();
+
+    Ok(keys)
+  }
+
+  async fn get_bytes(&self, key: &str) -> Result<Option<Vec<u8>>> {
+    Ok(self.get(key))
+  }
+
+  async fn set_bytes(&self, key: &str, value: Vec<u8>, ttl: Option<usize>) -> Result<()> {
+    self.insert(key, value, ttl);
+
+    Ok(())
+  }
+
+  async fn rename(&self, from: &str, to: &str) -> Result<bool> {
+    let mut values = self.values.lock().unwrap();
+    match values.remove(from) {
+      Some(entry) => {
+        values.insert(to.to_owned(), entry);
+        Ok(true)
+      }
+      None => Ok(false),
+    }
+  }
+
+  async fn del(&self, key: &str) -> Result<()> {
+    self.values.lock().unwrap().remove(key);
+
+    Ok(())
+  }
+}
+
+/// Redis style glob matching which supports `*` and `?`.
+fn glob_match(pattern: &[u8], value: &[u8]) -> bool {
+  match (pattern.first(), value.first()) {
+    (None, None) => true,
+    (Some(b'*'), _) => glob_match(&pattern[1..], value) || (!value.is_empty() && glob_match(pattern, &value[1..])),
+    (Some(b'?'), Some(_)) => glob_match(&pattern[1..], &value[1..]),
+    (Some(p), Some(v)) if p == v => glob_match(&pattern[1..], &value[1..]),
+    _ => false,
+  }
+}
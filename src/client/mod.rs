@@ -1,5 +1,7 @@
 pub mod redis;
 pub mod http;
 pub mod filter_stream;
+#[cfg(test)]
+pub mod memory;
 mod parameter;
 mod oauth;
@@ -1,6 +1,23 @@
-use crate::{error, Result};
+use crate::{
+  error,
+  resources::redis::{RENAME_IF_EXISTS_SCRIPT, SCAN_COUNT},
+  Result,
+};
+use futures::{Stream, StreamExt};
 use redis::{AsyncCommands, Client};
-use std::convert::TryInto;
+use std::{collections::HashMap, convert::TryInto};
+
+///
+/// A write of `Redis::transaction`
+///
+/// * `Set` - `SET key value`, with `EX ttl` if `ttl` is not zero.
+/// * `Del` - `DEL key`
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum RedisWrite {
+  Set { key: String, value: Vec<u8>, ttl: usize },
+  Del { key: String },
+}
 
 #[derive(Clone)]
 pub struct Redis {
@@ -106,6 +123,23 @@ impl Redis {
       .map_err(|error| error::Error::RedisGetValue { error })
   }
 
+  /// `HGETALL`, an empty map is returned if the key does not exist.
+  pub async fn hgetall<S>(&self, key: S) -> Result<HashMap<String, String>>
+  where
+    S: Into<String>,
+  {
+    let mut connection = self
+      .client
+      .get_tokio_connection()
+      .await
+      .map_err(|error| error::Error::RedisGetConnection { error })?;
+
+    connection
+      .hgetall(key.into())
+      .await
+      .map_err(|error| error::Error::RedisGetValue { error })
+  }
+
   pub async fn get_string<S, I>(&self, key: I) -> Result<String>
   where
     I: Into<String>,
@@ -208,6 +242,219 @@ impl Redis {
     Ok(())
   }
 
+  pub async fn get_bytes<S>(&self, key: S) -> Result<Option<Vec<u8>>>
+  where
+    S: Into<String>,
+  {
+    let mut connection = self
+      .client
+      .get_tokio_connection()
+      .await
+      .map_err(|error| error::Error::RedisGetConnection { error })?;
+
+    connection
+      .get(key.into())
+      .await
+      .map_err(|error| error::Error::RedisGetValue { error })
+  }
+
+  pub async fn set_bytes<S, U>(&self, key: S, value: Vec<u8>, ttl: U) -> Result<()>
+  where
+    S: Into<String>,
+    U: TryInto<usize> + Copy,
+  {
+    let redis_key = key.into();
+    let redis_ttl = ttl.try_into().map_err(|_| error::Error::U32ToUSize)?;
+
+    let mut connection = self
+      .client
+      .get_tokio_connection()
+      .await
+      .map_err(|error| error::Error::RedisGetConnection { error })?;
+
+    match redis_ttl {
+      0 => connection.set(&redis_key, value).await,
+      _ => connection.set_ex(&redis_key, value, redis_ttl).await,
+    }
+    .map_err(|error| error::Error::RedisSetValue { error })
+  }
+
+  /// Rename `from` to `to` with its TTL in one step, `false` if `from` does not exist.
+  pub async fn rename_if_exists<S>(&self, from: S, to: S) -> Result<bool>
+  where
+    S: Into<String>,
+  {
+    let mut connection = self
+      .client
+      .get_tokio_connection()
+      .await
+      .map_err(|error| error::Error::RedisGetConnection { error })?;
+
+    let renamed: i32 = redis::Script::new(RENAME_IF_EXISTS_SCRIPT)
+      .key(from.into())
+      .key(to.into())
+      .invoke_async(&mut connection)
+      .await
+      .map_err(|error| error::Error::RedisRename { error })?;
+
+    Ok(renamed == 1)
+  }
+
+  pub async fn del<S>(&self, key: S) -> Result<()>
+  where
+    S: Into<String>,
+  {
+    let mut connection = self
+      .client
+      .get_tokio_connection()
+      .await
+      .map_err(|error| error::Error::RedisGetConnection { error })?;
+
+    connection
+      .del(key.into())
+      .await
+      .map_err(|error| error::Error::RedisDelete { error })
+  }
+
+  pub async fn publish<S>(&self, channel: S, value: Vec<u8>) -> Result<()>
+  where
+    S: Into<String>,
+  {
+    let mut connection = self
+      .client
+      .get_tokio_connection()
+      .await
+      .map_err(|error| error::Error::RedisGetConnection { error })?;
+
+    connection
+      .publish(channel.into(), value)
+      .await
+      .map_err(|error| error::Error::RedisPublish { error })
+  }
+
+  ///
+  /// Subscribe a pub/sub channel with a dedicated connection.
+  /// The stream ends when the connection is closed.
+  ///
+  pub async fn subscribe<S>(&self, channel: S) -> Result<impl Stream<Item = Vec<u8>>>
+  where
+    S: Into<String>,
+  {
+    let connection = self
+      .client
+      .get_tokio_connection()
+      .await
+      .map_err(|error| error::Error::RedisGetConnection { error })?;
+    let mut pubsub = connection.into_pubsub();
+
+    pubsub
+      .subscribe(channel.into())
+      .await
+      .map_err(|error| error::Error::RedisSubscribe { error })?;
+
+    Ok(pubsub.into_on_message().filter_map(|msg| async move { msg.get_payload::<Vec<u8>>().ok() }))
+  }
+
+  ///
+  /// `XADD key MAXLEN ~ max_len * field value`, return the id of the new entry.
+  ///
+  pub async fn xadd_capped<S>(&self, key: S, max_len: usize, field: &str, value: Vec<u8>) -> Result<String>
+  where
+    S: Into<String>,
+  {
+    let mut connection = self
+      .client
+      .get_tokio_connection()
+      .await
+      .map_err(|error| error::Error::RedisGetConnection { error })?;
+
+    redis::cmd("XADD")
+      .arg(key.into())
+      .arg("MAXLEN")
+      .arg("~")
+      .arg(max_len)
+      .arg("*")
+      .arg(field)
+      .arg(value)
+      .query_async(&mut connection)
+      .await
+      .map_err(|error| error::Error::RedisStreamAdd { error })
+  }
+
+  ///
+  /// `XRANGE key start end COUNT count` or `XREVRANGE key start end COUNT count` if `reverse` is true.
+  /// Return entry ids with the value of `field`, entries without the field are skipped.
+  ///
+  pub async fn xrange<S>(
+    &self,
+    key: S,
+    start: &str,
+    end: &str,
+    count: usize,
+    field: &str,
+    reverse: bool,
+  ) -> Result<Vec<(String, Vec<u8>)>>
+  where
+    S: Into<String>,
+  {
+    let mut connection = self
+      .client
+      .get_tokio_connection()
+      .await
+      .map_err(|error| error::Error::RedisGetConnection { error })?;
+
+    let entries: Vec<(String, Vec<Vec<u8>>)> = redis::cmd(if reverse { "XREVRANGE" } else { "XRANGE" })
+      .arg(key.into())
+      .arg(start)
+      .arg(end)
+      .arg("COUNT")
+      .arg(count)
+      .query_async(&mut connection)
+      .await
+      .map_err(|error| error::Error::RedisStreamRange { error })?;
+
+    Ok(
+      entries
+        .into_iter()
+        .filter_map(|(id, fields)| {
+          // Fields are flattened as [field, value, field, value, ...]
+          fields
+            .chunks(2)
+            .find(|pair| pair.len() == 2 && pair[0] == field.as_bytes())
+            .map(|pair| (id, pair[1].clone()))
+        })
+        .collect(),
+    )
+  }
+
+  /// `SET key value NX EX ttl`, return false if the key already exists.
+  pub async fn set_nx_ex<S, V, U>(&self, key: S, value: V, ttl: U) -> Result<bool>
+  where
+    S: Into<String>,
+    V: Into<String>,
+    U: TryInto<usize> + Copy,
+  {
+    let redis_ttl: usize = ttl.try_into().map_err(|_| error::Error::U32ToUSize)?;
+
+    let mut connection = self
+      .client
+      .get_tokio_connection()
+      .await
+      .map_err(|error| error::Error::RedisGetConnection { error })?;
+
+    let result: Option<String> = redis::cmd("SET")
+      .arg(key.into())
+      .arg(value.into())
+      .arg("NX")
+      .arg("EX")
+      .arg(redis_ttl)
+      .query_async(&mut connection)
+      .await
+      .map_err(|error| error::Error::RedisSetValue { error })?;
+
+    Ok(result.is_some())
+  }
+
   pub async fn keys<S>(&self, key: S) -> Result<Vec<String>>
   where
     S: Into<String>,
@@ -225,4 +472,82 @@ impl Redis {
       .await
       .map_err(|error| error::Error::RedisGetKeys { error })
   }
+
+  /// Keys matching `pattern` by `SCAN`, which does not block redis like `KEYS` does on a large keyspace.
+  pub async fn scan<S>(&self, pattern: S) -> Result<Vec<String>>
+  where
+    S: Into<String>,
+  {
+    let pattern = pattern.into();
+
+    let mut connection = self
+      .client
+      .get_tokio_connection()
+      .await
+      .map_err(|error| error::Error::RedisGetConnection { error })?;
+
+    let mut keys = vec![];
+    let mut cursor = 0u64;
+    loop {
+      let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+        .arg(cursor)
+        .arg("MATCH")
+        .arg(&pattern)
+        .arg("COUNT")
+        .arg(SCAN_COUNT)
+        .query_async(&mut connection)
+        .await
+        .map_err(|error| error::Error::RedisGetKeys { error })?;
+      keys.extend(batch);
+      if next == 0 {
+        break;
+      }
+      cursor = next;
+    }
+    // A key may be returned more than once while redis rehashes.
+    keys.sort();
+    keys.dedup();
+
+    Ok(keys)
+  }
+
+  /// Apply `writes` in a `MULTI`/`EXEC` transaction, so other clients see either all of them or none.
+  pub async fn transaction(&self, writes: Vec<RedisWrite>) -> Result<()> {
+    let mut connection = self
+      .client
+      .get_tokio_connection()
+      .await
+      .map_err(|error| error::Error::RedisGetConnection { error })?;
+
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    for write in writes {
+      match write {
+        RedisWrite::Set { key, value, ttl: 0 } => pipe.set(key, value).ignore(),
+        RedisWrite::Set { key, value, ttl } => pipe.set_ex(key, value, ttl).ignore(),
+        RedisWrite::Del { key } => pipe.del(key).ignore(),
+      };
+    }
+
+    pipe
+      .query_async(&mut connection)
+      .await
+      .map_err(|error| error::Error::RedisTransaction { error })
+  }
+
+  /// `PING`, used by the readiness probe.
+  pub async fn ping(&self) -> Result<()> {
+    let mut connection = self
+      .client
+      .get_tokio_connection()
+      .await
+      .map_err(|error| error::Error::RedisGetConnection { error })?;
+
+    redis::cmd("PING")
+      .query_async::<_, String>(&mut connection)
+      .await
+      .map_err(|error| error::Error::RedisPing { error })?;
+
+    Ok(())
+  }
 }
@@ -0,0 +1,112 @@
+use crate::common::chrono::current_timestamp_u64;
+use std::sync::{
+  atomic::{AtomicBool, AtomicU64, Ordering},
+  Arc,
+};
+
+///
+/// Process wide state of the tweet pipeline, reported by `/livez` and `/readyz`
+///
+/// # Arguments
+/// * `stream_connected`: whether the twitter filter stream is connected.
+/// * `last_parsed_tweet`: timestamp(seconds) of the last parsed raid tweet, zero if nothing is parsed yet.
+/// * `actor_alive`: whether the `TweetActor` task is still running.
+/// * `started`: timestamp(seconds) when the process started, used before the first raid tweet is parsed.
+///
+pub struct Health {
+  stream_connected: AtomicBool,
+  last_parsed_tweet: AtomicU64,
+  actor_alive: AtomicBool,
+  started: u64,
+}
+
+impl Default for Health {
+  fn default() -> Self {
+    Self {
+      stream_connected: AtomicBool::new(false),
+      last_parsed_tweet: AtomicU64::new(0),
+      actor_alive: AtomicBool::new(false),
+      started: current_timestamp_u64(),
+    }
+  }
+}
+
+impl Health {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn set_stream_connected(&self, connected: bool) {
+    self.stream_connected.store(connected, Ordering::Relaxed);
+  }
+
+  pub fn is_stream_connected(&self) -> bool {
+    self.stream_connected.load(Ordering::Relaxed)
+  }
+
+  pub fn tweet_parsed(&self) {
+    self.last_parsed_tweet.store(current_timestamp_u64(), Ordering::Relaxed);
+  }
+
+  /// Seconds since the last parsed raid tweet, or since the process started if nothing is parsed yet.
+  pub fn tweet_idle_secs(&self, now: u64) -> u64 {
+    let last_parsed_tweet = match self.last_parsed_tweet.load(Ordering::Relaxed) {
+      0 => self.started,
+      timestamp => timestamp,
+    };
+
+    now.saturating_sub(last_parsed_tweet)
+  }
+
+  pub fn is_actor_alive(&self) -> bool {
+    self.actor_alive.load(Ordering::Relaxed)
+  }
+
+  ///
+  /// Mark the actor as alive until the returned guard is dropped.
+  /// The guard should be moved into the actor task, so a panicked or finished actor is reported.
+  ///
+  pub fn actor_guard(self: &Arc<Self>) -> ActorGuard {
+    self.actor_alive.store(true, Ordering::Relaxed);
+
+    ActorGuard { health: self.clone() }
+  }
+}
+
+pub struct ActorGuard {
+  health: Arc<Health>,
+}
+
+impl Drop for ActorGuard {
+  fn drop(&mut self) {
+    self.health.actor_alive.store(false, Ordering::Relaxed);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_tweet_idle_secs() {
+    let health = Health::new();
+    let now = current_timestamp_u64();
+    assert!(health.tweet_idle_secs(now + 10) >= 10);
+    health.tweet_parsed();
+    assert!(health.tweet_idle_secs(now + 10) <= 10);
+    assert_eq!(0, health.tweet_idle_secs(0));
+  }
+
+  #[tokio::test]
+  async fn test_actor_guard() {
+    let health = Arc::new(Health::new());
+    assert!(!health.is_actor_alive());
+    let guard = health.actor_guard();
+    let task = tokio::spawn(async move {
+      let _guard = guard;
+      panic!("actor is killed");
+    });
+    assert!(task.await.is_err());
+    assert!(!health.is_actor_alive());
+  }
+}
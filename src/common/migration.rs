@@ -0,0 +1,367 @@
+use crate::{
+  client::redis::Redis,
+  common::redis::{
+    gbf_api_key, gbf_persistence_raid_tweet_key, gbf_persistence_raid_tweets_keys, gbf_schema_version_key,
+    gbf_translator_key, gbf_translator_keys,
+  },
+  error,
+  proto::raid_tweet::RaidTweet,
+  resources::redis::CURRENT_SCHEMA_VERSION,
+  Result,
+};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use log::info;
+use prost::Message;
+
+///
+/// The minimal set of key-value operations which the schema check and migrations need.
+/// Implemented by `client::redis::Redis`, and by `client::memory::MemoryStore` in tests.
+///
+#[async_trait]
+pub trait MigrationStore: Send + Sync {
+  /// Keys matching `pattern`, it should not block other clients like `KEYS` does.
+  async fn scan(&self, pattern: &str) -> Result<Vec<String>>;
+  async fn get_bytes(&self, key: &str) -> Result<Option<Vec<u8>>>;
+  /// `ttl` of `None` means the key never expires.
+  async fn set_bytes(&self, key: &str, value: Vec<u8>, ttl: Option<usize>) -> Result<()>;
+  /// Move `from` to `to` with its TTL atomically, `false` if `from` does not exist, ex. it is expired.
+  async fn rename(&self, from: &str, to: &str) -> Result<bool>;
+  async fn del(&self, key: &str) -> Result<()>;
+}
+
+#[async_trait]
+impl MigrationStore for Redis {
+  async fn scan(&self, pattern: &str) -> Result<Vec<String>> {
+    Redis::scan(self, pattern).await
+  }
+
+  async fn get_bytes(&self, key: &str) -> Result<Option<Vec<u8>>> {
+    Redis::get_bytes(self, key).await
+  }
+
+  async fn set_bytes(&self, key: &str, value: Vec<u8>, ttl: Option<usize>) -> Result<()> {
+    Redis::set_bytes(self, key, value, ttl.unwrap_or(0)).await
+  }
+
+  async fn rename(&self, from: &str, to: &str) -> Result<bool> {
+    Redis::rename_if_exists(self, from, to).await
+  }
+
+  async fn del(&self, key: &str) -> Result<()> {
+    Redis::del(self, key).await
+  }
+}
+
+/// A migration step which rewrites keys from version `n` to version `n + 1`.
+type Migration = for<'a> fn(&'a dyn MigrationStore, &'a str) -> BoxFuture<'a, Result<()>>;
+
+///
+/// Migrations ordered by the version they migrate from, `MIGRATIONS[n]` upgrades version `n` to `n + 1`.
+/// Every migration should be idempotent, running it on already migrated data should change nothing.
+///
+/// # Versions
+/// * `0` - data written before schema versioning, persistence keys were `{prefix}:persistence:{name}.{tweet_id}`.
+/// * `1` - persistence keys are `{prefix}:persistence:{name}.{tweet_id}.{created}`,
+///   translator entries are always stored in pairs.
+///
+const MIGRATIONS: [Migration; CURRENT_SCHEMA_VERSION as usize] =
+  [|store, prefix| Box::pin(migrate_v0_to_v1(store, prefix))];
+
+///
+/// Read the stored schema version.
+/// Return `None` if the version key does not exist.
+///
+pub async fn stored_schema_version(store: &dyn MigrationStore, prefix: &str) -> Result<Option<u32>> {
+  match store.get_bytes(&gbf_schema_version_key(prefix)).await? {
+    Some(bytes) => {
+      let value = String::from_utf8(bytes).map_err(|error| error::Error::StringParseFromBytes { error })?;
+      value
+        .trim()
+        .parse::<u32>()
+        .map(Some)
+        .map_err(|_| error::Error::SchemaVersionParse { value })
+    }
+    None => Ok(None),
+  }
+}
+
+async fn write_schema_version(store: &dyn MigrationStore, prefix: &str, version: u32) -> Result<()> {
+  store
+    .set_bytes(&gbf_schema_version_key(prefix), version.to_string().into_bytes(), None)
+    .await
+}
+
+///
+/// Whether there is any data under the namespace except the schema version key itself.
+/// API keys are ignored, operators may create them before the first start.
+///
+async fn has_data(store: &dyn MigrationStore, prefix: &str) -> Result<bool> {
+  let version_key = gbf_schema_version_key(prefix);
+  let api_keys = gbf_api_key(prefix, "");
+
+  Ok(
+    store
+      .scan(&format!("{}:*", prefix))
+      .await?
+      .iter()
+      .any(|key| key != &version_key && !key.starts_with(&api_keys)),
+  )
+}
+
+///
+/// Startup check of the redis key layout.
+///
+/// # Specification
+/// 1. If the stored version is `CURRENT_SCHEMA_VERSION`, nothing is scanned or written.
+/// 2. If there is no schema version and no data, it is a fresh namespace, write current version.
+/// 3. If there is data without schema version or with an older one, it is written by an older binary,
+///    `migrate` it like `raid-finder migrate` does. Replicas may migrate at the same time, every step is idempotent.
+/// 4. If the stored version is newer than `CURRENT_SCHEMA_VERSION`, refuse to start.
+///
+/// # Arguments
+/// * `store` - redis client or any other `MigrationStore`.
+/// * `prefix` - Redis namespace, `Config.redis_prefix`
+///
+pub async fn check_schema_version(store: &dyn MigrationStore, prefix: &str) -> Result<()> {
+  match stored_schema_version(store, prefix).await? {
+    Some(version) if version == CURRENT_SCHEMA_VERSION => Ok(()),
+    Some(version) if version > CURRENT_SCHEMA_VERSION => Err(error::Error::SchemaVersionUnknown {
+      found: version,
+      expected: CURRENT_SCHEMA_VERSION,
+    }),
+    _ => {
+      let from = migrate(store, prefix).await?;
+      if from < CURRENT_SCHEMA_VERSION {
+        info!(
+          "Redis namespace {} is migrated from schema version {} to {} on startup.",
+          prefix, from, CURRENT_SCHEMA_VERSION
+        );
+      }
+
+      Ok(())
+    }
+  }
+}
+
+///
+/// Migrate the namespace to `CURRENT_SCHEMA_VERSION` step by step.
+/// Schema version is written after each step, so an interrupted migration can be resumed by running it again.
+///
+/// # Arguments
+/// * `store` - redis client or any other `MigrationStore`.
+/// * `prefix` - Redis namespace, `Config.redis_prefix`
+///
+/// # Returns
+/// The schema version before migration.
+///
+pub async fn migrate(store: &dyn MigrationStore, prefix: &str) -> Result<u32> {
+  let from = match stored_schema_version(store, prefix).await? {
+    Some(version) => version,
+    None => match has_data(store, prefix).await? {
+      true => 0,
+      false => CURRENT_SCHEMA_VERSION,
+    },
+  };
+
+  if from > CURRENT_SCHEMA_VERSION {
+    return Err(error::Error::SchemaVersionUnknown {
+      found: from,
+      expected: CURRENT_SCHEMA_VERSION,
+    });
+  }
+
+  for version in from..CURRENT_SCHEMA_VERSION {
+    info!(
+      "Migrating redis namespace {} from version {} to {}...",
+      prefix,
+      version,
+      version + 1
+    );
+    MIGRATIONS[version as usize](store, prefix).await?;
+    write_schema_version(store, prefix, version + 1).await?;
+  }
+
+  // Fresh namespace or already migrated data should also have a version key.
+  write_schema_version(store, prefix, CURRENT_SCHEMA_VERSION).await?;
+
+  Ok(from)
+}
+
+///
+/// Version 0 to 1
+///
+/// 1. Rename persisted tweets to `gbf_persistence_raid_tweet_key` with the created suffix, keeping their TTL.
+///    Tweets which expire while they are migrated are skipped.
+/// 2. Remove empty translator entries and write the missing reverse side of every translator pair.
+///
+async fn migrate_v0_to_v1(store: &dyn MigrationStore, prefix: &str) -> Result<()> {
+  for key in store.scan(&gbf_persistence_raid_tweets_keys(prefix, "*")).await? {
+    let bytes = match store.get_bytes(&key).await? {
+      Some(bytes) => bytes,
+      None => continue,
+    };
+    let raid_tweet = RaidTweet::decode(&mut bytes.as_slice()).map_err(|error| error::Error::ProtobufParse { error })?;
+    let new_key = gbf_persistence_raid_tweet_key(
+      prefix,
+      raid_tweet.boss_name.as_str(),
+      raid_tweet.tweet_id,
+      raid_tweet.created,
+    );
+    if new_key == key {
+      continue;
+    }
+    store.rename(&key, &new_key).await?;
+  }
+
+  let replace = gbf_translator_key(prefix, "");
+  for key in store.scan(&gbf_translator_keys(prefix)).await? {
+    let translated = match store.get_bytes(&key).await? {
+      Some(bytes) => String::from_utf8(bytes).map_err(|error| error::Error::StringParseFromBytes { error })?,
+      None => continue,
+    };
+    if translated.is_empty() {
+      store.del(&key).await?;
+      continue;
+    }
+    let reverse_key = gbf_translator_key(prefix, &translated);
+    if store.get_bytes(&reverse_key).await?.is_none() {
+      let name = key.replacen(&replace, "", 1);
+      store.set_bytes(&reverse_key, name.into_bytes(), None).await?;
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    client::memory::MemoryStore,
+    models::Language,
+    proto::{raid_tweet::RaidTweetExt, MessageExt},
+    resources::redis::DEFAULT_PREFIX,
+  };
+
+  fn raid_tweet() -> RaidTweet {
+    RaidTweet::apply_args(
+      1390247452125458434,
+      "",
+      1620698515453,
+      "Lv150 プロトバハムート",
+      "7D705AE2",
+      "",
+      Language::Japanese,
+      "",
+    )
+  }
+
+  fn legacy_store() -> MemoryStore {
+    let store = MemoryStore::new();
+    let tweet = raid_tweet();
+    store.insert(
+      format!("gbf:persistence:{}.{}", tweet.boss_name, tweet.tweet_id),
+      tweet.to_bytes().unwrap(),
+      Some(7200),
+    );
+    store.insert("gbf:translator:Lv150 プロトバハムート", "Lvl 150 Proto Bahamut", None);
+    store.insert("gbf:translator:Lv200 アーカーシャ", "", None);
+    store
+  }
+
+  #[tokio::test]
+  async fn test_check_fresh_namespace() -> Result<()> {
+    let store = MemoryStore::new();
+    store.insert("gbf:api_key:3a0f1c", "", None);
+    check_schema_version(&store, DEFAULT_PREFIX).await?;
+    assert_eq!(
+      Some(CURRENT_SCHEMA_VERSION),
+      stored_schema_version(&store, DEFAULT_PREFIX).await?
+    );
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_check_migrates_unversioned_data() -> Result<()> {
+    let store = legacy_store();
+    let tweet = raid_tweet();
+    check_schema_version(&store, DEFAULT_PREFIX).await?;
+    assert_eq!(
+      Some(CURRENT_SCHEMA_VERSION),
+      stored_schema_version(&store, DEFAULT_PREFIX).await?
+    );
+    let new_key = gbf_persistence_raid_tweet_key(DEFAULT_PREFIX, &tweet.boss_name, tweet.tweet_id, tweet.created);
+    assert_eq!(Some(tweet.to_bytes()?), store.get(&new_key));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_check_refuses_unknown_version() {
+    let store = MemoryStore::new();
+    store.insert(gbf_schema_version_key(DEFAULT_PREFIX), "99", None);
+    let result = check_schema_version(&store, DEFAULT_PREFIX).await;
+    assert!(matches!(
+      result,
+      Err(error::Error::SchemaVersionUnknown { found: 99, .. })
+    ));
+    let result = migrate(&store, DEFAULT_PREFIX).await;
+    assert!(matches!(
+      result,
+      Err(error::Error::SchemaVersionUnknown { found: 99, .. })
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_check_ignores_other_namespace() -> Result<()> {
+    let store = legacy_store();
+    check_schema_version(&store, "gbf-staging").await?;
+    assert_eq!(
+      Some(CURRENT_SCHEMA_VERSION),
+      stored_schema_version(&store, "gbf-staging").await?
+    );
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_migrate_v0_to_v1() -> Result<()> {
+    let store = legacy_store();
+    let tweet = raid_tweet();
+    assert_eq!(0, migrate(&store, DEFAULT_PREFIX).await?);
+    check_schema_version(&store, DEFAULT_PREFIX).await?;
+
+    let new_key =
+      gbf_persistence_raid_tweet_key(DEFAULT_PREFIX, tweet.boss_name.as_str(), tweet.tweet_id, tweet.created);
+    assert_eq!(Some(tweet.to_bytes()?), store.get(&new_key));
+    assert_eq!(Some(7200), store.ttl_of(&new_key));
+    assert_eq!(
+      None,
+      store.get(&format!("gbf:persistence:{}.{}", tweet.boss_name, tweet.tweet_id))
+    );
+    assert_eq!(
+      Some("Lv150 プロトバハムート".as_bytes().to_vec()),
+      store.get("gbf:translator:Lvl 150 Proto Bahamut")
+    );
+    assert_eq!(None, store.get("gbf:translator:Lv200 アーカーシャ"));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_migrate_is_idempotent() -> Result<()> {
+    let store = legacy_store();
+    migrate(&store, DEFAULT_PREFIX).await?;
+    let len = store.len();
+    let keys = store.scan("gbf:*").await?;
+    // Running every step again on migrated data should not change anything.
+    migrate_v0_to_v1(&store, DEFAULT_PREFIX).await?;
+    assert_eq!(CURRENT_SCHEMA_VERSION, migrate(&store, DEFAULT_PREFIX).await?);
+    assert_eq!(len, store.len());
+    assert_eq!(keys, store.scan("gbf:*").await?);
+
+    Ok(())
+  }
+}
@@ -1,3 +1,10 @@
 pub mod redis;
 pub mod encode;
-pub mod chrono;
\ No newline at end of file
+pub mod chrono;
+pub mod health;
+pub mod migration;
+pub mod rate_limit;
+pub mod shutdown;
+pub mod stats;
+pub mod translator_map;
+pub mod tweet_log;
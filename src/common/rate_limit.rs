@@ -0,0 +1,238 @@
+use std::{
+  collections::{BTreeMap, HashMap},
+  hash::Hash,
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+
+/// The least recently used bucket is forgotten when a new key comes and there are already this many buckets.
+const MAX_BUCKETS: usize = 4096;
+
+struct Bucket {
+  tokens: f64,
+  updated: Instant,
+  capacity: f64,
+  /// Tokens refilled per second.
+  rate: f64,
+  /// When the bucket is used last time, the position of its key in `Buckets.recent`.
+  used: u64,
+}
+
+impl Bucket {
+  fn refill(&mut self, now: Instant) {
+    let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+    self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+    self.updated = now;
+  }
+}
+
+struct Buckets<K> {
+  buckets: HashMap<K, Bucket>,
+  /// Keys ordered by when their buckets are used, the first one is the least recently used.
+  recent: BTreeMap<u64, K>,
+  clock: u64,
+}
+
+///
+/// In-process token buckets keyed by client, ex. an API key or an IP address
+///
+/// Each bucket holds at most `capacity` tokens and refills `capacity` tokens per `period`,
+/// so a client could burst `capacity` requests and then make `capacity` requests per `period`.
+/// At most `MAX_BUCKETS` buckets are kept, the least recently used one has refilled the longest,
+/// so forgetting it gives away the fewest tokens.
+///
+pub struct RateLimiter<K> {
+  state: Mutex<Buckets<K>>,
+}
+
+impl<K: Eq + Hash + Clone> Default for RateLimiter<K> {
+  fn default() -> Self {
+    RateLimiter {
+      state: Mutex::new(Buckets {
+        buckets: HashMap::new(),
+        recent: BTreeMap::new(),
+        clock: 0,
+      }),
+    }
+  }
+}
+
+impl<K: Eq + Hash + Clone> RateLimiter<K> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Take a token of `key`, return false if its bucket is empty.
+  pub fn check(&self, key: &K, capacity: u32, period: Duration) -> bool {
+    self.check_at(key, capacity, period, Instant::now())
+  }
+
+  fn check_at(&self, key: &K, capacity: u32, period: Duration, now: Instant) -> bool {
+    let mut state = self.state.lock().unwrap();
+    let state = &mut *state;
+    state.clock += 1;
+    let used = state.clock;
+    if state.buckets.len() >= MAX_BUCKETS && !state.buckets.contains_key(key) {
+      let oldest = state.recent.keys().next().copied();
+      if let Some(oldest_key) = oldest.and_then(|oldest| state.recent.remove(&oldest)) {
+        state.buckets.remove(&oldest_key);
+      }
+    }
+    let capacity = capacity as f64;
+    let bucket = state.buckets.entry(key.clone()).or_insert_with(|| Bucket {
+      tokens: capacity,
+      updated: now,
+      capacity,
+      rate: capacity / period.as_secs_f64().max(f64::EPSILON),
+      used,
+    });
+    state.recent.remove(&bucket.used);
+    bucket.used = used;
+    state.recent.insert(used, key.clone());
+    bucket.refill(now);
+    if bucket.tokens < 1.0 {
+      return false;
+    }
+    bucket.tokens -= 1.0;
+
+    true
+  }
+
+  #[cfg(test)]
+  fn contains(&self, key: &K) -> bool {
+    self.state.lock().unwrap().buckets.contains_key(key)
+  }
+
+  #[cfg(test)]
+  fn len(&self) -> usize {
+    let state = self.state.lock().unwrap();
+    assert_eq!(state.buckets.len(), state.recent.len());
+
+    state.buckets.len()
+  }
+}
+
+///
+/// Concurrent connections keyed by client, ex. websocket streams of an API key or an IP address
+///
+pub struct ConnectionLimiter<K: Eq + Hash> {
+  connections: Mutex<HashMap<K, u32>>,
+}
+
+impl<K: Eq + Hash + Clone> Default for ConnectionLimiter<K> {
+  fn default() -> Self {
+    ConnectionLimiter {
+      connections: Mutex::new(HashMap::new()),
+    }
+  }
+}
+
+impl<K: Eq + Hash + Clone> ConnectionLimiter<K> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Count a new connection of `key`, `None` if it already has `max` connections. `0` means unlimited.
+  pub fn acquire(self: &Arc<Self>, key: K, max: u32) -> Option<ConnectionGuard<K>> {
+    let mut connections = self.connections.lock().unwrap();
+    let count = connections.entry(key.clone()).or_insert(0);
+    if max > 0 && *count >= max {
+      return None;
+    }
+    *count += 1;
+
+    Some(ConnectionGuard {
+      limiter: self.clone(),
+      key,
+    })
+  }
+
+  fn release(&self, key: &K) {
+    let mut connections = self.connections.lock().unwrap();
+    if let Some(count) = connections.get_mut(key) {
+      *count -= 1;
+      if *count == 0 {
+        connections.remove(key);
+      }
+    }
+  }
+
+  #[cfg(test)]
+  pub fn count(&self, key: &K) -> u32 {
+    self.connections.lock().unwrap().get(key).copied().unwrap_or(0)
+  }
+}
+
+/// A connection counted in `ConnectionLimiter`, it is released when dropped.
+pub struct ConnectionGuard<K: Eq + Hash + Clone> {
+  limiter: Arc<ConnectionLimiter<K>>,
+  key: K,
+}
+
+impl<K: Eq + Hash + Clone> Drop for ConnectionGuard<K> {
+  fn drop(&mut self) {
+    self.limiter.release(&self.key);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_token_bucket() {
+    let limiter = RateLimiter::new();
+    let now = Instant::now();
+    let minute = Duration::from_secs(60);
+    for _ in 0..3 {
+      assert!(limiter.check_at(&"a", 3, minute, now));
+    }
+    assert!(!limiter.check_at(&"a", 3, minute, now));
+    // Other keys have their own buckets.
+    assert!(limiter.check_at(&"b", 3, minute, now));
+    // 3 tokens per minute, one token is refilled every 20 seconds.
+    assert!(!limiter.check_at(&"a", 3, minute, now + Duration::from_secs(19)));
+    assert!(limiter.check_at(&"a", 3, minute, now + Duration::from_secs(20)));
+    assert!(!limiter.check_at(&"a", 3, minute, now + Duration::from_secs(20)));
+  }
+
+  #[test]
+  fn test_forget_least_recently_used() {
+    let limiter = RateLimiter::new();
+    let now = Instant::now();
+    let minute = Duration::from_secs(60);
+    // Every bucket is partly drained.
+    for key in 0..MAX_BUCKETS {
+      assert!(limiter.check_at(&key, 10, minute, now));
+    }
+    assert!(limiter.check_at(&0, 10, minute, now));
+    assert_eq!(MAX_BUCKETS, limiter.len());
+
+    assert!(limiter.check_at(&MAX_BUCKETS, 10, minute, now));
+    assert_eq!(MAX_BUCKETS, limiter.len());
+    assert!(limiter.contains(&0));
+    assert!(!limiter.contains(&1));
+    assert!(limiter.check_at(&(MAX_BUCKETS + 1), 10, minute, now));
+    assert!(!limiter.contains(&2));
+    assert_eq!(MAX_BUCKETS, limiter.len());
+  }
+
+  #[test]
+  fn test_connection_limiter() {
+    let limiter = Arc::new(ConnectionLimiter::new());
+    let first = limiter.acquire("a", 2).unwrap();
+    let second = limiter.acquire("a", 2).unwrap();
+    assert!(limiter.acquire("a", 2).is_none());
+    assert!(limiter.acquire("b", 2).is_some());
+    drop(first);
+    assert_eq!(1, limiter.count(&"a"));
+    let _third = limiter.acquire("a", 2).unwrap();
+    drop(second);
+    // Unlimited
+    let guards: Vec<_> = (0..5).map(|_| limiter.acquire("c", 0).unwrap()).collect();
+    assert_eq!(5, limiter.count(&"c"));
+    drop(guards);
+    assert_eq!(0, limiter.count(&"c"));
+    assert!(!limiter.connections.lock().unwrap().contains_key("c"));
+  }
+}
@@ -1,11 +1,15 @@
 use crate::resources::{
-  redis::{BOSS_KEY_WORD, GBF_PREFIX, GBF_TRANSLATOR_KEY, PERSISTENCE_KEY_WORD},
+  redis::{
+    API_KEY_KEY_WORD, BOSS_KEY_WORD, DEDUP_KEY_WORD, IMAGE_KEY_WORD, PERSISTENCE_KEY_WORD, RAID_TWEETS_CHANNEL_KEY_WORD,
+    SCHEMA_VERSION_KEY_WORD, STREAM_KEY_WORD, TRANSLATION_KEY_WORD, TRANSLATION_REVIEW_KEY_WORD, TRANSLATOR_KEY_WORD,
+    TRANSLATOR_LOCK_KEY_WORD,
+  },
   SHORTHAND_ENGLISH, SHORTHAND_JAPANESE,
 };
 use crate::{
   client::redis::Redis,
   models::Language,
-  proto::{raid_boss::RaidBoss, raid_boss_raw::RaidBossRaw},
+  proto::{raid_boss::RaidBoss, raid_boss_raw::RaidBossRaw, translation::Translation},
   Result,
 };
 use std::{collections::HashMap, str::FromStr};
@@ -15,32 +19,34 @@ use std::{collections::HashMap, str::FromStr};
 ///
 /// # Arguments
 ///
+/// * `prefix` - Redis namespace, `Config.redis_prefix`
 /// * `raid_boss_raw` - RaidBossRaw instance
 ///
 /// # Example
 ///
 /// ```
+/// let config = Config::new()?;
 /// let raid_boss_raw = RaidBossRaw::apply_args(
 ///   "Lv200 アーカーシャ",
 ///   200,
 ///   r"https://pbs.twimg.com/media/DumtNdnUYAE9PCr.jpg",
 ///   Language::Japanese,
 /// );
-/// let key = gbf_raid_boss_raw_key(&raid_boss_raw);
-/// assert_eq!("gbf:jp:200.Lv200 アーカーシャ", key);
+/// let key = gbf_raid_boss_raw_key(&config.redis_prefix, &raid_boss_raw);
+/// assert_eq!(format!("{}:jp:200.Lv200 アーカーシャ", config.redis_prefix), key);
 /// ```
-pub fn gbf_raid_boss_raw_key(raid_boss_raw: &RaidBossRaw) -> String {
-  let language = match Language::from_str(raid_boss_raw.get_language()).unwrap() {
+pub fn gbf_raid_boss_raw_key(prefix: &str, raid_boss_raw: &RaidBossRaw) -> String {
+  let language = match Language::from_str(raid_boss_raw.language.as_str()).unwrap() {
     Language::Japanese => SHORTHAND_JAPANESE,
     Language::English => SHORTHAND_ENGLISH,
   };
 
   format!(
     "{}:{}:{}.{}",
-    GBF_PREFIX,
+    prefix,
     language,
     raid_boss_raw.level,
-    raid_boss_raw.get_boss_name()
+    raid_boss_raw.boss_name.as_str()
   )
 }
 
@@ -50,57 +56,99 @@ pub fn gbf_raid_boss_raw_key(raid_boss_raw: &RaidBossRaw) -> String {
 ///
 /// # Arguments
 ///
+/// * `prefix` - Redis namespace, `Config.redis_prefix`
 /// * `level` - level filter
 ///
 /// # Example
 ///
 /// ```
-/// let key = gbf_raid_boss_keys(200);
-/// assert_eq!("gbf:boss:200.*", key);
+/// let config = Config::new()?;
+/// let key = gbf_raid_boss_keys(&config.redis_prefix, 200);
+/// assert_eq!(format!("{}:boss:200.*", config.redis_prefix), key);
 /// ```
-pub fn gbf_raid_boss_keys(level: u32) -> String {
+pub fn gbf_raid_boss_keys(prefix: &str, level: u32) -> String {
   let level_match = match level {
     0 => "*".to_owned(),
     _ => level.to_string(),
   };
 
-  format!("{}:{}:{}.*", GBF_PREFIX, BOSS_KEY_WORD, level_match)
+  format!("{}:{}:{}.*", prefix, BOSS_KEY_WORD, level_match)
+}
+
+///
+/// Get raw boss keys of every level by its name
+///
+/// # Arguments
+///
+/// * `prefix` - Redis namespace, `Config.redis_prefix`
+/// * `lang` - language of the tweets which the name comes from
+/// * `boss_name` - english or japanese name of the boss
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let keys = gbf_raid_boss_raw_name_keys(&config.redis_prefix, Language::English, "Lvl 200 Akasha");
+/// assert_eq!(format!("{}:en:*.Lvl 200 Akasha", config.redis_prefix), keys);
+/// ```
+pub fn gbf_raid_boss_raw_name_keys(prefix: &str, lang: Language, boss_name: &str) -> String {
+  let language = match lang {
+    Language::English => SHORTHAND_ENGLISH,
+    Language::Japanese => SHORTHAND_JAPANESE,
+  };
+
+  format!("{}:{}:*.{}", prefix, language, boss_name)
+}
+
+///
+/// Get translated boss keys of every level by its english or japanese name
+///
+/// # Arguments
+///
+/// * `prefix` - Redis namespace, `Config.redis_prefix`
+/// * `boss_name` - english or japanese name of the boss
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let keys = gbf_raid_boss_name_keys(&config.redis_prefix, "Lv200 アーカーシャ");
+/// assert_eq!(format!("{}:boss:*.Lv200 アーカーシャ", config.redis_prefix), keys);
+/// ```
+pub fn gbf_raid_boss_name_keys(prefix: &str, boss_name: &str) -> String {
+  format!("{}:{}:*.{}", prefix, BOSS_KEY_WORD, boss_name)
 }
 
 /// Get translated boss with its level and language
 ///
 /// # Arguments
 ///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
 /// * `lang`: Language we want to take
 /// * `raid_boss`: Raid boss information
 ///
 /// # Example:
 ///
 /// ```
+/// let config = Config::new()?;
 /// let raid_boss = RaidBoss::apply_args(
 ///   "Lvl 200 Akasha",
 ///   "Lv200 アーカーシャ",
 ///   200,
 ///   r"https://pbs.twimg.com/media/DumtNdnUYAE9PCr.jpg",
 /// );
-/// let jp_key = gbf_raid_boss_key(Language::Japanese, &raid_boss);
-/// let en_key = gbf_raid_boss_key(Language::English, &raid_boss);
-/// assert_eq!("gbf:boss:200.Lv200 アーカーシャ", jp_key);
-/// assert_eq!("gbf:boss:200.Lvl 200 Akasha", en_key);
+/// let jp_key = gbf_raid_boss_key(&config.redis_prefix, Language::Japanese, &raid_boss);
+/// let en_key = gbf_raid_boss_key(&config.redis_prefix, Language::English, &raid_boss);
+/// assert_eq!(format!("{}:boss:200.Lv200 アーカーシャ", config.redis_prefix), jp_key);
+/// assert_eq!(format!("{}:boss:200.Lvl 200 Akasha", config.redis_prefix), en_key);
 /// ```
-pub fn gbf_raid_boss_key(lang: Language, raid_boss: &RaidBoss) -> String {
+pub fn gbf_raid_boss_key(prefix: &str, lang: Language, raid_boss: &RaidBoss) -> String {
   match lang {
     Language::English => {
-      format!(
-        "{}:{}:{}.{}",
-        GBF_PREFIX, BOSS_KEY_WORD, raid_boss.level, raid_boss.en_name
-      )
+      format!("{}:{}:{}.{}", prefix, BOSS_KEY_WORD, raid_boss.level, raid_boss.en_name)
     }
     Language::Japanese => {
-      format!(
-        "{}:{}:{}.{}",
-        GBF_PREFIX, BOSS_KEY_WORD, raid_boss.level, raid_boss.jp_name
-      )
+      format!("{}:{}:{}.{}", prefix, BOSS_KEY_WORD, raid_boss.level, raid_boss.jp_name)
     }
   }
 }
@@ -109,6 +157,7 @@ pub fn gbf_raid_boss_key(lang: Language, raid_boss: &RaidBoss) -> String {
 ///
 /// # Arguments
 ///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
 /// * `lang`: Language we want to take
 /// * `raid_boss_raw`: Raid boss raw information
 /// * `translated`: Translated name.
@@ -116,15 +165,16 @@ pub fn gbf_raid_boss_key(lang: Language, raid_boss: &RaidBoss) -> String {
 /// # Example:
 ///
 /// ```
-///let raid_boss_raw = RaidBossRaw::apply_args(
+/// let config = Config::new()?;
+/// let raid_boss_raw = RaidBossRaw::apply_args(
 ///   "Lv200 アーカーシャ",
 ///   200,
 ///   r"https://pbs.twimg.com/media/DumtNdnUYAE9PCr.jpg",
 ///   Language::Japanese,
 /// );
 /// let translated = "Lvl 200 Akasha";
-/// let jp_key = gbf_raid_boss_jp_key_from_raw(Language::Japanese, &raid_boss_raw, translated);
-/// assert_eq!("gbf:boss:200.Lv200 アーカーシャ", jp_key);
+/// let jp_key = gbf_raid_boss_jp_key_from_raw(&config.redis_prefix, Language::Japanese, &raid_boss_raw, translated);
+/// assert_eq!(format!("{}:boss:200.Lv200 アーカーシャ", config.redis_prefix), jp_key);
 /// let raid_boss_raw = RaidBossRaw::apply_args(
 ///   "Lvl 200 Akasha",
 ///   200,
@@ -132,21 +182,32 @@ pub fn gbf_raid_boss_key(lang: Language, raid_boss: &RaidBoss) -> String {
 ///   Language::English,
 /// );
 /// let translated = "Lv200 アーカーシャ";
-/// let jp_key = gbf_raid_boss_jp_key_from_raw(Language::English, &raid_boss_raw, translated);
-/// assert_eq!("gbf:boss:200.Lv200 アーカーシャ", jp_key);
+/// let jp_key = gbf_raid_boss_jp_key_from_raw(&config.redis_prefix, Language::English, &raid_boss_raw, translated);
+/// assert_eq!(format!("{}:boss:200.Lv200 アーカーシャ", config.redis_prefix), jp_key);
 /// ```
-pub fn gbf_raid_boss_jp_key_from_raw(lang: Language, raid_boss_raw: &RaidBossRaw, translated: &str) -> String {
+pub fn gbf_raid_boss_jp_key_from_raw(
+  prefix: &str,
+  lang: Language,
+  raid_boss_raw: &RaidBossRaw,
+  translated: &str,
+) -> String {
   match lang {
     Language::English => {
       format!(
         "{}:{}:{}.{}",
-        GBF_PREFIX, BOSS_KEY_WORD, raid_boss_raw.get_level(), translated,
+        prefix,
+        BOSS_KEY_WORD,
+        raid_boss_raw.level,
+        translated,
       )
     }
     Language::Japanese => {
       format!(
         "{}:{}:{}.{}",
-        GBF_PREFIX, BOSS_KEY_WORD, raid_boss_raw.get_level(), raid_boss_raw.get_boss_name(),
+        prefix,
+        BOSS_KEY_WORD,
+        raid_boss_raw.level,
+        raid_boss_raw.boss_name.as_str(),
       )
     }
   }
@@ -156,35 +217,45 @@ pub fn gbf_raid_boss_jp_key_from_raw(lang: Language, raid_boss_raw: &RaidBossRaw
 ///
 /// # Arguments
 ///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
 /// * `raid_boss_name`: The name which we want to retrieve from persistence
 ///
 /// # Example
 ///
 /// ```
-/// let keys = gbf_persistence_raid_tweets_keys("Lv200 アーカーシャ");
-/// assert_eq!("gbf:persistence:Lv200 アーカーシャ.*", keys);
+/// let config = Config::new()?;
+/// let keys = gbf_persistence_raid_tweets_keys(&config.redis_prefix, "Lv200 アーカーシャ");
+/// assert_eq!(format!("{}:persistence:Lv200 アーカーシャ.*", config.redis_prefix), keys);
 /// ```
-pub fn gbf_persistence_raid_tweets_keys<S: Into<String>>(raid_boss_name: S) -> String {
-  format!("{}:{}:{}.*", GBF_PREFIX, PERSISTENCE_KEY_WORD, raid_boss_name.into())
+pub fn gbf_persistence_raid_tweets_keys<S: Into<String>>(prefix: &str, raid_boss_name: S) -> String {
+  format!("{}:{}:{}.*", prefix, PERSISTENCE_KEY_WORD, raid_boss_name.into())
 }
 
-/// Get specific persistence tweet by raid boss name and tweet_id
+/// Get specific persistence tweet by raid boss name, tweet_id and its created timestamp
 ///
 /// # Arguments
 ///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
 /// * `raid_boss_name`: The name which we want to retrieve from persistence
 /// * `tweet_id`: specific tweet id
+/// * `created`: the timestamp(ms) when the tweet is created
 ///
 /// # Example
 ///
 /// ```
-/// let key = gbf_persistence_raid_tweet_key("Lv200 アーカーシャ", 1234567890);
-/// assert_eq!("gbf:persistence:Lv200 アーカーシャ.1234567890", key);
+/// let config = Config::new()?;
+/// let key = gbf_persistence_raid_tweet_key(&config.redis_prefix, "Lv200 アーカーシャ", 1234567890, 1620698515453);
+/// assert_eq!(format!("{}:persistence:Lv200 アーカーシャ.1234567890.1620698515453", config.redis_prefix), key);
 /// ```
-pub fn gbf_persistence_raid_tweet_key<S: Into<String>>(raid_boss_name: S, tweet_id: u64, created: u64) -> String {
+pub fn gbf_persistence_raid_tweet_key<S: Into<String>>(
+  prefix: &str,
+  raid_boss_name: S,
+  tweet_id: u64,
+  created: u64,
+) -> String {
   format!(
     "{}:{}:{}.{}.{}",
-    GBF_PREFIX,
+    prefix,
     PERSISTENCE_KEY_WORD,
     raid_boss_name.into(),
     tweet_id,
@@ -192,40 +263,319 @@ pub fn gbf_persistence_raid_tweet_key<S: Into<String>>(raid_boss_name: S, tweet_
   )
 }
 
+/// Get tweet_id and created timestamp back from a persistence tweet key
+///
+/// # Arguments
+///
+/// * `key`: key which is built by `gbf_persistence_raid_tweet_key`
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let key = gbf_persistence_raid_tweet_key(&config.redis_prefix, "Lv200 アーカーシャ", 1234567890, 1620698515453);
+/// assert_eq!(Some((1234567890, 1620698515453)), gbf_persistence_raid_tweet_key_parts(&key));
+/// ```
+pub fn gbf_persistence_raid_tweet_key_parts(key: &str) -> Option<(u64, u64)> {
+  // Boss names might contain `.`, so split from the end of the key.
+  let mut parts = key.rsplitn(3, '.');
+  let created = parts.next()?.parse::<u64>().ok()?;
+  let tweet_id = parts.next()?.parse::<u64>().ok()?;
+  parts.next()?;
+
+  Some((tweet_id, created))
+}
+
+/// Parse tweet id and created timestamp of a persistence key only if it belongs to the raid boss
+///
+/// Persistence key patterns of a boss also match other bosses,
+/// ex. `Lv120 Mt. Qilin.*` matches keys of `Lv120 Mt. Qilin.Impossible` as well.
+///
+/// # Arguments
+///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
+/// * `raid_boss_name`: The name which the key should belong to
+/// * `key`: key which is returned by `gbf_persistence_raid_tweets_keys`
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let key = gbf_persistence_raid_tweet_key(&config.redis_prefix, "Lv120 Mt. Qilin.Impossible", 1234567890, 1);
+/// assert_eq!(None, gbf_persistence_raid_tweet_key_parts_of(&config.redis_prefix, "Lv120 Mt. Qilin", &key));
+/// ```
+pub fn gbf_persistence_raid_tweet_key_parts_of(prefix: &str, raid_boss_name: &str, key: &str) -> Option<(u64, u64)> {
+  gbf_persistence_raid_tweet_key_parts(key)
+    .filter(|(tweet_id, created)| key == gbf_persistence_raid_tweet_key(prefix, raid_boss_name, *tweet_id, *created))
+}
+
+/// Escape glob characters of a user input, so it is matched literally in a `KEYS` or `SCAN` pattern
+///
+/// # Arguments
+///
+/// * `input`: a part of the pattern, ex. a boss name from the url.
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let keys = gbf_raid_boss_name_keys(&config.redis_prefix, &escape_key_pattern("Lv200 *"));
+/// assert_eq!(format!("{}:boss:*.Lv200 \\*", config.redis_prefix), keys);
+/// ```
+pub fn escape_key_pattern(input: &str) -> String {
+  let mut escaped = String::with_capacity(input.len());
+  for c in input.chars() {
+    if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+      escaped.push('\\');
+    }
+    escaped.push(c);
+  }
+
+  escaped
+}
+
 /// 
 /// Get bosses which are at the same level with given raid_boss_raw.
 ///
 /// # Arguments
 ///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
 /// * `raid_boss_name`: The raid boss which we want to match its level.
 /// * `lang`: The language of keys we want to retrieve.
 ///
 /// # Example
 ///
 /// ```
+/// let config = Config::new()?;
 /// let raid_boss_raw = RaidBossRaw::apply_args(
 ///   "Lv200 アーカーシャ",
 ///   200,
 ///   r"https://pbs.twimg.com/media/DumtNdnUYAE9PCr.jpg",
 ///   Language::Japanese,
 /// );
-/// let jp_key = gbf_get_possible_boss_name(&raid_boss_raw, Language::Japanese);
-/// let en_key = gbf_get_possible_boss_name(&raid_boss_raw, Language::English);
-/// assert_eq!("gbf:jp:200.*", jp_key);
-/// assert_eq!("gbf:en:200.*", en_key);
+/// let jp_key = gbf_get_possible_boss_name(&config.redis_prefix, &raid_boss_raw, Language::Japanese);
+/// let en_key = gbf_get_possible_boss_name(&config.redis_prefix, &raid_boss_raw, Language::English);
+/// assert_eq!(format!("{}:jp:200.*", config.redis_prefix), jp_key);
+/// assert_eq!(format!("{}:en:200.*", config.redis_prefix), en_key);
 /// ```
-pub fn gbf_get_possible_boss_name(raid_boss_raw: &RaidBossRaw, lang: Language) -> String {
+pub fn gbf_get_possible_boss_name(prefix: &str, raid_boss_raw: &RaidBossRaw, lang: Language) -> String {
   let language = match lang {
     Language::English => SHORTHAND_ENGLISH,
     Language::Japanese => SHORTHAND_JAPANESE,
   };
-  format!("{}:{}:{}.*", GBF_PREFIX, language, raid_boss_raw.level)
+  format!("{}:{}:{}.*", prefix, language, raid_boss_raw.level)
+}
+
+///
+/// Get translator key of the given boss name
+///
+/// # Arguments
+///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
+/// * `boss_name`: The boss name which is translated from.
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let key = gbf_translator_key(&config.redis_prefix, "Lv200 アーカーシャ");
+/// assert_eq!(format!("{}:translator:Lv200 アーカーシャ", config.redis_prefix), key);
+/// ```
+pub fn gbf_translator_key(prefix: &str, boss_name: &str) -> String {
+  format!("{}:{}:{}", prefix, TRANSLATOR_KEY_WORD, boss_name)
 }
 
-pub async fn get_translator_map(redis: &Redis) -> Result<HashMap<String, String>> {
-  let redis_keys = redis.keys(format!("{}:*", GBF_TRANSLATOR_KEY)).await?;
+///
+/// Get all translator keys
+///
+/// # Arguments
+///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let keys = gbf_translator_keys(&config.redis_prefix);
+/// assert_eq!(format!("{}:translator:*", config.redis_prefix), keys);
+/// ```
+pub fn gbf_translator_keys(prefix: &str) -> String {
+  gbf_translator_key(prefix, "*")
+}
+
+///
+/// Get the lock of a translated boss name, both names of a locked translator pair have it
+///
+/// # Arguments
+///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
+/// * `boss_name`: english or japanese name of the boss, `*` to match every lock.
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let key = gbf_translator_lock_key(&config.redis_prefix, "Lv200 アーカーシャ");
+/// assert_eq!(format!("{}:translator_lock:Lv200 アーカーシャ", config.redis_prefix), key);
+/// ```
+pub fn gbf_translator_lock_key(prefix: &str, boss_name: &str) -> String {
+  format!("{}:{}:{}", prefix, TRANSLATOR_LOCK_KEY_WORD, boss_name)
+}
+
+///
+/// Get the `Translation` of a translator pair, it keeps the scores of the automatic match
+///
+/// # Arguments
+///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
+/// * `jp_name`: japanese name of the pair, `*` to match every translation.
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let key = gbf_translation_key(&config.redis_prefix, "Lv200 アーカーシャ");
+/// assert_eq!(format!("{}:translation:Lv200 アーカーシャ", config.redis_prefix), key);
+/// ```
+pub fn gbf_translation_key(prefix: &str, jp_name: &str) -> String {
+  format!("{}:{}:{}", prefix, TRANSLATION_KEY_WORD, jp_name)
+}
+
+///
+/// Get the `Translation` of a boss name which is waiting for review, the name is pending until it is reviewed
+///
+/// # Arguments
+///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
+/// * `boss_name`: english or japanese name of the boss, `*` to match every review.
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let key = gbf_translation_review_key(&config.redis_prefix, "Lvl 120 Medusa");
+/// assert_eq!(format!("{}:translation_review:Lvl 120 Medusa", config.redis_prefix), key);
+/// ```
+pub fn gbf_translation_review_key(prefix: &str, boss_name: &str) -> String {
+  format!("{}:{}:{}", prefix, TRANSLATION_REVIEW_KEY_WORD, boss_name)
+}
+
+///
+/// Get the cached `ImageFingerprint` key of a boss image, it expires after `Config.image_ttl`
+///
+/// # Arguments
+///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
+/// * `url`: url of the boss image.
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let key = gbf_image_key(&config.redis_prefix, "https://pbs.twimg.com/media/DumtNdnUYAE9PCr.jpg");
+/// assert_eq!(format!("{}:image:https://pbs.twimg.com/media/DumtNdnUYAE9PCr.jpg", config.redis_prefix), key);
+/// ```
+pub fn gbf_image_key(prefix: &str, url: &str) -> String {
+  format!("{}:{}:{}", prefix, IMAGE_KEY_WORD, url)
+}
+
+///
+/// Get de-duplication key of a raid, tweets share the same key if they are posting the same battle id of the same boss.
+///
+/// # Arguments
+///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
+/// * `raid_boss_name`: Japanese name of the raid boss, english tweets should be translated first.
+/// * `raid_id`: battle id in the tweet
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let key = gbf_raid_dedup_key(&config.redis_prefix, "Lv200 アーカーシャ", "7D705AE2");
+/// assert_eq!(format!("{}:dedup:Lv200 アーカーシャ.7D705AE2", config.redis_prefix), key);
+/// ```
+pub fn gbf_raid_dedup_key(prefix: &str, raid_boss_name: &str, raid_id: &str) -> String {
+  format!("{}:{}:{}.{}", prefix, DEDUP_KEY_WORD, raid_boss_name, raid_id)
+}
+
+///
+/// Get the pub/sub channel which ingester publishes raid tweets to.
+///
+/// # Arguments
+///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let channel = gbf_raid_tweets_channel(&config.redis_prefix);
+/// assert_eq!(format!("{}:raid_tweets", config.redis_prefix), channel);
+/// ```
+pub fn gbf_raid_tweets_channel(prefix: &str) -> String {
+  format!("{}:{}", prefix, RAID_TWEETS_CHANNEL_KEY_WORD)
+}
+
+///
+/// Get the redis stream which keeps an ordered log of every processed raid tweet.
+///
+/// # Arguments
+///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let key = gbf_raid_tweets_stream_key(&config.redis_prefix);
+/// assert_eq!(format!("{}:stream:raid_tweets", config.redis_prefix), key);
+/// ```
+pub fn gbf_raid_tweets_stream_key(prefix: &str) -> String {
+  format!("{}:{}:{}", prefix, STREAM_KEY_WORD, RAID_TWEETS_CHANNEL_KEY_WORD)
+}
+
+///
+/// Get the key which stores the schema version of the key layout
+///
+/// # Arguments
+///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let key = gbf_schema_version_key(&config.redis_prefix);
+/// assert_eq!(format!("{}:schema_version", config.redis_prefix), key);
+/// ```
+pub fn gbf_schema_version_key(prefix: &str) -> String {
+  format!("{}:{}", prefix, SCHEMA_VERSION_KEY_WORD)
+}
+
+///
+/// Get the redis hash of a client API key, which holds its name and limits
+///
+/// # Arguments
+///
+/// * `prefix`: Redis namespace, `Config.redis_prefix`
+/// * `api_key`: the API key sent by the client.
+///
+/// # Example
+///
+/// ```
+/// let config = Config::new()?;
+/// let key = gbf_api_key(&config.redis_prefix, "3a0f1c");
+/// assert_eq!(format!("{}:api_key:3a0f1c", config.redis_prefix), key);
+/// ```
+pub fn gbf_api_key(prefix: &str, api_key: &str) -> String {
+  format!("{}:{}:{}", prefix, API_KEY_KEY_WORD, api_key)
+}
+
+pub async fn get_translator_map(prefix: &str, redis: &Redis) -> Result<HashMap<String, String>> {
+  let redis_keys = redis.keys(gbf_translator_keys(prefix)).await?;
   let redis_values = redis.mget_string(redis_keys.clone()).await?;
-  let replace = format!("{}:", GBF_TRANSLATOR_KEY);
+  let replace = gbf_translator_key(prefix, "");
 
   Ok(
     redis_keys
@@ -236,9 +586,20 @@ pub async fn get_translator_map(redis: &Redis) -> Result<HashMap<String, String>
   )
 }
 
+/// Translations waiting for review, see `tasks::translator`.
+pub async fn get_translation_reviews(prefix: &str, redis: &Redis) -> Result<Vec<Translation>> {
+  let redis_keys = redis.keys(gbf_translation_review_key(prefix, "*")).await?;
+
+  redis.mget_protobuf(redis_keys).await
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::{
+    proto::{raid_boss::RaidBossExt, raid_boss_raw::RaidBossRawExt},
+    resources::redis::DEFAULT_PREFIX,
+  };
 
   #[test]
   fn test_gbf_raid_boss_raw_key() {
@@ -248,18 +609,34 @@ mod tests {
       r"https://pbs.twimg.com/media/DumtNdnUYAE9PCr.jpg",
       Language::Japanese,
     );
-    let key = gbf_raid_boss_raw_key(&raid_boss_raw);
+    let key = gbf_raid_boss_raw_key(DEFAULT_PREFIX, &raid_boss_raw);
     assert_eq!("gbf:jp:200.Lv200 アーカーシャ", key);
+    let key = gbf_raid_boss_raw_key("gbf-staging", &raid_boss_raw);
+    assert_eq!("gbf-staging:jp:200.Lv200 アーカーシャ", key);
   }
 
   #[test]
   fn test_gbf_raid_boss_keys() {
-    let key = gbf_raid_boss_keys(200);
+    let key = gbf_raid_boss_keys(DEFAULT_PREFIX, 200);
     assert_eq!("gbf:boss:200.*", key);
-    let key = gbf_raid_boss_keys(0);
+    let key = gbf_raid_boss_keys(DEFAULT_PREFIX, 0);
     assert_eq!("gbf:boss:*.*", key);
   }
 
+  #[test]
+  fn test_gbf_raid_boss_raw_name_keys() {
+    let keys = gbf_raid_boss_raw_name_keys(DEFAULT_PREFIX, Language::English, "Lvl 200 Akasha");
+    assert_eq!("gbf:en:*.Lvl 200 Akasha", keys);
+    let keys = gbf_raid_boss_raw_name_keys(DEFAULT_PREFIX, Language::Japanese, "Lv200 アーカーシャ");
+    assert_eq!("gbf:jp:*.Lv200 アーカーシャ", keys);
+  }
+
+  #[test]
+  fn test_gbf_raid_boss_name_keys() {
+    let keys = gbf_raid_boss_name_keys(DEFAULT_PREFIX, "Lv200 アーカーシャ");
+    assert_eq!("gbf:boss:*.Lv200 アーカーシャ", keys);
+  }
+
   #[test]
   fn test_gbf_raid_boss_key() {
     let raid_boss = RaidBoss::apply_args(
@@ -268,8 +645,8 @@ mod tests {
       200,
       r"https://pbs.twimg.com/media/DumtNdnUYAE9PCr.jpg",
     );
-    let jp_key = gbf_raid_boss_key(Language::Japanese, &raid_boss);
-    let en_key = gbf_raid_boss_key(Language::English, &raid_boss);
+    let jp_key = gbf_raid_boss_key(DEFAULT_PREFIX, Language::Japanese, &raid_boss);
+    let en_key = gbf_raid_boss_key(DEFAULT_PREFIX, Language::English, &raid_boss);
     assert_eq!("gbf:boss:200.Lv200 アーカーシャ", jp_key);
     assert_eq!("gbf:boss:200.Lvl 200 Akasha", en_key);
   }
@@ -283,7 +660,7 @@ mod tests {
       Language::Japanese,
     );
     let translated = "Lvl 200 Akasha";
-    let jp_key = gbf_raid_boss_jp_key_from_raw(Language::Japanese, &raid_boss_raw, translated);
+    let jp_key = gbf_raid_boss_jp_key_from_raw(DEFAULT_PREFIX, Language::Japanese, &raid_boss_raw, translated);
     assert_eq!("gbf:boss:200.Lv200 アーカーシャ", jp_key);
     let raid_boss_raw = RaidBossRaw::apply_args(
       "Lvl 200 Akasha",
@@ -292,22 +669,41 @@ mod tests {
       Language::English,
     );
     let translated = "Lv200 アーカーシャ";
-    let jp_key = gbf_raid_boss_jp_key_from_raw(Language::English, &raid_boss_raw, translated);
+    let jp_key = gbf_raid_boss_jp_key_from_raw(DEFAULT_PREFIX, Language::English, &raid_boss_raw, translated);
     assert_eq!("gbf:boss:200.Lv200 アーカーシャ", jp_key);
   }
 
   #[test]
   fn test_gbf_persistence_raid_tweets_keys() {
-    let keys = gbf_persistence_raid_tweets_keys("Lv200 アーカーシャ");
+    let keys = gbf_persistence_raid_tweets_keys(DEFAULT_PREFIX, "Lv200 アーカーシャ");
     assert_eq!("gbf:persistence:Lv200 アーカーシャ.*", keys);
   }
 
   #[test]
   fn test_gbf_persistence_raid_tweet_key() {
-    let key = gbf_persistence_raid_tweet_key("Lv200 アーカーシャ", 1234567890, 12345678909999);
+    let key = gbf_persistence_raid_tweet_key(DEFAULT_PREFIX, "Lv200 アーカーシャ", 1234567890, 12345678909999);
     assert_eq!("gbf:persistence:Lv200 アーカーシャ.1234567890.12345678909999", key);
   }
 
+  #[test]
+  fn test_gbf_persistence_raid_tweet_key_parts() {
+    let key = gbf_persistence_raid_tweet_key(DEFAULT_PREFIX, "Lv120 Mt. Qilin", 1234567890, 12345678909999);
+    assert_eq!(Some((1234567890, 12345678909999)), gbf_persistence_raid_tweet_key_parts(&key));
+    assert_eq!(None, gbf_persistence_raid_tweet_key_parts("gbf:persistence:Lv200 アーカーシャ.1234567890"));
+  }
+
+  #[test]
+  fn test_gbf_persistence_raid_tweet_key_parts_of() {
+    let key = gbf_persistence_raid_tweet_key(DEFAULT_PREFIX, "Lv120 Mt. Qilin", 1234567890, 12345678909999);
+    let parts = gbf_persistence_raid_tweet_key_parts_of(DEFAULT_PREFIX, "Lv120 Mt. Qilin", &key);
+    assert_eq!(Some((1234567890, 12345678909999)), parts);
+    let key = gbf_persistence_raid_tweet_key(DEFAULT_PREFIX, "Lv120 Mt. Qilin.Impossible", 1234567890, 12345678909999);
+    assert_eq!(
+      None,
+      gbf_persistence_raid_tweet_key_parts_of(DEFAULT_PREFIX, "Lv120 Mt. Qilin", &key)
+    );
+  }
+
   #[test]
   fn test_gbf_get_possible_boss_name() {
     let raid_boss_raw = RaidBossRaw::apply_args(
@@ -316,9 +712,79 @@ mod tests {
       r"https://pbs.twimg.com/media/DumtNdnUYAE9PCr.jpg",
       Language::Japanese,
     );
-    let jp_key = gbf_get_possible_boss_name(&raid_boss_raw, Language::Japanese);
-    let en_key = gbf_get_possible_boss_name(&raid_boss_raw, Language::English);
+    let jp_key = gbf_get_possible_boss_name(DEFAULT_PREFIX, &raid_boss_raw, Language::Japanese);
+    let en_key = gbf_get_possible_boss_name(DEFAULT_PREFIX, &raid_boss_raw, Language::English);
     assert_eq!("gbf:jp:200.*", jp_key);
     assert_eq!("gbf:en:200.*", en_key);
   }
+
+  #[test]
+  fn test_gbf_translator_key() {
+    let key = gbf_translator_key(DEFAULT_PREFIX, "Lv200 アーカーシャ");
+    assert_eq!("gbf:translator:Lv200 アーカーシャ", key);
+    let keys = gbf_translator_keys("gbf-staging");
+    assert_eq!("gbf-staging:translator:*", keys);
+  }
+
+  #[test]
+  fn test_gbf_translator_lock_key() {
+    let key = gbf_translator_lock_key(DEFAULT_PREFIX, "Lv200 アーカーシャ");
+    assert_eq!("gbf:translator_lock:Lv200 アーカーシャ", key);
+    // Locks are not matched by translator keys.
+    assert!(!key.starts_with(&gbf_translator_key(DEFAULT_PREFIX, "")));
+  }
+
+  #[test]
+  fn test_gbf_translation_keys() {
+    let key = gbf_translation_key(DEFAULT_PREFIX, "Lv200 アーカーシャ");
+    assert_eq!("gbf:translation:Lv200 アーカーシャ", key);
+    let key = gbf_translation_review_key(DEFAULT_PREFIX, "Lvl 120 Medusa");
+    assert_eq!("gbf:translation_review:Lvl 120 Medusa", key);
+    // Reviews are not matched by translations.
+    assert!(!key.starts_with(&gbf_translation_key(DEFAULT_PREFIX, "")));
+  }
+
+  #[test]
+  fn test_gbf_image_key() {
+    let key = gbf_image_key(DEFAULT_PREFIX, "https://pbs.twimg.com/media/DumtNdnUYAE9PCr.jpg");
+    assert_eq!("gbf:image:https://pbs.twimg.com/media/DumtNdnUYAE9PCr.jpg", key);
+  }
+
+  #[test]
+  fn test_gbf_raid_dedup_key() {
+    let key = gbf_raid_dedup_key(DEFAULT_PREFIX, "Lv200 アーカーシャ", "7D705AE2");
+    assert_eq!("gbf:dedup:Lv200 アーカーシャ.7D705AE2", key);
+  }
+
+  #[test]
+  fn test_gbf_raid_tweets_channel() {
+    let channel = gbf_raid_tweets_channel(DEFAULT_PREFIX);
+    assert_eq!("gbf:raid_tweets", channel);
+  }
+
+  #[test]
+  fn test_gbf_raid_tweets_stream_key() {
+    let key = gbf_raid_tweets_stream_key(DEFAULT_PREFIX);
+    assert_eq!("gbf:stream:raid_tweets", key);
+  }
+
+  #[test]
+  fn test_gbf_schema_version_key() {
+    let key = gbf_schema_version_key(DEFAULT_PREFIX);
+    assert_eq!("gbf:schema_version", key);
+  }
+
+  #[test]
+  fn test_gbf_api_key() {
+    let key = gbf_api_key(DEFAULT_PREFIX, "3a0f1c");
+    assert_eq!("gbf:api_key:3a0f1c", key);
+  }
+
+  #[test]
+  fn test_escape_key_pattern() {
+    assert_eq!("Lv200 アーカーシャ", escape_key_pattern("Lv200 アーカーシャ"));
+    assert_eq!(r"\*\?\[a-z\]\\", escape_key_pattern(r"*?[a-z]\"));
+    let keys = gbf_persistence_raid_tweets_keys(DEFAULT_PREFIX, escape_key_pattern("*"));
+    assert_eq!(r"gbf:persistence:\*.*", keys);
+  }
 }
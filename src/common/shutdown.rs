@@ -0,0 +1,171 @@
+use log::{error, info};
+use std::{future::Future, sync::Mutex, time::Duration};
+use tokio::{
+  signal::unix::{signal, SignalKind},
+  sync::{mpsc, watch},
+  time::{timeout_at, Instant},
+};
+
+///
+/// Coordinated graceful shutdown of the process
+///
+/// Listeners and streams wait for `triggered()` to stop taking new work.
+/// Tasks which should not be lost, ex. pending redis writes, are spawned with `spawn()` so `drain()` waits for them.
+/// Everything should be done before `deadline()`, the remaining tasks are dropped when the process exits.
+///
+/// # Arguments
+/// * `triggered`: the instant when shutdown is triggered, `None` while the process is running.
+/// * `timeout`: how long the process could take to shut down after it is triggered.
+/// * `tracker`: cloned into every tracked task, `drain()` drops the original one.
+/// * `idle`: resolves once every clone of `tracker` is dropped.
+///
+pub struct Shutdown {
+  triggered: watch::Sender<Option<Instant>>,
+  receiver: watch::Receiver<Option<Instant>>,
+  timeout: Duration,
+  tracker: Mutex<Option<mpsc::Sender<()>>>,
+  idle: Mutex<Option<mpsc::Receiver<()>>>,
+}
+
+impl Shutdown {
+  pub fn new(timeout: Duration) -> Self {
+    let (triggered, receiver) = watch::channel(None);
+    let (tracker, idle) = mpsc::channel(1);
+
+    Shutdown {
+      triggered,
+      receiver,
+      timeout,
+      tracker: Mutex::new(Some(tracker)),
+      idle: Mutex::new(Some(idle)),
+    }
+  }
+
+  /// Start shutting down, it is fine to call it more than once.
+  pub fn trigger(&self) {
+    if !self.is_triggered() {
+      let _ = self.triggered.send(Some(Instant::now()));
+    }
+  }
+
+  pub fn is_triggered(&self) -> bool {
+    self.receiver.borrow().is_some()
+  }
+
+  /// Resolve once shutdown is triggered.
+  pub fn triggered(&self) -> impl Future<Output = ()> + Send + 'static {
+    let mut receiver = self.receiver.clone();
+
+    async move {
+      while receiver.borrow().is_none() {
+        // The sender lives as long as `Shutdown`, nothing will trigger it once it is dropped.
+        if receiver.changed().await.is_err() {
+          futures::future::pending::<()>().await;
+        }
+      }
+    }
+  }
+
+  /// The instant the process should exit, counted from when shutdown is triggered.
+  pub fn deadline(&self) -> Instant {
+    let triggered = (*self.receiver.borrow()).unwrap_or_else(Instant::now);
+
+    triggered + self.timeout
+  }
+
+  ///
+  /// Spawn a task which `drain()` waits for.
+  /// Tasks spawned after draining is started are not tracked.
+  ///
+  pub fn spawn<F>(&self, task: F)
+  where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+  {
+    let tracker = self.tracker.lock().unwrap().clone();
+    tokio::spawn(async move {
+      let output = task.await;
+      // A panicked task drops its tracker as well.
+      drop(tracker);
+
+      output
+    });
+  }
+
+  ///
+  /// Wait for tracked tasks until `deadline()`.
+  /// Return false if some tasks are still running at the deadline.
+  ///
+  pub async fn drain(&self) -> bool {
+    self.tracker.lock().unwrap().take();
+    let idle = self.idle.lock().unwrap().take();
+    match idle {
+      // `recv` returns None once every tracker is dropped, nothing is sent through the channel.
+      Some(mut idle) => timeout_at(self.deadline(), idle.recv()).await.is_ok(),
+      None => true,
+    }
+  }
+}
+
+///
+/// Trigger `shutdown` when the process receives SIGTERM(ex. from Kubernetes) or SIGINT.
+///
+pub fn trigger_on_signal(shutdown: std::sync::Arc<Shutdown>) {
+  tokio::spawn(async move {
+    let mut terminate = match signal(SignalKind::terminate()) {
+      Ok(terminate) => terminate,
+      Err(e) => {
+        error!("Cannot listen SIGTERM, the process will not shut down gracefully, error: {}", e);
+        return;
+      }
+    };
+    tokio::select! {
+      _ = terminate.recv() => info!("Received SIGTERM, shutting down..."),
+      _ = tokio::signal::ctrl_c() => info!("Received SIGINT, shutting down..."),
+    }
+    shutdown.trigger();
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_trigger() {
+    let shutdown = Shutdown::new(Duration::from_secs(1));
+    let triggered = tokio::spawn(shutdown.triggered());
+    assert!(!shutdown.is_triggered());
+    shutdown.trigger();
+    let deadline = shutdown.deadline();
+    shutdown.trigger();
+    assert!(shutdown.is_triggered());
+    assert_eq!(deadline, shutdown.deadline());
+    triggered.await.unwrap();
+    // Futures created after the trigger resolve immediately.
+    shutdown.triggered().await;
+  }
+
+  #[tokio::test]
+  async fn test_drain() {
+    let shutdown = Shutdown::new(Duration::from_secs(5));
+    let (sender, receiver) = tokio::sync::oneshot::channel::<()>();
+    shutdown.spawn(async move {
+      let _ = receiver.await;
+    });
+    shutdown.spawn(async { panic!("tracked task is panicked") });
+    shutdown.trigger();
+    let mut drain = tokio::spawn(async move { shutdown.drain().await });
+    assert!(tokio::time::timeout(Duration::from_millis(20), &mut drain).await.is_err());
+    sender.send(()).unwrap();
+    assert!(drain.await.unwrap());
+  }
+
+  #[tokio::test]
+  async fn test_drain_deadline() {
+    let shutdown = Shutdown::new(Duration::from_millis(10));
+    shutdown.spawn(futures::future::pending::<()>());
+    shutdown.trigger();
+    assert!(!shutdown.drain().await);
+  }
+}
@@ -0,0 +1,42 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+///
+/// Process wide counters of the tweet pipeline
+///
+/// # Arguments
+/// * `parsed_tweets`: tweets which are parsed into raid tweets.
+/// * `duplicated_tweets`: raid tweets dropped because their raid id is already posted.
+/// * `published_tweets`: raid tweets which are sent to clients.
+///
+#[derive(Default)]
+pub struct Stats {
+  pub parsed_tweets: AtomicU64,
+  pub duplicated_tweets: AtomicU64,
+  pub published_tweets: AtomicU64,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct StatsSnapshot {
+  pub parsed_tweets: u64,
+  pub duplicated_tweets: u64,
+  pub published_tweets: u64,
+}
+
+impl Stats {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn increase(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub fn snapshot(&self) -> StatsSnapshot {
+    StatsSnapshot {
+      parsed_tweets: self.parsed_tweets.load(Ordering::Relaxed),
+      duplicated_tweets: self.duplicated_tweets.load(Ordering::Relaxed),
+      published_tweets: self.published_tweets.load(Ordering::Relaxed),
+    }
+  }
+}
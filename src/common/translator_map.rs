@@ -0,0 +1,25 @@
+use std::collections::HashMap;
+use tokio::sync::{Mutex, RwLock};
+
+///
+/// Translator pairs of the ingesting process, shared by the translator tasks and the admin APIs
+///
+/// # Arguments
+/// * `pairs`: boss names to their translated names, an empty name means the translation is pending.
+/// * `writes`: serializes changes of the pairs in redis. It is held from comparing `pairs` through the redis
+///   transaction, so a translator task and an admin request cannot interleave their writes.
+///
+#[derive(Default)]
+pub struct TranslatorPairs {
+  pub pairs: RwLock<HashMap<String, String>>,
+  pub writes: Mutex<()>,
+}
+
+impl TranslatorPairs {
+  pub fn new(pairs: HashMap<String, String>) -> Self {
+    Self {
+      pairs: RwLock::new(pairs),
+      writes: Mutex::new(()),
+    }
+  }
+}
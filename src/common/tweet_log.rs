@@ -0,0 +1,80 @@
+use crate::{
+  client::redis::Redis, common::redis::gbf_raid_tweets_stream_key, resources::redis::STREAM_TWEET_FIELD, Result,
+};
+
+/// Entry id of the raid tweet stream with the protobuf encoded raid tweet.
+pub type TweetLogEntry = (String, Vec<u8>);
+
+///
+/// Read the raid tweet stream `{prefix}:stream:raid_tweets` in ascending order.
+///
+/// # Arguments
+/// * `redis` - a redis client.
+/// * `prefix` - Redis namespace, `Config.redis_prefix`
+/// * `since` - stream id of the last entry which is already consumed, if it is `None` read the latest entries.
+/// * `limit` - max number of entries.
+///
+pub async fn read_tweet_log(
+  redis: &Redis,
+  prefix: &str,
+  since: Option<&str>,
+  limit: usize,
+) -> Result<Vec<TweetLogEntry>> {
+  let key = gbf_raid_tweets_stream_key(prefix);
+
+  match since {
+    Some(since) => {
+      // `since` itself is included by XRANGE, read one more entry and skip it.
+      let entries = redis
+        .xrange(key, since, "+", limit + 1, STREAM_TWEET_FIELD, false)
+        .await?;
+
+      Ok(skip_consumed(entries, since, limit))
+    }
+    None => {
+      let mut entries = redis.xrange(key, "+", "-", limit, STREAM_TWEET_FIELD, true).await?;
+      entries.reverse();
+
+      Ok(entries)
+    }
+  }
+}
+
+/// Whether `id` is a stream entry id, ex. `1620698515453-0`, the sequence number could be omitted.
+pub fn is_stream_id(id: &str) -> bool {
+  let (ms, sequence) = id.split_once('-').unwrap_or((id, "0"));
+  ms.parse::<u64>().is_ok() && sequence.parse::<u64>().is_ok()
+}
+
+fn skip_consumed(entries: Vec<TweetLogEntry>, since: &str, limit: usize) -> Vec<TweetLogEntry> {
+  entries.into_iter().filter(|(id, _)| id != since).take(limit).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_skip_consumed() {
+    let entries = vec![
+      ("1-0".to_owned(), vec![1]),
+      ("2-0".to_owned(), vec![2]),
+      ("3-0".to_owned(), vec![3]),
+    ];
+    let result = skip_consumed(entries.clone(), "1-0", 2);
+    assert_eq!(vec![entries[1].clone(), entries[2].clone()], result);
+    // `since` may have been trimmed from the stream already.
+    let result = skip_consumed(entries.clone(), "0-1", 2);
+    assert_eq!(vec![entries[0].clone(), entries[1].clone()], result);
+  }
+
+  #[test]
+  fn test_is_stream_id() {
+    assert!(is_stream_id("1620698515453-0"));
+    assert!(is_stream_id("1620698515453"));
+    assert!(!is_stream_id("1620698515453-"));
+    assert!(!is_stream_id("-0"));
+    assert!(!is_stream_id("+"));
+    assert!(!is_stream_id("latest"));
+  }
+}
@@ -1,6 +1,228 @@
-use crate::{error, Result};
+use crate::{
+  error,
+  resources::{
+    cors::{DEFAULT_ALLOWED_HEADERS, DEFAULT_ALLOWED_METHODS},
+    redis::{
+      DEFAULT_BOSS_TTL, DEFAULT_IMAGE_TTL, DEFAULT_PREFIX, DEFAULT_RAID_DEDUP_TTL, DEFAULT_TWEET_STREAM_MAX_LEN,
+      DEFAULT_TWEET_TTL,
+    },
+    throttle::{DEFAULT_IP_MAX_CONNECTIONS, DEFAULT_IP_REQUESTS_PER_MINUTE},
+    translator::{DEFAULT_ACCEPT_DSSIM, DEFAULT_MAX_HASH_DISTANCE, DEFAULT_MIN_MARGIN, DEFAULT_REVIEW_DSSIM},
+    ws::DEFAULT_CLIENT_QUEUE_CAPACITY,
+    DEFAULT_GRPC_ADDR, DEFAULT_HTTP_ADDR, DEFAULT_SHUTDOWN_TIMEOUT,
+  },
+  Result,
+};
 
-use std::env;
+use std::{
+  env,
+  net::{IpAddr, SocketAddr},
+  str::FromStr,
+};
+use warp::http::{header::HeaderName, Method, Uri};
+
+///
+/// Which part of the raid finder this process should run.
+///
+/// * `All` - consume twitter stream and serve websocket clients in the same process.
+/// * `Ingester` - consume twitter stream and publish raid tweets to redis, there should be only one ingester.
+/// * `Server` - subscribe raid tweets from redis and serve websocket clients, can be scaled horizontally.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+  All,
+  Ingester,
+  Server,
+}
+
+impl Role {
+  /// Whether this process should connect to twitter stream api.
+  pub fn ingests(&self) -> bool {
+    matches!(self, Role::All | Role::Ingester)
+  }
+}
+
+impl FromStr for Role {
+  type Err = ();
+
+  fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+    match s {
+      "all" => Ok(Role::All),
+      "ingester" => Ok(Role::Ingester),
+      "server" => Ok(Role::Server),
+      _ => Err(()),
+    }
+  }
+}
+
+///
+/// What to do when the message queue of a websocket client is full.
+///
+/// * `DropOldest` - drop the oldest queued message to make room for the new one.
+/// * `DropNewest` - drop the new message.
+/// * `Disconnect` - close the websocket with a policy violation close code.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverflowPolicy {
+  DropOldest,
+  DropNewest,
+  Disconnect,
+}
+
+impl FromStr for OverflowPolicy {
+  type Err = ();
+
+  fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+    match s {
+      "drop_oldest" => Ok(OverflowPolicy::DropOldest),
+      "drop_newest" => Ok(OverflowPolicy::DropNewest),
+      "disconnect" => Ok(OverflowPolicy::Disconnect),
+      _ => Err(()),
+    }
+  }
+}
+
+///
+/// Certificate chain and private key of the http server, both in PEM format.
+/// They are read again when the process receives SIGHUP.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct TlsConfig {
+  pub cert_path: String,
+  pub key_path: String,
+}
+
+///
+/// CORS policy of the public listeners for browser front ends served from other origins.
+/// Websocket upgrades are checked against `allowed_origins` as well, to prevent cross-site websocket hijacking.
+///
+/// # Arguments
+/// * `allowed_origins`: ex. `https://raid.example.com`, `*` allows any origin.
+/// * `allowed_methods`: methods of preflight requests.
+/// * `allowed_headers`: request headers of preflight requests, ex. `x-api-key`.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorsConfig {
+  pub allowed_origins: Vec<String>,
+  pub allowed_methods: Vec<Method>,
+  pub allowed_headers: Vec<HeaderName>,
+}
+
+///
+/// Thresholds of the translator, a match is accepted if it is clearly the most similar boss, or queued for review
+/// if it is close enough, see `image::Ranking::verdict`.
+///
+/// # Arguments
+/// * `accept_dssim`: DSSIM of accepted matches should be lower than it.
+/// * `min_margin`: DSSIM of the runner-up should be higher than the accepted match by at least this much.
+/// * `review_dssim`: matches which are not accepted are reviewed if their DSSIM is lower than it.
+/// * `max_hash_distance`: competitors whose image hash differs in more bits are pruned before DSSIM.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct TranslationConfig {
+  pub accept_dssim: f64,
+  pub min_margin: f64,
+  pub review_dssim: f64,
+  pub max_hash_distance: u32,
+}
+
+impl Default for TranslationConfig {
+  fn default() -> Self {
+    TranslationConfig {
+      accept_dssim: DEFAULT_ACCEPT_DSSIM,
+      min_margin: DEFAULT_MIN_MARGIN,
+      review_dssim: DEFAULT_REVIEW_DSSIM,
+      max_hash_distance: DEFAULT_MAX_HASH_DISTANCE,
+    }
+  }
+}
+
+/// Whether `origin` is `*` or a `scheme://host[:port]` which could be sent in the `Origin` header.
+fn is_valid_origin(origin: &str) -> bool {
+  if origin == "*" {
+    return true;
+  }
+  match origin.parse::<Uri>() {
+    Ok(uri) => uri.scheme().is_some() && uri.authority().is_some() && uri.path() == "/" && uri.query().is_none(),
+    Err(_) => false,
+  }
+}
+
+///
+/// Where API keys of raid finder clients are stored, not to be confused with `Config.api_key` of twitter.
+///
+/// * `None` - every endpoint is open.
+/// * `Redis` - hashes of `{prefix}:api_key:{key}`, keys could be added or revoked while serving.
+/// * `File` - a JSON file read at startup, see `server::auth::ApiKey`.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum ApiKeyStore {
+  None,
+  Redis,
+  File(String),
+}
+
+impl FromStr for ApiKeyStore {
+  type Err = ();
+
+  fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+    match s {
+      "none" => Ok(ApiKeyStore::None),
+      "redis" => Ok(ApiKeyStore::Redis),
+      _ => match s.strip_prefix("file:") {
+        Some(path) if !path.is_empty() => Ok(ApiKeyStore::File(path.to_owned())),
+        _ => Err(()),
+      },
+    }
+  }
+}
+
+///
+/// An IP address or a CIDR block, ex. `10.0.0.0/8` or `::1`.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IpNetwork {
+  addr: IpAddr,
+  prefix_len: u8,
+}
+
+impl IpNetwork {
+  pub fn contains(&self, ip: IpAddr) -> bool {
+    match (self.addr, ip) {
+      (IpAddr::V4(network), IpAddr::V4(ip)) => {
+        let mask = u32::MAX.checked_shl(32 - self.prefix_len as u32).unwrap_or(0);
+        u32::from(network) & mask == u32::from(ip) & mask
+      }
+      (IpAddr::V6(network), IpAddr::V6(ip)) => {
+        let mask = u128::MAX.checked_shl(128 - self.prefix_len as u32).unwrap_or(0);
+        u128::from(network) & mask == u128::from(ip) & mask
+      }
+      _ => false,
+    }
+  }
+}
+
+impl FromStr for IpNetwork {
+  type Err = ();
+
+  fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+    let (addr, prefix_len) = match s.split_once('/') {
+      Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+      None => (s, None),
+    };
+    let addr = addr.parse::<IpAddr>().map_err(|_| ())?;
+    let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix_len = match prefix_len {
+      Some(prefix_len) => prefix_len.parse::<u8>().map_err(|_| ())?,
+      None => max_prefix_len,
+    };
+    if prefix_len > max_prefix_len {
+      return Err(());
+    }
+
+    Ok(IpNetwork { addr, prefix_len })
+  }
+}
 
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -10,6 +232,44 @@ pub struct Config {
   pub access_token_secret: String,
   pub redis_url: String,
   pub log_path: String,
+  /// Namespace of every redis key, ex. `gbf` will produce `gbf:boss:*`.
+  pub redis_prefix: String,
+  /// TTL(seconds) of raid boss information.
+  pub boss_ttl: u32,
+  /// TTL(seconds) of persisted raid tweets.
+  pub tweet_ttl: u32,
+  /// Window(seconds) in which tweets with the same boss and raid id are considered duplicated, 0 to disable.
+  pub raid_dedup_ttl: u32,
+  /// TTL(seconds) of cached boss images, boss images rarely change once they are tweeted.
+  pub image_ttl: u32,
+  /// Approximate max length of the raid tweet stream `{prefix}:stream:raid_tweets`.
+  pub tweet_stream_max_len: usize,
+  pub role: Role,
+  /// Max messages queued for a websocket client which does not read fast enough.
+  pub client_queue_capacity: usize,
+  pub client_queue_policy: OverflowPolicy,
+  /// Addresses the http server listens on, every listener serves all routes.
+  pub http_addrs: Vec<SocketAddr>,
+  /// Plaintext listener which only serves probes and stats, ex. `127.0.0.1:9090`.
+  pub admin_addr: Option<SocketAddr>,
+  /// Address the gRPC server listens on.
+  pub grpc_addr: SocketAddr,
+  /// Terminate TLS on `http_addrs`, requires the `tls` feature.
+  pub tls: Option<TlsConfig>,
+  /// Seconds to drain clients and pending tasks after SIGTERM before the process exits.
+  pub shutdown_timeout: u64,
+  /// Require API keys of http and websocket clients, probes are always open.
+  pub api_key_store: ApiKeyStore,
+  /// Proxies whose `X-Forwarded-For` is trusted to find the client IP, ex. the ingress controller.
+  pub trusted_proxies: Vec<IpNetwork>,
+  /// Http requests of each client IP, `0` means unlimited.
+  pub ip_requests_per_minute: u32,
+  /// Concurrent websocket and SSE streams of each client IP, `0` means unlimited.
+  pub ip_max_connections: u32,
+  /// Browsers are not allowed to call the public listeners from other origins if it is not set.
+  pub cors: Option<CorsConfig>,
+  /// Thresholds to accept or review matches of the translator.
+  pub translation: TranslationConfig,
 }
 
 impl Config {
@@ -20,6 +280,66 @@ impl Config {
     let access_token_secret = env::var("TWITTER_ACCESS_TOKEN_SECRET").map_err(|_| error::Error::AccessTokenSecretNotFound)?;
     let redis_url = env::var("REDIS_URL").map_err(|_| error::Error::RedisURLNotFound)?;
     let log_path = env::var("GBF_RAID_FINDER_LOG_PATH").unwrap_or_else(|_| "/var/log".to_owned());
+    let redis_prefix = env::var("GBF_RAID_FINDER_REDIS_PREFIX").unwrap_or_else(|_| DEFAULT_PREFIX.to_owned());
+    let boss_ttl = env_or("GBF_RAID_FINDER_BOSS_TTL", DEFAULT_BOSS_TTL)?;
+    let tweet_ttl = env_or("GBF_RAID_FINDER_TWEET_TTL", DEFAULT_TWEET_TTL)?;
+    let raid_dedup_ttl = env_or("GBF_RAID_FINDER_RAID_DEDUP_TTL", DEFAULT_RAID_DEDUP_TTL)?;
+    let image_ttl = env_or("GBF_RAID_FINDER_IMAGE_TTL", DEFAULT_IMAGE_TTL)?;
+    let tweet_stream_max_len = env_or("GBF_RAID_FINDER_TWEET_STREAM_MAX_LEN", DEFAULT_TWEET_STREAM_MAX_LEN)?;
+    let role = env_or("GBF_RAID_FINDER_ROLE", Role::All)?;
+    let client_queue_capacity = env_or("GBF_RAID_FINDER_CLIENT_QUEUE_CAPACITY", DEFAULT_CLIENT_QUEUE_CAPACITY)?;
+    let client_queue_policy = env_or("GBF_RAID_FINDER_CLIENT_QUEUE_POLICY", OverflowPolicy::DropOldest)?;
+    let http_addrs = env_list("GBF_RAID_FINDER_HTTP_ADDRS", vec![DEFAULT_HTTP_ADDR.parse().unwrap()])?;
+    let admin_addr = env_optional("GBF_RAID_FINDER_ADMIN_ADDR")?;
+    let grpc_addr = env_or("GBF_RAID_FINDER_GRPC_ADDR", DEFAULT_GRPC_ADDR.parse().unwrap())?;
+    let tls = match (
+      env_optional::<String>("GBF_RAID_FINDER_TLS_CERT")?,
+      env_optional::<String>("GBF_RAID_FINDER_TLS_KEY")?,
+    ) {
+      (Some(cert_path), Some(key_path)) => Some(TlsConfig { cert_path, key_path }),
+      (None, None) => None,
+      (Some(_), None) => {
+        return Err(error::Error::InvalidEnvironment {
+          name: "GBF_RAID_FINDER_TLS_KEY".to_owned(),
+        })
+      }
+      (None, Some(_)) => {
+        return Err(error::Error::InvalidEnvironment {
+          name: "GBF_RAID_FINDER_TLS_CERT".to_owned(),
+        })
+      }
+    };
+
+    let shutdown_timeout = env_or("GBF_RAID_FINDER_SHUTDOWN_TIMEOUT", DEFAULT_SHUTDOWN_TIMEOUT)?;
+    let api_key_store = env_or("GBF_RAID_FINDER_API_KEY_STORE", ApiKeyStore::None)?;
+    let trusted_proxies = env_list("GBF_RAID_FINDER_TRUSTED_PROXIES", vec![])?;
+    let ip_requests_per_minute = env_or("GBF_RAID_FINDER_IP_REQUESTS_PER_MINUTE", DEFAULT_IP_REQUESTS_PER_MINUTE)?;
+    let ip_max_connections = env_or("GBF_RAID_FINDER_IP_MAX_CONNECTIONS", DEFAULT_IP_MAX_CONNECTIONS)?;
+    let allowed_origins = env_list::<String>("GBF_RAID_FINDER_CORS_ALLOWED_ORIGINS", vec![])?;
+    if !allowed_origins.iter().all(|origin| is_valid_origin(origin)) {
+      return Err(error::Error::InvalidEnvironment {
+        name: "GBF_RAID_FINDER_CORS_ALLOWED_ORIGINS".to_owned(),
+      });
+    }
+    let cors = match allowed_origins.is_empty() {
+      true => None,
+      false => Some(CorsConfig {
+        allowed_origins,
+        allowed_methods: env_list("GBF_RAID_FINDER_CORS_ALLOWED_METHODS", parse_all(DEFAULT_ALLOWED_METHODS))?,
+        allowed_headers: env_list("GBF_RAID_FINDER_CORS_ALLOWED_HEADERS", parse_all(DEFAULT_ALLOWED_HEADERS))?,
+      }),
+    };
+    let translation = TranslationConfig {
+      accept_dssim: env_or("GBF_RAID_FINDER_TRANSLATION_ACCEPT_DSSIM", DEFAULT_ACCEPT_DSSIM)?,
+      min_margin: env_or("GBF_RAID_FINDER_TRANSLATION_MIN_MARGIN", DEFAULT_MIN_MARGIN)?,
+      review_dssim: env_or("GBF_RAID_FINDER_TRANSLATION_REVIEW_DSSIM", DEFAULT_REVIEW_DSSIM)?,
+      max_hash_distance: env_or("GBF_RAID_FINDER_TRANSLATION_MAX_HASH_DISTANCE", DEFAULT_MAX_HASH_DISTANCE)?,
+    };
+    if translation.review_dssim < translation.accept_dssim {
+      return Err(error::Error::InvalidEnvironment {
+        name: "GBF_RAID_FINDER_TRANSLATION_REVIEW_DSSIM".to_owned(),
+      });
+    }
 
     Ok(Config {
       api_key,
@@ -28,6 +348,109 @@ impl Config {
       access_token_secret,
       redis_url,
       log_path,
+      redis_prefix,
+      boss_ttl,
+      tweet_ttl,
+      raid_dedup_ttl,
+      image_ttl,
+      tweet_stream_max_len,
+      role,
+      client_queue_capacity,
+      client_queue_policy,
+      http_addrs,
+      admin_addr,
+      grpc_addr,
+      tls,
+      shutdown_timeout,
+      api_key_store,
+      trusted_proxies,
+      ip_requests_per_minute,
+      ip_max_connections,
+      cors,
+      translation,
     })
   }
 }
+
+impl Default for Config {
+  fn default() -> Self {
+    Config {
+      api_key: "".into(),
+      api_secret_key: "".into(),
+      access_token: "".into(),
+      access_token_secret: "".into(),
+      redis_url: "".into(),
+      log_path: "/var/log".into(),
+      redis_prefix: DEFAULT_PREFIX.into(),
+      boss_ttl: DEFAULT_BOSS_TTL,
+      tweet_ttl: DEFAULT_TWEET_TTL,
+      raid_dedup_ttl: DEFAULT_RAID_DEDUP_TTL,
+      image_ttl: DEFAULT_IMAGE_TTL,
+      tweet_stream_max_len: DEFAULT_TWEET_STREAM_MAX_LEN,
+      role: Role::All,
+      client_queue_capacity: DEFAULT_CLIENT_QUEUE_CAPACITY,
+      client_queue_policy: OverflowPolicy::DropOldest,
+      http_addrs: vec![DEFAULT_HTTP_ADDR.parse().unwrap()],
+      admin_addr: None,
+      grpc_addr: DEFAULT_GRPC_ADDR.parse().unwrap(),
+      tls: None,
+      shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+      api_key_store: ApiKeyStore::None,
+      trusted_proxies: vec![],
+      ip_requests_per_minute: DEFAULT_IP_REQUESTS_PER_MINUTE,
+      ip_max_connections: DEFAULT_IP_MAX_CONNECTIONS,
+      cors: None,
+      translation: TranslationConfig::default(),
+    }
+  }
+}
+
+///
+/// Read an optional environment variable, fallback to `default` if it is not set.
+/// Return an InvalidEnvironment error if the variable cannot be parsed.
+///
+fn env_or<T: FromStr>(name: &str, default: T) -> Result<T> {
+  match env::var(name) {
+    Ok(value) => value
+      .parse::<T>()
+      .map_err(|_| error::Error::InvalidEnvironment { name: name.to_owned() }),
+    Err(_) => Ok(default),
+  }
+}
+
+///
+/// Read an optional environment variable, `None` if it is not set or empty.
+/// Return an InvalidEnvironment error if the variable cannot be parsed.
+///
+fn env_optional<T: FromStr>(name: &str) -> Result<Option<T>> {
+  match env::var(name) {
+    Ok(value) if !value.trim().is_empty() => value
+      .trim()
+      .parse::<T>()
+      .map(Some)
+      .map_err(|_| error::Error::InvalidEnvironment { name: name.to_owned() }),
+    _ => Ok(None),
+  }
+}
+
+///
+/// Read a comma separated environment variable, fallback to `default` if it is not set.
+/// Return an InvalidEnvironment error if any item cannot be parsed.
+///
+fn env_list<T: FromStr>(name: &str, default: Vec<T>) -> Result<Vec<T>> {
+  match env::var(name) {
+    Ok(value) => value
+      .split(',')
+      .map(|item| item.trim())
+      .filter(|item| !item.is_empty())
+      .map(|item| item.parse::<T>())
+      .collect::<std::result::Result<Vec<_>, _>>()
+      .map_err(|_| error::Error::InvalidEnvironment { name: name.to_owned() }),
+    Err(_) => Ok(default),
+  }
+}
+
+/// Parse built-in defaults, they are always valid.
+fn parse_all<T: FromStr>(items: &[&str]) -> Vec<T> {
+  items.iter().filter_map(|item| item.parse().ok()).collect()
+}
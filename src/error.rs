@@ -15,6 +15,8 @@ pub enum Error {
   ApiSecretKeyNotFound,
   #[snafu(display("Cannot find environment variable REDIS_URL"))]
   RedisURLNotFound,
+  #[snafu(display("Environment variable {} has an invalid value", name))]
+  InvalidEnvironment { name: String },
 
   /// Redis Error
   #[snafu(display("Cannot get redis connection, error: {}", error))]
@@ -29,6 +31,30 @@ pub enum Error {
   RedisExpire { error: redis::RedisError },
   #[snafu(display("Cannot open redis connection, error: {}", error))]
   RedisConnection { error: redis::RedisError },
+  #[snafu(display("Cannot delete redis key, error: {}", error))]
+  RedisDelete { error: redis::RedisError },
+  #[snafu(display("Cannot publish redis message, error: {}", error))]
+  RedisPublish { error: redis::RedisError },
+  #[snafu(display("Cannot subscribe redis channel, error: {}", error))]
+  RedisSubscribe { error: redis::RedisError },
+  #[snafu(display("Redis subscription is closed"))]
+  RedisSubscriptionClosed,
+  #[snafu(display("Cannot add redis stream entry, error: {}", error))]
+  RedisStreamAdd { error: redis::RedisError },
+  #[snafu(display("Cannot read redis stream range, error: {}", error))]
+  RedisStreamRange { error: redis::RedisError },
+  #[snafu(display("Cannot ping redis, error: {}", error))]
+  RedisPing { error: redis::RedisError },
+  #[snafu(display("Cannot execute redis transaction, error: {}", error))]
+  RedisTransaction { error: redis::RedisError },
+  #[snafu(display("Cannot rename redis key, error: {}", error))]
+  RedisRename { error: redis::RedisError },
+
+  /// Schema Error
+  #[snafu(display("Unknown redis schema version {}, this binary only supports up to {}", found, expected))]
+  SchemaVersionUnknown { found: u32, expected: u32 },
+  #[snafu(display("Cannot parse redis schema version {:?}", value))]
+  SchemaVersionParse { value: String },
 
   /// HTTP Request Error
   #[snafu(display("Cannot get stream, error: {}", error))]
@@ -50,6 +76,24 @@ pub enum Error {
   #[snafu(display("Websocket client gone"))]
   WebsocketClientClose,
 
+  /// HTTP Server Error
+  #[snafu(display("Cannot bind http listener {}, error: {}", addr, error))]
+  HttpBind { addr: std::net::SocketAddr, error: warp::Error },
+  #[snafu(display("Cannot bind https listener {}, error: {}", addr, error))]
+  TlsBind { addr: std::net::SocketAddr, error: std::io::Error },
+  #[snafu(display("Cannot read TLS file {}, error: {}", path, error))]
+  TlsReadFile { path: String, error: std::io::Error },
+  #[snafu(display("Cannot find any certificate in {}", path))]
+  TlsCertificateNotFound { path: String },
+  #[snafu(display("Cannot find any PKCS8 or RSA private key in {}", path))]
+  TlsPrivateKeyNotFound { path: String },
+  #[snafu(display("Invalid TLS configuration: {}", message))]
+  TlsConfig { message: String },
+  #[snafu(display("Cannot read API keys file {}, error: {}", path, error))]
+  ApiKeysFileRead { path: String, error: std::io::Error },
+  #[snafu(display("Cannot parse API keys file {}, error: {}", path, error))]
+  ApiKeysFileParse { path: String, error: serde_json::Error },
+
   /// Image Comparison Error
   #[snafu(display("Cannot get image from url, error: {}", error))]
   ImageCannotGet { error: reqwest::Error },
@@ -79,6 +123,8 @@ pub enum Error {
   /// Common Error
   #[snafu(display("Cannot translate given name, name: {}", name))]
   CannotTranslate { name: String },
+  #[snafu(display("Raid {} of {} is already posted", raid_id, boss_name))]
+  DuplicatedRaidTweet { boss_name: String, raid_id: String },
   #[snafu(display("Actor task has been killed, error: {}", error))]
   ActorTaskBeenKilled { error: tokio::sync::oneshot::error::RecvError },
   #[snafu(display("String parse from bytes error, error: {}", error))]
@@ -87,17 +133,22 @@ pub enum Error {
   FutureAlreadyCompleted,
 }
 
+///
+/// Rejection of http APIs, it is turned into a JSON error envelope by `server::recover`.
+///
 #[derive(Debug)]
-#[allow(dead_code)]
-struct HttpRejection {
-  message: String,
-  code: u16,
+pub struct HttpRejection {
+  pub message: String,
+  /// Stable machine readable error code, ex. `boss_not_found`.
+  pub error: &'static str,
+  pub code: u16,
 }
 
 impl HttpRejection {
-  fn new<S: Into<String>>(message: S, code: u16) -> Self {
+  pub fn new<S: Into<String>>(message: S, error: &'static str, code: u16) -> Self {
     Self {
       message: message.into(),
+      error,
       code,
     }
   }
@@ -105,16 +156,115 @@ impl HttpRejection {
 
 impl warp::reject::Reject for HttpRejection {}
 
+#[derive(Debug)]
 pub enum HttpError {
   CannotGetRedisKeysError,
   CannotMGetRedisError,
+  CannotReadStreamError,
+  EmptyBossNamesError,
+  BossNotFoundError,
+  InvalidCursorError,
+  MissingApiKeyError,
+  InvalidApiKeyError,
+  CannotGetApiKeyError,
+  RateLimitedError,
+  TooManyConnectionsError,
+  TooManyBossNamesError,
+  BossNamesTooLongError,
+  AdminDisabledError,
+  AdminRequiredError,
+  InvalidTranslationError,
+  TranslationNotFoundError,
+  ReviewNotFoundError,
+  TranslationConflictError,
+  TranslationLockedError,
+  TranslationChangedError,
+  TranslatorUnavailableError,
+  CannotWriteRedisError,
 }
 
 impl HttpError {
-  pub fn reject(&self) -> warp::reject::Rejection {
+  pub fn message(&self) -> &'static str {
+    match self {
+      HttpError::CannotGetRedisKeysError => "Cannot get redis keys.",
+      HttpError::CannotMGetRedisError => "Cannot mget redis values.",
+      HttpError::CannotReadStreamError => "Cannot read redis stream.",
+      HttpError::EmptyBossNamesError => "boss_names should not be empty.",
+      HttpError::BossNotFoundError => "Cannot find the boss.",
+      HttpError::InvalidCursorError => "Invalid cursor, before should be a tweet id and since should be a stream id.",
+      HttpError::MissingApiKeyError => "API key is required, send it with the x-api-key header.",
+      HttpError::InvalidApiKeyError => "API key is invalid.",
+      HttpError::CannotGetApiKeyError => "Cannot get API key.",
+      HttpError::RateLimitedError => "Too many requests, retry later.",
+      HttpError::TooManyConnectionsError => "Too many concurrent streams.",
+      HttpError::TooManyBossNamesError => "Too many boss_names for this client.",
+      HttpError::BossNamesTooLongError => "boss_names is too long.",
+      HttpError::AdminDisabledError => "Admin APIs require API key authentication to be enabled.",
+      HttpError::AdminRequiredError => "API key is not an admin key.",
+      HttpError::InvalidTranslationError => "jp_name and en_name should be different non-empty names.",
+      HttpError::TranslationNotFoundError => "The name is not translated.",
+      HttpError::ReviewNotFoundError => "The name is not waiting for review.",
+      HttpError::TranslationConflictError => "The name is already translated, override it instead.",
+      HttpError::TranslationLockedError => "The translation is locked, unlock it first.",
+      HttpError::TranslationChangedError => "The names are changed by another request, retry it.",
+      HttpError::TranslatorUnavailableError => "This process does not run the translator.",
+      HttpError::CannotWriteRedisError => "Cannot write redis values.",
+    }
+  }
+
+  /// Stable error code of the JSON error envelope, clients should match it instead of the message.
+  pub fn error(&self) -> &'static str {
     match self {
-      HttpError::CannotGetRedisKeysError => warp::reject::custom(HttpRejection::new("Cannot get redis keys.", 404)),
-      HttpError::CannotMGetRedisError => warp::reject::custom(HttpRejection::new("Cannot mget redis values.", 404)),
+      HttpError::CannotGetRedisKeysError => "cannot_get_redis_keys",
+      HttpError::CannotMGetRedisError => "cannot_mget_redis",
+      HttpError::CannotReadStreamError => "cannot_read_stream",
+      HttpError::EmptyBossNamesError => "empty_boss_names",
+      HttpError::BossNotFoundError => "boss_not_found",
+      HttpError::InvalidCursorError => "invalid_cursor",
+      HttpError::MissingApiKeyError => "missing_api_key",
+      HttpError::InvalidApiKeyError => "invalid_api_key",
+      HttpError::CannotGetApiKeyError => "cannot_get_api_key",
+      HttpError::RateLimitedError => "rate_limited",
+      HttpError::TooManyConnectionsError => "too_many_connections",
+      HttpError::TooManyBossNamesError => "too_many_boss_names",
+      HttpError::BossNamesTooLongError => "boss_names_too_long",
+      HttpError::AdminDisabledError => "admin_disabled",
+      HttpError::AdminRequiredError => "admin_required",
+      HttpError::InvalidTranslationError => "invalid_translation",
+      HttpError::TranslationNotFoundError => "translation_not_found",
+      HttpError::ReviewNotFoundError => "review_not_found",
+      HttpError::TranslationConflictError => "translation_conflict",
+      HttpError::TranslationLockedError => "translation_locked",
+      HttpError::TranslationChangedError => "translation_changed",
+      HttpError::TranslatorUnavailableError => "translator_unavailable",
+      HttpError::CannotWriteRedisError => "cannot_write_redis",
     }
   }
+
+  pub fn code(&self) -> u16 {
+    match self {
+      HttpError::EmptyBossNamesError
+      | HttpError::InvalidCursorError
+      | HttpError::BossNamesTooLongError
+      | HttpError::InvalidTranslationError => 400,
+      HttpError::MissingApiKeyError | HttpError::InvalidApiKeyError => 401,
+      HttpError::AdminDisabledError | HttpError::AdminRequiredError => 403,
+      HttpError::BossNotFoundError | HttpError::TranslationNotFoundError | HttpError::ReviewNotFoundError => 404,
+      HttpError::TranslationConflictError
+      | HttpError::TranslationLockedError
+      | HttpError::TranslationChangedError => 409,
+      HttpError::RateLimitedError | HttpError::TooManyConnectionsError | HttpError::TooManyBossNamesError => 429,
+      // Redis is unreachable or failing, clients could retry another replica.
+      HttpError::CannotGetRedisKeysError
+      | HttpError::CannotMGetRedisError
+      | HttpError::CannotReadStreamError
+      | HttpError::CannotGetApiKeyError
+      | HttpError::TranslatorUnavailableError
+      | HttpError::CannotWriteRedisError => 503,
+    }
+  }
+
+  pub fn reject(&self) -> warp::reject::Rejection {
+    warp::reject::custom(HttpRejection::new(self.message(), self.error(), self.code()))
+  }
 }
@@ -0,0 +1,58 @@
+use super::fingerprint::is_valid;
+use crate::{common::redis::gbf_image_key, proto::image_fingerprint::ImageFingerprint, Redis};
+
+use log::warn;
+use prost::Message;
+use std::sync::Arc;
+
+///
+/// Fingerprints of boss images in redis, so each image is downloaded once in `ttl` by every translator task.
+/// The cache is best effort, redis errors are logged and the image is downloaded instead.
+///
+#[derive(Clone)]
+pub struct ImageCache {
+  redis: Arc<Redis>,
+  prefix: String,
+  ttl: u32,
+}
+
+impl ImageCache {
+  pub fn new<S>(redis: Arc<Redis>, prefix: S, ttl: u32) -> Self
+  where
+    S: Into<String>,
+  {
+    Self {
+      redis,
+      prefix: prefix.into(),
+      ttl,
+    }
+  }
+
+  /// The cached fingerprint of `url`, `None` if it is not cached, cannot be read or its pixels are invalid.
+  pub async fn get(&self, url: &str) -> Option<ImageFingerprint> {
+    let bytes = match self.redis.get_bytes(gbf_image_key(&self.prefix, url)).await {
+      Ok(bytes) => bytes?,
+      Err(error) => {
+        warn!("Cannot read cached image {}, error: {}", url, error);
+        return None;
+      }
+    };
+
+    let fingerprint = ImageFingerprint::decode(bytes.as_slice())
+      .map_err(|error| warn!("Cannot parse cached image {}, error: {}", url, error))
+      .ok()?;
+    if !is_valid(&fingerprint) {
+      warn!("Cached image {} has invalid pixels, download it again.", url);
+      return None;
+    }
+
+    Some(fingerprint)
+  }
+
+  pub async fn set(&self, url: &str, fingerprint: &ImageFingerprint) {
+    let key = gbf_image_key(&self.prefix, url);
+    if let Err(error) = self.redis.set_bytes(key, fingerprint.encode_to_vec(), self.ttl).await {
+      warn!("Cannot cache image {}, error: {}", url, error);
+    }
+  }
+}
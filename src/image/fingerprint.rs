@@ -0,0 +1,177 @@
+use crate::{
+  proto::image_fingerprint::ImageFingerprint,
+  resources::translator::{FINGERPRINT_WIDTH, MIN_SHORTLIST},
+};
+
+///
+/// Fingerprint an image, the bottom quarter is cropped out since it shows the boss name of the tweet language.
+/// The rest is downscaled to `FINGERPRINT_WIDTH` for DSSIM, and hashed by the difference of adjacent pixels.
+///
+/// # Arguments
+/// * `pixels`: RGB8 pixels of the image, row by row.
+/// * `width`: width of the image.
+/// * `height`: height of the image.
+///
+pub fn fingerprint(pixels: &[u8], width: usize, height: usize) -> ImageFingerprint {
+  let height = height * 3 / 4;
+  if width == 0 || height == 0 {
+    return ImageFingerprint::default();
+  }
+  let (scaled_width, scaled_height) = match width > FINGERPRINT_WIDTH {
+    true => (FINGERPRINT_WIDTH, (height * FINGERPRINT_WIDTH / width).max(1)),
+    false => (width, height),
+  };
+  let scaled = resize(
+    &pixels[..width * height * 3],
+    width,
+    height,
+    scaled_width,
+    scaled_height,
+    3,
+  );
+  let gray = scaled
+    .chunks_exact(3)
+    .map(|rgb| ((rgb[0] as u32 * 299 + rgb[1] as u32 * 587 + rgb[2] as u32 * 114) / 1000) as u8)
+    .collect::<Vec<_>>();
+
+  ImageFingerprint {
+    hash: dhash(&resize(&gray, scaled_width, scaled_height, 9, 8, 1)),
+    width: scaled_width as u32,
+    height: scaled_height as u32,
+    pixels: scaled,
+  }
+}
+
+/// Whether `pixels` has every RGB8 pixel of the downscaled image, cached fingerprints may be truncated or corrupted.
+pub fn is_valid(fingerprint: &ImageFingerprint) -> bool {
+  fingerprint.pixels.len() == fingerprint.width as usize * fingerprint.height as usize * 3
+}
+
+/// Number of different bits of two hashes, 0 means the images look the same.
+pub fn distance(a: u64, b: u64) -> u32 {
+  (a ^ b).count_ones()
+}
+
+///
+/// Prune candidates whose hash is far from `origin`, the rest are sorted by their distance.
+/// The `MIN_SHORTLIST` closest ones are always kept, so a coarse hash would not prune the boss it is looking for.
+///
+pub fn shortlist<T, F>(origin: u64, mut candidates: Vec<T>, max_distance: u32, hash: F) -> Vec<T>
+where
+  F: Fn(&T) -> u64,
+{
+  candidates.sort_by_key(|candidate| distance(origin, hash(candidate)));
+
+  candidates
+    .into_iter()
+    .enumerate()
+    .take_while(|(index, candidate)| *index < MIN_SHORTLIST || distance(origin, hash(candidate)) <= max_distance)
+    .map(|(_, candidate)| candidate)
+    .collect()
+}
+
+/// Resize by averaging the source pixels covered by each pixel.
+fn resize(pixels: &[u8], width: usize, height: usize, new_width: usize, new_height: usize, channels: usize) -> Vec<u8> {
+  let span = |index: usize, size: usize, new_size: usize| {
+    let start = index * size / new_size;
+    (start, ((index + 1) * size / new_size).max(start + 1))
+  };
+  let mut resized = Vec::with_capacity(new_width * new_height * channels);
+  for y in 0..new_height {
+    let (top, bottom) = span(y, height, new_height);
+    for x in 0..new_width {
+      let (left, right) = span(x, width, new_width);
+      let area = ((bottom - top) * (right - left)) as u32;
+      for channel in 0..channels {
+        let sum = (top..bottom)
+          .flat_map(|row| (left..right).map(move |column| (row * width + column) * channels + channel))
+          .map(|offset| pixels[offset] as u32)
+          .sum::<u32>();
+        resized.push((sum / area) as u8);
+      }
+    }
+  }
+
+  resized
+}
+
+/// Difference hash of a 9x8 grayscale image, each bit is whether a pixel is darker than its right one.
+fn dhash(gray: &[u8]) -> u64 {
+  gray
+    .chunks_exact(9)
+    .flat_map(|row| row.windows(2).map(|pair| pair[0] < pair[1]))
+    .fold(0, |hash, bit| hash << 1 | bit as u64)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// An RGB image whose brightness is `shade(x, y)`.
+  fn image(width: usize, height: usize, shade: impl Fn(usize, usize) -> u8) -> Vec<u8> {
+    (0..height)
+      .flat_map(|y| (0..width).map(move |x| (x, y)))
+      .flat_map(|(x, y)| vec![shade(x, y); 3])
+      .collect()
+  }
+
+  #[test]
+  fn test_fingerprint() {
+    let gradient = image(320, 200, |x, _| (x * 255 / 320) as u8);
+    let fingerprint = fingerprint(&gradient, 320, 200);
+    assert_eq!((160, 75), (fingerprint.width, fingerprint.height));
+    assert_eq!(160 * 75 * 3, fingerprint.pixels.len());
+    // Each pixel of a gradient is lighter than its left one.
+    assert_eq!(u64::MAX, fingerprint.hash);
+
+    // Images are not upscaled.
+    let small = super::fingerprint(&image(90, 80, |_, _| 128), 90, 80);
+    assert_eq!((90, 60), (small.width, small.height));
+    assert_eq!(0, small.hash);
+    assert_eq!(ImageFingerprint::default(), super::fingerprint(&[], 0, 0));
+  }
+
+  #[test]
+  fn test_is_valid() {
+    let mut fingerprint = fingerprint(&image(90, 80, |_, _| 128), 90, 80);
+    assert!(is_valid(&fingerprint));
+    fingerprint.pixels.pop();
+    assert!(!is_valid(&fingerprint));
+    fingerprint.width = u32::MAX;
+    assert!(!is_valid(&fingerprint));
+  }
+
+  #[test]
+  fn test_fingerprint_crop() {
+    // The bottom quarter shows the boss name, it should not change the fingerprint.
+    let boss = |x: usize, y: usize| ((x * 7 + y * 3) % 256) as u8;
+    let japanese = image(400, 400, |x, y| if y < 300 { boss(x, y) } else { 0 });
+    let english = image(400, 400, |x, y| if y < 300 { boss(x, y) } else { 255 });
+    assert_eq!(fingerprint(&japanese, 400, 400), fingerprint(&english, 400, 400));
+  }
+
+  #[test]
+  fn test_distance() {
+    let light = fingerprint(&image(180, 160, |x, y| ((x + y) % 256) as u8), 180, 160);
+    let dark = fingerprint(&image(180, 160, |x, y| ((x + y) % 256) as u8 / 2), 180, 160);
+    let mirrored = fingerprint(&image(180, 160, |x, y| (255 - (x + y) % 256) as u8), 180, 160);
+    assert_eq!(0, distance(light.hash, dark.hash));
+    assert!(distance(light.hash, mirrored.hash) > 32);
+    assert_eq!(64, distance(0, u64::MAX));
+  }
+
+  #[test]
+  fn test_shortlist() {
+    let candidates = vec![("a", 0b1111), ("b", 0), ("c", 0b1), ("d", 0b111), ("e", u64::MAX)];
+    let names = |max_distance| {
+      shortlist(0, candidates.clone(), max_distance, |(_, hash)| *hash)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(vec!["b", "c", "d"], names(0));
+    assert_eq!(vec!["b", "c", "d", "a"], names(4));
+    assert_eq!(vec!["b", "c", "d", "a", "e"], names(64));
+    assert!(shortlist(0, Vec::<u64>::new(), 64, |hash| *hash).is_empty());
+  }
+}
@@ -1,13 +1,24 @@
-use crate::{error, Result};
+mod cache;
+mod fingerprint;
+mod ranking;
 
-use crate::proto::raid_boss_raw::RaidBossRaw;
+pub use cache::ImageCache;
+pub use ranking::{Ranking, Score, Verdict};
+
+use crate::{config::TranslationConfig, error, Result};
+
+use crate::proto::{image_fingerprint::ImageFingerprint, raid_boss_raw::RaidBossRaw};
 use dssim::{Dssim, DssimImage, ToRGBAPLU};
 use imgref::Img;
 use load_image::ImageData;
+use log::{info, warn};
+use rgb::RGB8;
 
 pub struct Comparison {
   origin: RaidBossRaw,
   competitors: Vec<RaidBossRaw>,
+  config: TranslationConfig,
+  cache: Option<ImageCache>,
   context: Dssim,
 }
 
@@ -17,17 +28,20 @@ impl Comparison {
   /// Given an origin image and bunch of contestants
   ///
   /// # Specification
-  /// 1. Get origin image from the given url.
-  /// 2. Get all competitors image from its url.
-  /// 3. Use Dssim algorithm to check whether the origin image is same as the competitor image.
-  /// 4. If the result of Dssim algorithm is lower than 0.3 (lower is better) it show that competitor is almost same as origin.
+  /// 1. Get the fingerprint of origin image from the cache, or download it and cache it.
+  /// 2. Get the fingerprints of all competitors image in the same way.
+  /// 3. Prune competitors whose image hash is far from the origin one, see `TranslationConfig.max_hash_distance`.
+  /// 4. Use Dssim algorithm to score how similar each shortlisted image is to the origin image (lower is better).
+  /// 5. Rank competitors by their scores, `Ranking::verdict` decides whether the best one is the same boss.
   ///
-  /// * Most of return values from Dssim algorithm will approach zero, except  Lv120 メドゥーサ and Lvl 120 Medusa.
-  ///   The result of  Lvl 120 Medusa will be 0.2xxxxxxx, so I choose 0.3 for insurance purposes.
+  /// * Competitors whose image cannot be loaded or compared are skipped and recorded in the ranking, the origin image
+  ///   is required.
+  /// * Images are compared at the downscaled size of their fingerprints.
   ///
   /// # Arguments
   /// * `origin`: origin raid boss that you want to pair with matchers.
   /// * `competitors`: bunch of contestants may match the origin image.
+  /// * `config`: thresholds of the translator.
   ///
   /// # Examples
   ///
@@ -50,36 +64,100 @@ impl Comparison {
   ///   r"https://pbs.twimg.com/media/Ed52ry_U0AARvyI.jpg",
   ///   Language::English,
   /// );
-  /// let comparison = Comparison::new(origin, vec![possible_1, possible_2]);
-  /// let result = comparison.compare().await.unwrap();
-  /// assert_eq!("Akasha", result.unwrap().get_boss_name()); // => "Akasha"
+  /// let comparison = Comparison::new(origin, vec![possible_1, possible_2], TranslationConfig::default())
+  ///   .with_cache(ImageCache::new(redis, &config.redis_prefix, config.image_ttl));
+  /// let ranking = comparison.compare().await.unwrap();
+  /// assert_eq!("Akasha", ranking.best().unwrap().competitor.boss_name); // => "Akasha"
   /// ```
-  pub fn new<V>(origin: RaidBossRaw, competitors: V) -> Self
+  pub fn new<V>(origin: RaidBossRaw, competitors: V, config: TranslationConfig) -> Self
   where
     V: IntoIterator<Item = RaidBossRaw>,
   {
     Self {
       origin,
       competitors: competitors.into_iter().collect::<Vec<_>>(),
+      config,
+      cache: None,
       context: Dssim::new(),
     }
   }
 
-  pub async fn compare(&self) -> Result<Option<RaidBossRaw>> {
-    let origin = self.get_image_from_url(self.origin.get_image()).await?;
+  /// Cache the fingerprints of images, otherwise every image is downloaded by each comparison.
+  pub fn with_cache(mut self, cache: ImageCache) -> Self {
+    self.cache = Some(cache);
+    self
+  }
+
+  pub async fn compare(&self) -> Result<Ranking> {
+    let origin = self.get_fingerprint(self.origin.image.as_str()).await?;
+    let mut fingerprints = Vec::with_capacity(self.competitors.len());
+    let mut skipped = vec![];
 
     for competitor in self.competitors.clone() {
-      let modified = self.get_image_from_url(competitor.get_image()).await?;
-      let comparison = self.context.compare(&origin, &modified);
-      if comparison.0 < 0.3 {
-        return Ok(Some(competitor));
+      match self.get_fingerprint(competitor.image.as_str()).await {
+        Ok(fingerprint) => fingerprints.push((competitor, fingerprint)),
+        Err(error) => {
+          warn!(
+            "Cannot load image of {}, skip it. error: {}",
+            competitor.boss_name, error
+          );
+          skipped.push(competitor);
+        }
       }
     }
 
-    Ok(None)
+    let total = fingerprints.len();
+    let max_distance = self.config.max_hash_distance;
+    let shortlist = fingerprint::shortlist(origin.hash, fingerprints, max_distance, |(_, f)| f.hash);
+    if shortlist.len() < total {
+      info!(
+        "{} of {} competitors of {} are pruned by their image hash.",
+        total - shortlist.len(),
+        total,
+        self.origin.boss_name
+      );
+    }
+
+    let origin = self.create_image(&origin)?;
+    let mut scores = Vec::with_capacity(shortlist.len());
+    for (competitor, fingerprint) in shortlist {
+      let modified = match self.create_image(&fingerprint) {
+        Ok(modified) => modified,
+        Err(error) => {
+          warn!(
+            "Cannot create image of {}, skip it. error: {}",
+            competitor.boss_name, error
+          );
+          skipped.push(competitor);
+          continue;
+        }
+      };
+      let (dssim, _) = self.context.compare(&origin, &modified);
+      scores.push(Score {
+        competitor,
+        dssim: dssim.into(),
+      });
+    }
+
+    Ok(Ranking::new(scores).with_skipped(skipped))
+  }
+
+  async fn get_fingerprint(&self, url: &str) -> Result<ImageFingerprint> {
+    if let Some(cache) = &self.cache {
+      if let Some(fingerprint) = cache.get(url).await {
+        return Ok(fingerprint);
+      }
+    }
+
+    let fingerprint = self.get_image_from_url(url).await?;
+    if let Some(cache) = &self.cache {
+      cache.set(url, &fingerprint).await;
+    }
+
+    Ok(fingerprint)
   }
 
-  async fn get_image_from_url<S>(&self, url: S) -> Result<DssimImage<f32>>
+  async fn get_image_from_url<S>(&self, url: S) -> Result<ImageFingerprint>
   where
     S: Into<String>,
   {
@@ -94,61 +172,58 @@ impl Comparison {
     let img =
       load_image::load_image_data(&buffer, false).map_err(|error| error::Error::ImageParseBytes { error })?;
 
-    self.load_image_to_image_data(img)
+    Ok(fingerprint::fingerprint(&to_rgb_bytes(&img), img.width, img.height))
   }
 
-  /// Load image from bytes and crop out the bottom 25%
-  fn load_image_to_image_data(&self, img: load_image::Image) -> Result<DssimImage<f32>> {
-    match img.bitmap {
-      ImageData::RGB8(ref bitmap) => {
-        self
-          .context
-          .create_image(&Img::new(bitmap.to_rgblu(), img.width, img.height * 3 / 4))
-      }
-      ImageData::RGB16(ref bitmap) => {
-        self
-          .context
-          .create_image(&Img::new(bitmap.to_rgblu(), img.width, img.height * 3 / 4))
-      }
-      ImageData::RGBA8(ref bitmap) => {
-        self
-          .context
-          .create_image(&Img::new(bitmap.to_rgbaplu(), img.width, img.height * 3 / 4))
-      }
-      ImageData::RGBA16(ref bitmap) => {
-        self
-          .context
-          .create_image(&Img::new(bitmap.to_rgbaplu(), img.width, img.height * 3 / 4))
-      }
-      ImageData::GRAY8(ref bitmap) => {
-        self
-          .context
-          .create_image(&Img::new(bitmap.to_rgblu(), img.width, img.height * 3 / 4))
-      }
-      ImageData::GRAY16(ref bitmap) => {
-        self
-          .context
-          .create_image(&Img::new(bitmap.to_rgblu(), img.width, img.height * 3 / 4))
-      }
-      ImageData::GRAYA8(ref bitmap) => {
-        self
-          .context
-          .create_image(&Img::new(bitmap.to_rgbaplu(), img.width, img.height * 3 / 4))
-      }
-      ImageData::GRAYA16(ref bitmap) => {
-        self
-          .context
-          .create_image(&Img::new(bitmap.to_rgbaplu(), img.width, img.height * 3 / 4))
-      }
+  fn create_image(&self, fingerprint: &ImageFingerprint) -> Result<DssimImage<f32>> {
+    if !fingerprint::is_valid(fingerprint) {
+      return Err(error::Error::ImageToImageData);
     }
-    .ok_or(error::Error::ImageToImageData)
+    let bitmap = fingerprint
+      .pixels
+      .chunks_exact(3)
+      .map(|rgb| RGB8::new(rgb[0], rgb[1], rgb[2]))
+      .collect::<Vec<_>>();
+
+    self
+      .context
+      .create_image(&Img::new(
+        bitmap.to_rgblu(),
+        fingerprint.width as usize,
+        fingerprint.height as usize,
+      ))
+      .ok_or(error::Error::ImageToImageData)
   }
 }
 
+/// RGB8 bytes of an image, alpha is dropped since boss images are opaque JPEGs.
+fn to_rgb_bytes(img: &load_image::Image) -> Vec<u8> {
+  let mut pixels = Vec::with_capacity(img.width * img.height * 3);
+  let mut push = |r: u8, g: u8, b: u8| pixels.extend_from_slice(&[r, g, b]);
+  // 16 bits channels keep their high bytes.
+  let high = |channel: u16| (channel >> 8) as u8;
+
+  match img.bitmap {
+    ImageData::RGB8(ref bitmap) => bitmap.iter().for_each(|p| push(p.r, p.g, p.b)),
+    ImageData::RGB16(ref bitmap) => bitmap.iter().for_each(|p| push(high(p.r), high(p.g), high(p.b))),
+    ImageData::RGBA8(ref bitmap) => bitmap.iter().for_each(|p| push(p.r, p.g, p.b)),
+    ImageData::RGBA16(ref bitmap) => bitmap.iter().for_each(|p| push(high(p.r), high(p.g), high(p.b))),
+    ImageData::GRAY8(ref bitmap) => bitmap.iter().for_each(|p| push(p.0, p.0, p.0)),
+    ImageData::GRAY16(ref bitmap) => bitmap.iter().for_each(|p| push(high(p.0), high(p.0), high(p.0))),
+    ImageData::GRAYA8(ref bitmap) => bitmap.iter().for_each(|p| push(p.0, p.0, p.0)),
+    ImageData::GRAYA16(ref bitmap) => bitmap.iter().for_each(|p| push(high(p.0), high(p.0), high(p.0))),
+  }
+
+  pixels
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::{models::Language, proto::raid_boss_raw::RaidBossRaw};
+  use crate::{
+    models::Language,
+    proto::raid_boss_raw::{RaidBossRaw, RaidBossRawExt},
+  };
 
   #[tokio::test]
   async fn translate_akasha_name() {
@@ -170,9 +245,10 @@ mod tests {
       r"https://pbs.twimg.com/media/DumtOgzUYAA_GD3.jpg",
       Language::English,
     );
-    let comparison = Comparison::new(origin, vec![possible_1, possible_2]);
-    let result = comparison.compare().await.unwrap();
-    assert_eq!("Lvl 200 Akasha", result.unwrap().get_boss_name()); // => "Lvl 200 Akasha"
+    let comparison = Comparison::new(origin, vec![possible_1, possible_2], TranslationConfig::default());
+    let ranking = comparison.compare().await.unwrap();
+    assert_eq!("Lvl 200 Akasha", ranking.best().unwrap().competitor.boss_name); // => "Lvl 200 Akasha"
+    assert_eq!(Verdict::Accept, ranking.verdict(&TranslationConfig::default()));
   }
 
   #[tokio::test]
@@ -195,8 +271,9 @@ mod tests {
       r"https://pbs.twimg.com/media/DZVlpmXU8AEbF6G.jpg",
       Language::English,
     );
-    let comparison = Comparison::new(origin, vec![possible_1, possible_2]);
-    let result = comparison.compare().await.unwrap();
-    assert_eq!("Lvl 120 Medusa", result.unwrap().get_boss_name()); // => "Lvl 120 Medusa"
+    let comparison = Comparison::new(origin, vec![possible_1, possible_2], TranslationConfig::default());
+    let ranking = comparison.compare().await.unwrap();
+    assert_eq!("Lvl 120 Medusa", ranking.best().unwrap().competitor.boss_name); // => "Lvl 120 Medusa"
+    assert_eq!(Verdict::Accept, ranking.verdict(&TranslationConfig::default()));
   }
 }
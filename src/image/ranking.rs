@@ -0,0 +1,184 @@
+use crate::{
+  config::TranslationConfig,
+  proto::{raid_boss_raw::RaidBossRaw, translation::Translation},
+};
+
+/// DSSIM of a competitor against the origin image, lower is more similar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Score {
+  pub competitor: RaidBossRaw,
+  pub dssim: f64,
+}
+
+///
+/// What the translator should do with the best match of a `Ranking`.
+///
+/// * `Accept` - pair the names at once.
+/// * `Review` - queue the match for an admin to accept or reject it.
+/// * `Reject` - no competitor is similar enough, the boss is not translated.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+  Accept,
+  Review,
+  Reject,
+}
+
+/// Scores of every competitor, the most similar first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ranking {
+  pub scores: Vec<Score>,
+  /// Competitors whose image cannot be loaded, any of them could be the boss.
+  pub skipped: Vec<RaidBossRaw>,
+}
+
+impl Ranking {
+  pub fn new(mut scores: Vec<Score>) -> Self {
+    scores.sort_by(|a, b| a.dssim.partial_cmp(&b.dssim).unwrap_or(std::cmp::Ordering::Equal));
+
+    Ranking {
+      scores,
+      skipped: vec![],
+    }
+  }
+
+  /// Record the competitors which are not scored.
+  pub fn with_skipped(mut self, skipped: Vec<RaidBossRaw>) -> Self {
+    self.skipped = skipped;
+    self
+  }
+
+  pub fn best(&self) -> Option<&Score> {
+    self.scores.first()
+  }
+
+  pub fn runner_up(&self) -> Option<&Score> {
+    self.scores.get(1)
+  }
+
+  /// DSSIM of the runner-up minus the best one, zero if there is no runner-up.
+  pub fn margin(&self) -> f64 {
+    match (self.best(), self.runner_up()) {
+      (Some(best), Some(runner_up)) => runner_up.dssim - best.dssim,
+      _ => 0.0,
+    }
+  }
+
+  ///
+  /// Accept the best match only if it is a clear winner, its DSSIM is low and no other competitor is close to it.
+  /// A single competitor is accepted by its DSSIM alone, there is no other boss of its level to confuse it with.
+  /// If any competitor is skipped, it may be a closer one, so the best match is always reviewed.
+  ///
+  pub fn verdict(&self, config: &TranslationConfig) -> Verdict {
+    if !self.skipped.is_empty() {
+      return Verdict::Review;
+    }
+    let is_clear = self.runner_up().is_none() || self.margin() >= config.min_margin;
+
+    match self.best() {
+      Some(best) if best.dssim < config.accept_dssim && is_clear => Verdict::Accept,
+      Some(best) if best.dssim < config.review_dssim => Verdict::Review,
+      _ => Verdict::Reject,
+    }
+  }
+
+  /// The best match of `origin` with its scores, `None` if there is no competitor.
+  pub fn translation(&self, origin: &RaidBossRaw) -> Option<Translation> {
+    let best = self.best()?;
+
+    Some(Translation {
+      boss_name: origin.boss_name.clone(),
+      language: origin.language.clone(),
+      translated_name: best.competitor.boss_name.clone(),
+      level: origin.level,
+      image: origin.image.clone(),
+      score: best.dssim,
+      margin: self.margin(),
+      runner_up: self
+        .runner_up()
+        .map(|runner_up| runner_up.competitor.boss_name.clone())
+        .unwrap_or_default(),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{models::Language, proto::raid_boss_raw::RaidBossRawExt};
+
+  fn ranking(scores: &[(&str, f64)]) -> Ranking {
+    Ranking::new(
+      scores
+        .iter()
+        .map(|(boss_name, dssim)| Score {
+          competitor: RaidBossRaw::apply_args(*boss_name, 120, "", Language::English),
+          dssim: *dssim,
+        })
+        .collect(),
+    )
+  }
+
+  #[test]
+  fn test_verdict() {
+    let config = TranslationConfig::default();
+    let ranking = ranking(&[("Lvl 120 Metatron", 0.45), ("Lvl 120 Medusa", 0.22)]);
+    assert_eq!("Lvl 120 Medusa", ranking.best().unwrap().competitor.boss_name);
+    assert!((ranking.margin() - 0.23).abs() < 1e-9);
+    assert_eq!(Verdict::Accept, ranking.verdict(&config));
+
+    // A close runner-up makes the best match ambiguous.
+    let ranking = self::ranking(&[("Lvl 120 Medusa", 0.22), ("Lvl 120 Metatron", 0.25)]);
+    assert_eq!(Verdict::Review, ranking.verdict(&config));
+    assert_eq!(
+      Verdict::Review,
+      self::ranking(&[("Lvl 120 Medusa", 0.4)]).verdict(&config)
+    );
+    assert_eq!(
+      Verdict::Accept,
+      self::ranking(&[("Lvl 120 Medusa", 0.01)]).verdict(&config)
+    );
+    assert_eq!(
+      Verdict::Reject,
+      self::ranking(&[("Lvl 120 Medusa", 0.6)]).verdict(&config)
+    );
+    assert_eq!(Verdict::Reject, self::ranking(&[]).verdict(&config));
+  }
+
+  #[test]
+  fn test_verdict_skipped() {
+    let config = TranslationConfig::default();
+    let skipped = vec![RaidBossRaw::apply_args("Lvl 120 Metatron", 120, "", Language::English)];
+    let ranking = ranking(&[("Lvl 120 Medusa", 0.01)]).with_skipped(skipped.clone());
+    assert_eq!(skipped, ranking.skipped);
+    assert_eq!(Verdict::Review, ranking.verdict(&config));
+    assert_eq!(
+      Verdict::Review,
+      self::ranking(&[("Lvl 120 Medusa", 0.6)])
+        .with_skipped(skipped)
+        .verdict(&config)
+    );
+  }
+
+  #[test]
+  fn test_translation() {
+    let origin = RaidBossRaw::apply_args("Lv120 メドゥーサ", 120, "origin.jpg", Language::Japanese);
+    assert_eq!(None, ranking(&[]).translation(&origin));
+    let translation = ranking(&[("Lvl 120 Medusa", 0.22)]).translation(&origin).unwrap();
+    assert_eq!(
+      ("Lvl 120 Medusa", 0.0, ""),
+      (
+        translation.translated_name.as_str(),
+        translation.margin,
+        translation.runner_up.as_str()
+      )
+    );
+
+    let translation = ranking(&[("Lvl 120 Metatron", 0.25), ("Lvl 120 Medusa", 0.22)])
+      .translation(&origin)
+      .unwrap();
+    assert_eq!("Lv120 メドゥーサ", translation.boss_name);
+    assert_eq!("Lvl 120 Metatron", translation.runner_up);
+    assert_eq!(0.22, translation.score);
+  }
+}
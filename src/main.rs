@@ -14,27 +14,38 @@ mod tasks;
 use crate::{
   client::redis::Redis,
   client::{filter_stream::StreamingSource, http::FilterStreamClient},
-  common::redis::get_translator_map,
-  config::Config,
+  common::{
+    migration::{check_schema_version, migrate},
+    health::Health,
+    redis::{get_translation_reviews, get_translator_map},
+    shutdown::{trigger_on_signal, Shutdown},
+    stats::Stats,
+    translator_map::TranslatorPairs,
+  },
+  config::{Config, Role},
   models::{TranslatorResult, Tweet},
   proto::{raid_boss_raw::RaidBossRaw, raid_tweet::RaidTweet},
   resources::http::STREAM_URL,
-  server::{client::FinderClient, http::create_http_server},
+  server::{
+    auth::Auth, grpc::create_grpc_server, http::create_http_server, registry::ClientRegistry, throttle::Throttle,
+  },
   tasks::tweet::TweetActorHandle,
 };
 use futures::{TryStreamExt, TryFutureExt};
 use futures_retry::{FutureRetry, RetryPolicy};
 use log::{error as log_error, info};
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::RwLock;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{sync::RwLock, time::timeout_at};
 use tokio_stream::StreamExt;
 
-pub type FinderClients = Arc<RwLock<HashMap<String, FinderClient>>>;
+pub type FinderClients = Arc<RwLock<ClientRegistry>>;
+/// Boss names to their translated names, see `TranslatorPairs`.
+pub type TranslatorMap = Arc<TranslatorPairs>;
 pub type Result<T, E = error::Error> = std::result::Result<T, E>;
 
 #[tokio::main]
 pub async fn main() -> Result<()> {
-  let config = Config::new()?;
+  let config = Arc::new(Config::new()?);
 
   logger::create_logger(config.log_path.as_str(), "raid-finder-stream", 3)?;
 
@@ -43,27 +54,106 @@ pub async fn main() -> Result<()> {
 
   let redis = Arc::new(redis);
 
+  // `raid-finder migrate` rewrites existing keys into the current layout and exits.
+  if std::env::args().nth(1).as_deref() == Some("migrate") {
+    let from = migrate(&*redis, &config.redis_prefix).await?;
+    info!(
+      "Redis namespace {} is migrated from schema version {} to {}.",
+      config.redis_prefix,
+      from,
+      resources::redis::CURRENT_SCHEMA_VERSION
+    );
+    return Ok(());
+  }
+
+  // Migrate data written by older binaries, refuse to run against data written by newer ones.
+  check_schema_version(&*redis, &config.redis_prefix).await?;
+
   // Create twitter filter stream client
   let filter_stream_client = FilterStreamClient::new(
-    config,
+    (*config).clone(),
     vec!["参加者募集！", ":参戦ID", "I need backup!", ":Battle ID"],
     "true",
   );
 
-  // Create an empty client map
-  let finder_clients: FinderClients = Arc::new(RwLock::new(HashMap::new()));
+  // Create an empty client registry
+  let finder_clients: FinderClients = Arc::new(RwLock::new(ClientRegistry::new()));
+  let stats = Arc::new(Stats::new());
+  let health = Arc::new(Health::new());
+  // Shut down gracefully on SIGTERM/SIGINT
+  let shutdown = Arc::new(Shutdown::new(Duration::from_secs(config.shutdown_timeout)));
+  trigger_on_signal(shutdown.clone());
+  // Initialize translator map with redis keys `{prefix}:translator:*`, only the ingesting process translates.
+  // Names waiting for review stay pending, so they are not matched again.
+  let translator_map: Option<TranslatorMap> = match config.role.ingests() {
+    true => {
+      let mut map = get_translator_map(&config.redis_prefix, &redis)
+        .await
+        .unwrap_or_else(|_| HashMap::new());
+      for review in get_translation_reviews(&config.redis_prefix, &redis).await.unwrap_or_default() {
+        map.entry(review.boss_name).or_default();
+      }
+      Some(Arc::new(TranslatorPairs::new(map)))
+    }
+    false => None,
+  };
+  // API keys and client IP limits are shared by http and gRPC, so they count the calls of both.
+  let auth = Arc::new(Auth::new(&config, redis.clone())?);
+  let throttle = Arc::new(Throttle::new(&config));
   // Create http/ws server
-  create_http_server(redis.clone(), finder_clients.clone());
+  create_http_server(
+    config.clone(),
+    redis.clone(),
+    finder_clients.clone(),
+    stats.clone(),
+    health.clone(),
+    shutdown.clone(),
+    translator_map.clone(),
+    auth.clone(),
+    throttle.clone(),
+  )?;
+  // Create gRPC server
+  create_grpc_server(
+    config.clone(),
+    redis.clone(),
+    finder_clients.clone(),
+    stats.clone(),
+    health.clone(),
+    shutdown.clone(),
+    auth,
+    throttle,
+  );
+
+  // Server role does not connect to twitter, it only fans out raid tweets published by the ingester.
+  if !config.role.ingests() {
+    let result = tokio::select! {
+      result = tasks::publisher::subscribe_raid_tweets(config.clone(), redis.clone(), finder_clients.clone()) => result,
+      _ = shutdown.triggered() => Ok(()),
+    };
 
-  // Initialize translator map with redis keys `gbf:translator:*`
-  let translator_map = get_translator_map(&redis).await.unwrap_or_else(|_| HashMap::new());
-  // Create tweet handler to consuming incoming stream
-  let tweet_handler = TweetActorHandle::new(redis, translator_map);
+    return shutdown_gracefully(&shutdown, &finder_clients, result).await;
+  }
 
-  FutureRetry::new(
+  // Create tweet handler to consuming incoming stream, it shares the translator map with the admin APIs.
+  let tweet_handler = TweetActorHandle::new(
+    config.clone(),
+    redis.clone(),
+    translator_map.unwrap_or_default(),
+    stats.clone(),
+    health.clone(),
+    shutdown.clone(),
+  );
+
+  let ingest = FutureRetry::new(
     || async {
+      if shutdown.is_triggered() {
+        return Ok(());
+      }
       // Get tweet stream source from STREAM_URL
       let stream: StreamingSource<Tweet> = filter_stream_client.oauth_stream(STREAM_URL).await?;
+      health.set_stream_connected(true);
+      // Stop reading tweets once shutdown is triggered, the tweet in flight still goes through the pipeline.
+      let stream = futures::StreamExt::take_until(stream, shutdown.triggered());
 
       let tweet_stream = stream
         .and_then(|tweet| tweet_handler.parse_tweet(tweet))
@@ -77,6 +167,7 @@ pub async fn main() -> Result<()> {
             tweet_handler.translate_tweet(raid_boss_raw, raid_tweet, translator_result)
           },
         )
+        .and_then(|raid_tweet| tweet_handler.deduplicate_raid_tweet(raid_tweet))
         .and_then(|raid_tweet| tweet_handler.persist_raid_tweet(raid_tweet))
         .timeout(std::time::Duration::new(5, 0));
 
@@ -86,7 +177,13 @@ pub async fn main() -> Result<()> {
       while let Some(Ok(chunk)) = tweet_stream.next().await {
         match chunk {
           Ok(raid_tweet) => {
-            tasks::websocket::sending_message_to_websocket_client(raid_tweet, finder_clients.clone());
+            Stats::increase(&stats.published_tweets);
+            match config.role {
+              Role::Ingester => {
+                tasks::publisher::publish_raid_tweet(raid_tweet, config.clone(), redis.clone(), &shutdown)
+              }
+              _ => tasks::websocket::sending_message_to_websocket_client(raid_tweet, finder_clients.clone()).await,
+            }
           }
           // Only if we get StreamUnexpected/StreamEOF/BadResponse should reconnect the stream.
           // Otherwise we will skip the tweet.
@@ -99,28 +196,61 @@ pub async fn main() -> Result<()> {
         };
       }
 
+      if shutdown.is_triggered() {
+        // Dropping the stream closes the connection to twitter.
+        health.set_stream_connected(false);
+        return Ok(());
+      }
+
       Err::<(), error::Error>(error::Error::StreamUnexpected)
     },
-    |e: error::Error| match e {
-      error::Error::StreamUnexpected => {
-        info!("Get unexpected error while streaming tweets will restart in 5 second.");
-        RetryPolicy::WaitRetry(std::time::Duration::from_secs(5))
-      }
-      error::Error::BadResponse => {
-        info!("Get bad response when connecting to twitter stream api will restart in 5 second.");
-        RetryPolicy::WaitRetry(std::time::Duration::from_secs(5))
-      }
-      error::Error::StreamEOF => {
-        info!("Get EOF in twitter stream api will restart in 1 second.");
-        RetryPolicy::WaitRetry(std::time::Duration::from_secs(1))
-      }
-      _ => {
-        log_error!("Some error encounter, error: {:?}", e);
-        RetryPolicy::ForwardError(e)
+    |e: error::Error| {
+      health.set_stream_connected(false);
+      match e {
+        error::Error::StreamUnexpected => {
+          info!("Get unexpected error while streaming tweets will restart in 5 second.");
+          RetryPolicy::WaitRetry(std::time::Duration::from_secs(5))
+        }
+        error::Error::BadResponse => {
+          info!("Get bad response when connecting to twitter stream api will restart in 5 second.");
+          RetryPolicy::WaitRetry(std::time::Duration::from_secs(5))
+        }
+        error::Error::StreamEOF => {
+          info!("Get EOF in twitter stream api will restart in 1 second.");
+          RetryPolicy::WaitRetry(std::time::Duration::from_secs(1))
+        }
+        _ => {
+          log_error!("Some error encounter, error: {:?}", e);
+          RetryPolicy::ForwardError(e)
+        }
       }
     },
   )
-  .await
-  .map(|result| result.0)
-  .map_err(|error| error.0)
+  .map_ok(|result| result.0)
+  .map_err(|error| error.0);
+  tokio::pin!(ingest);
+
+  let result = tokio::select! {
+    result = &mut ingest => result,
+    // Wait for the tweet in flight, unless the stream is stuck until the deadline.
+    _ = shutdown.triggered() => timeout_at(shutdown.deadline(), ingest).await.unwrap_or(Ok(())),
+  };
+
+  shutdown_gracefully(&shutdown, &finder_clients, result).await
+}
+
+///
+/// Disconnect every client and wait for pending tasks until the shutdown deadline.
+/// The process also shuts down when the tweet stream stops with an error, `result` is returned afterwards.
+///
+async fn shutdown_gracefully(shutdown: &Shutdown, finder_clients: &FinderClients, result: Result<()>) -> Result<()> {
+  shutdown.trigger();
+  finder_clients.read().await.close_all();
+  info!("Shutting down, waiting for clients and pending tasks...");
+  match shutdown.drain().await {
+    true => info!("Shutdown gracefully."),
+    false => log_error!("Shutdown deadline is exceeded, pending tasks are dropped."),
+  }
+
+  result
 }
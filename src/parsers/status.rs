@@ -1,6 +1,9 @@
 use crate::{
   models::{Language, Tweet},
-  proto::{raid_boss_raw::RaidBossRaw, raid_tweet::RaidTweet},
+  proto::{
+    raid_boss_raw::{RaidBossRaw, RaidBossRawExt},
+    raid_tweet::{RaidTweet, RaidTweetExt},
+  },
 };
 
 use lazy_static::lazy_static;
@@ -43,17 +46,13 @@ impl StatusParser {
   fn match_raid(raid_cap: Captures, tweet: &Tweet, language: Language) -> Option<(RaidBossRaw, RaidTweet)> {
     let boss_name = raid_cap["boss"].to_owned();
     let mut level = 0;
-    let mut raid_boss = RaidBossRaw::new();
     if let Some(boss_cap) = BOSS_REGEX.captures(&raid_cap["boss"]) {
       level = boss_cap["level"].parse::<i32>().unwrap_or(0);
     }
-    raid_boss.set_boss_name(boss_name.clone());
-    raid_boss.set_level(level);
-    raid_boss.set_language(language.to_string());
 
     match Self::get_media_image_by_tweet(tweet) {
       Some(image) => {
-        raid_boss.set_image(image);
+        let raid_boss = RaidBossRaw::apply_args(boss_name.as_str(), level, image, language);
         let created = tweet.timestamp_ms.parse::<u64>().unwrap();
         let raid_tweet = RaidTweet::apply_args(
           tweet.id,
@@ -96,9 +95,9 @@ mod tests {
       },
     };
     let raid_boss = StatusParser::parse(tweet).unwrap().0;
-    assert_eq!("Lv150 プロトバハムート", raid_boss.get_boss_name());
-    assert_eq!(150, raid_boss.get_level());
-    assert_eq!("https://pbs.twimg.com/media/CdL4WyxUYAIXPb8.jpg", raid_boss.get_image());
-    assert_eq!(Language::Japanese.to_string(), raid_boss.get_language());
+    assert_eq!("Lv150 プロトバハムート", raid_boss.boss_name);
+    assert_eq!(150, raid_boss.level);
+    assert_eq!("https://pbs.twimg.com/media/CdL4WyxUYAIXPb8.jpg", raid_boss.image);
+    assert_eq!(Language::Japanese.to_string(), raid_boss.language);
   }
 }
@@ -0,0 +1 @@
+pub use super::messages::ImageFingerprint;
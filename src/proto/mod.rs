@@ -1,3 +1,112 @@
 pub mod raid_boss_raw;
 pub mod raid_tweet;
-pub mod raid_boss;
\ No newline at end of file
+pub mod raid_boss;
+pub mod translation;
+pub mod image_fingerprint;
+
+use crate::{error, Result};
+
+/// Messages generated from `proto/messages/*.proto` by `build.rs`.
+pub mod messages {
+  tonic::include_proto!("raid_finder.messages");
+}
+
+/// gRPC service generated from `proto/services/*.proto` by `build.rs`.
+pub mod services {
+  tonic::include_proto!("raid_finder.services");
+}
+
+pub trait MessageExt: prost::Message + Sized {
+  fn to_bytes(&self) -> Result<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(self.encoded_len());
+
+    self
+      .encode(&mut bytes)
+      .map_err(|error| error::Error::ProtobufWrite { error })?;
+
+    Ok(bytes)
+  }
+}
+
+impl<M: prost::Message> MessageExt for M {}
+
+/// Persisted values might be expired or corrupted, skip them like the websocket client does.
+/// An expired value is empty, it would be decoded as a default message.
+pub fn decode_all<M: prost::Message + Default>(bytes: Vec<Vec<u8>>) -> Vec<M> {
+  bytes
+    .iter()
+    .filter(|bytes| !bytes.is_empty())
+    .filter_map(|bytes| M::decode(bytes.as_slice()).ok())
+    .collect()
+}
+
+///
+/// Serialize an u64 into a JSON string,
+/// tweet ids are beyond the safe integer range of javascript so they cannot be JSON numbers.
+///
+pub fn serialize_u64_as_string<S: serde::Serializer>(value: &u64, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+  serializer.collect_str(value)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{decode_all, raid_boss::RaidBoss, raid_boss_raw::RaidBossRaw, raid_tweet::RaidTweet, MessageExt};
+  use prost::Message;
+
+  // Fixtures are encoded from `proto/fixtures/*.txtpb` with the schema, ex.
+  // protoc -I proto/messages --encode=raid_finder.messages.RaidTweet raid_tweet.proto \
+  //   < proto/fixtures/raid_tweet.txtpb > proto/fixtures/raid_tweet.bin
+  const RAID_TWEET: &[u8] = include_bytes!("../../proto/fixtures/raid_tweet.bin");
+  const RAID_BOSS: &[u8] = include_bytes!("../../proto/fixtures/raid_boss.bin");
+  const RAID_BOSS_RAW: &[u8] = include_bytes!("../../proto/fixtures/raid_boss_raw.bin");
+
+  #[test]
+  fn test_raid_tweet_fixture() {
+    let raid_tweet = RaidTweet::decode(RAID_TWEET).unwrap();
+    let expected = RaidTweet {
+      tweet_id: 1391331698651443207,
+      screen_name: "hank121314".into(),
+      boss_name: "Lv200 アーカーシャ".into(),
+      raid_id: "E8A4B1C2".into(),
+      text: "参加者募集！".into(),
+      created: 1620698515453,
+      language: "Japanese".into(),
+      profile_image: "https://pbs.twimg.com/profile_images/1/normal.jpg".into(),
+    };
+    assert_eq!(expected, raid_tweet);
+    assert_eq!(RAID_TWEET, expected.to_bytes().unwrap().as_slice());
+  }
+
+  #[test]
+  fn test_raid_boss_fixture() {
+    let raid_boss = RaidBoss::decode(RAID_BOSS).unwrap();
+    let expected = RaidBoss {
+      en_name: "Lvl 200 Akasha".into(),
+      jp_name: "Lv200 アーカーシャ".into(),
+      level: 200,
+      image: "https://pbs.twimg.com/media/DumtNdnUYAE9PCr.jpg".into(),
+    };
+    assert_eq!(expected, raid_boss);
+    assert_eq!(RAID_BOSS, expected.to_bytes().unwrap().as_slice());
+  }
+
+  #[test]
+  fn test_raid_boss_raw_fixture() {
+    let raid_boss_raw = RaidBossRaw::decode(RAID_BOSS_RAW).unwrap();
+    let expected = RaidBossRaw {
+      boss_name: "Lv150 プロトバハムート".into(),
+      level: 150,
+      image: "https://pbs.twimg.com/media/CdL4WyxUYAIXPb8.jpg".into(),
+      language: "Japanese".into(),
+    };
+    assert_eq!(expected, raid_boss_raw);
+    assert_eq!(RAID_BOSS_RAW, expected.to_bytes().unwrap().as_slice());
+  }
+
+  #[test]
+  fn test_decode_all() {
+    let raid_tweet = RaidTweet::decode(RAID_TWEET).unwrap();
+    let tweets = decode_all::<RaidTweet>(vec![RAID_TWEET.to_vec(), vec![0xff], vec![]]);
+    assert_eq!(vec![raid_tweet], tweets);
+  }
+}
@@ -0,0 +1,18 @@
+use crate::models::Language;
+use std::str::FromStr;
+
+pub use super::messages::Translation;
+
+pub trait TranslationExt {
+  /// Japanese and english names of the pair.
+  fn names(&self) -> (&str, &str);
+}
+
+impl TranslationExt for Translation {
+  fn names(&self) -> (&str, &str) {
+    match Language::from_str(self.language.as_str()) {
+      Ok(Language::English) => (self.translated_name.as_str(), self.boss_name.as_str()),
+      _ => (self.boss_name.as_str(), self.translated_name.as_str()),
+    }
+  }
+}
@@ -5,6 +5,13 @@ pub const SHORTHAND_JAPANESE: &str = "jp";
 
 pub const SHORTHAND_ENGLISH: &str = "en";
 
+pub const DEFAULT_HTTP_ADDR: &str = "0.0.0.0:50051";
+
+pub const DEFAULT_GRPC_ADDR: &str = "0.0.0.0:50052";
+
+/// Seconds the process could take to shut down, it should be shorter than `terminationGracePeriodSeconds` of the pod.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 25;
+
 pub mod http {
   pub const STREAM_URL: &str = "https://stream.twitter.com/1.1/statuses/filter.json";
 
@@ -18,18 +25,131 @@ pub mod ws {
   /// Custom ping pong is not the recommended way to handle this situation.
   pub const PING: &str = "__PING__";
   pub const PONG: &str = "__PONG__";
+  /// Max raid tweets replayed per boss when a client subscribes with a `since` marker.
+  pub const MAX_REPLAY_TWEETS: usize = 100;
+  /// Default max messages queued for a websocket client.
+  pub const DEFAULT_CLIENT_QUEUE_CAPACITY: usize = 256;
+  /// Close code(policy violation) sent to a client which is disconnected because its queue is full.
+  pub const SLOW_CONSUMER_CLOSE_CODE: u16 = 1008;
+  /// Close code(policy violation) sent to a protocol 1 client which subscribes more boss names than its API key allows.
+  pub const TOO_MANY_BOSS_NAMES_CLOSE_CODE: u16 = 1008;
+  /// A websocket send which takes longer than this is considered a stalled client.
+  pub const SEND_TIMEOUT_SECS: u64 = 10;
+  /// Close code(going away) sent to every client when the server is shutting down.
+  pub const GOING_AWAY_CLOSE_CODE: u16 = 1001;
+}
+
+pub mod auth {
+  /// Header of the API key, browsers cannot set headers of websocket and EventSource requests, so streams also accept
+  /// the `api_key` query parameter, and websockets the `{API_KEY_PROTOCOL_PREFIX}{key}` subprotocol.
+  pub const API_KEY_HEADER: &str = "x-api-key";
+  /// Subprotocols of websockets, the API key is sent next to `API_KEY_PROTOCOL` which is selected by the server.
+  /// Unlike the query string, they are not written to access logs.
+  pub const API_KEY_PROTOCOL: &str = "raid-finder";
+  pub const API_KEY_PROTOCOL_PREFIX: &str = "raid-finder.api-key.";
+  /// Default limits of an API key which does not set its own, `0` means unlimited.
+  pub const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+  pub const DEFAULT_MAX_BOSS_NAMES: u32 = 100;
+  pub const DEFAULT_REQUESTS_PER_MINUTE: u32 = 600;
+}
+
+pub mod throttle {
+  /// Default limits of each client IP, `0` means unlimited.
+  pub const DEFAULT_IP_REQUESTS_PER_MINUTE: u32 = 1200;
+  pub const DEFAULT_IP_MAX_CONNECTIONS: u32 = 20;
+  /// Max boss names of a request, or of a stream without API key.
+  pub const MAX_BOSS_NAMES: usize = 100;
+  /// Max raid tweets of each boss returned by `/get_persistence_boss`.
+  pub const MAX_PERSISTENCE_LIMIT: u32 = 100;
+}
+
+pub mod web {
+  /// Seconds browsers could reuse the built-in web UI before revalidating it with its `ETag`.
+  pub const ASSET_MAX_AGE: u32 = 300;
+}
+
+pub mod cors {
+  pub const DEFAULT_ALLOWED_METHODS: &[&str] = &["GET", "POST"];
+  pub const DEFAULT_ALLOWED_HEADERS: &[&str] = &["accept", "content-type", "if-none-match", "last-event-id", "x-api-key"];
+  /// Seconds browsers could cache a preflight response.
+  pub const MAX_AGE_SECS: u32 = 600;
+}
+
+pub mod translator {
+  /// Matches with lower DSSIM are accepted if no other boss is close, `Lvl 120 Medusa` scores `0.2x` against its
+  /// japanese boss while most bosses approach zero.
+  pub const DEFAULT_ACCEPT_DSSIM: f64 = 0.3;
+  /// The runner-up should be this much less similar than the best match to accept it.
+  pub const DEFAULT_MIN_MARGIN: f64 = 0.1;
+  /// Matches which are not accepted are reviewed if their DSSIM is lower, otherwise the boss is not translated.
+  pub const DEFAULT_REVIEW_DSSIM: f64 = 0.5;
+  /// Competitors whose dHash differs from the origin in more bits are not compared with DSSIM.
+  pub const DEFAULT_MAX_HASH_DISTANCE: u32 = 20;
+  /// The closest competitors are always compared with DSSIM however far their hashes are, dHash is coarse.
+  pub const MIN_SHORTLIST: usize = 3;
+  /// Boss images are downscaled to this width before they are cached and compared.
+  pub const FINGERPRINT_WIDTH: usize = 160;
+}
+
+pub mod health {
+  /// Raid tweets are posted every few seconds, the stream is considered stalled without a parsed tweet for this long.
+  pub const MAX_TWEET_IDLE_SECS: u64 = 300;
+  /// Readiness fails if redis does not answer `PING` in time.
+  pub const REDIS_PING_TIMEOUT_SECS: u64 = 2;
 }
 
 pub mod redis {
-  pub const GBF_PREFIX: &str = "gbf";
+  pub const DEFAULT_PREFIX: &str = "gbf";
 
   pub const BOSS_KEY_WORD: &str = "boss";
 
   pub const PERSISTENCE_KEY_WORD: &str = "persistence";
 
-  pub const GBF_TRANSLATOR_KEY: &str = "gbf:translator";
+  pub const TRANSLATOR_KEY_WORD: &str = "translator";
+
+  pub const TRANSLATOR_LOCK_KEY_WORD: &str = "translator_lock";
+
+  pub const TRANSLATION_KEY_WORD: &str = "translation";
+
+  pub const TRANSLATION_REVIEW_KEY_WORD: &str = "translation_review";
+
+  pub const IMAGE_KEY_WORD: &str = "image";
+
+  pub const DEDUP_KEY_WORD: &str = "dedup";
+
+  pub const RAID_TWEETS_CHANNEL_KEY_WORD: &str = "raid_tweets";
+
+  pub const STREAM_KEY_WORD: &str = "stream";
+
+  /// Field name of the protobuf encoded raid tweet in each stream entry.
+  pub const STREAM_TWEET_FIELD: &str = "tweet";
+
+  pub const API_KEY_KEY_WORD: &str = "api_key";
+
+  pub const SCHEMA_VERSION_KEY_WORD: &str = "schema_version";
+
+  /// Version of the key layout written by this binary, see `common::migration`.
+  pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
+  /// Keys returned by each `SCAN` call.
+  pub const SCAN_COUNT: usize = 1000;
+
+  /// `RENAME` fails if the source key does not exist, ex. it expires after it is read.
+  pub const RENAME_IF_EXISTS_SCRIPT: &str = r"
+    if redis.call('EXISTS', KEYS[1]) == 0 then
+      return 0
+    end
+    redis.call('RENAME', KEYS[1], KEYS[2])
+    return 1
+  ";
+
+  pub const DEFAULT_BOSS_TTL: u32 = 2592000;
+
+  pub const DEFAULT_TWEET_TTL: u32 = 7200;
+
+  pub const DEFAULT_RAID_DEDUP_TTL: u32 = 600;
 
-  pub const BOSS_EXPIRE_IN_30_DAYS_TTL: u32 = 2592000;
+  pub const DEFAULT_IMAGE_TTL: u32 = 604800;
 
-  pub const TWEET_PERSISTENCE_ONLY_2_HOURS_TTL: u32 = 7200;
+  pub const DEFAULT_TWEET_STREAM_MAX_LEN: usize = 10000;
 }
\ No newline at end of file
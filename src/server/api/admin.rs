@@ -0,0 +1,276 @@
+use crate::{
+  common::redis::{
+    gbf_raid_boss_keys, gbf_raid_boss_name_keys, gbf_raid_boss_raw_name_keys, gbf_translation_key,
+    gbf_translation_review_key, gbf_translator_lock_key, get_translation_reviews, get_translator_map,
+  },
+  error::{Error, HttpError},
+  models::Language,
+  proto::{
+    raid_boss::RaidBoss,
+    raid_boss_raw::RaidBossRaw,
+    translation::{Translation, TranslationExt},
+  },
+  server::{
+    state::AppState,
+    translator::{self, Change, Snapshot, TranslationPair},
+  },
+  Redis, Result,
+};
+use log::{error, info};
+use prost::Message;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use warp::{http::StatusCode, Reply};
+
+#[derive(Deserialize)]
+pub struct TranslationQuery {
+  pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct LockRequest {
+  pub name: String,
+  pub locked: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ReviewRequest {
+  pub name: String,
+}
+
+///
+/// List translator pairs in redis, sorted by their japanese names.
+///
+/// # Example
+///
+/// ```
+/// [{"jp_name": "Lv200 アーカーシャ", "en_name": "Lvl 200 Akasha", "locked": false, "score": 0.01, "margin": 0.4}]
+/// ```
+pub async fn list_translations(app_state: AppState) -> Result<impl warp::Reply, warp::Rejection> {
+  let (redis, prefix) = (&app_state.redis, app_state.config.redis_prefix.as_str());
+  let read = async {
+    let map = get_translator_map(prefix, redis).await?;
+    let locked = locked_names(redis, prefix).await?;
+    let boss_prefix = gbf_raid_boss_keys(prefix, 0).replace("*.*", "");
+    let jp_names = redis
+      .keys(gbf_raid_boss_keys(prefix, 0))
+      .await?
+      .into_iter()
+      .filter_map(|key| Some(key.strip_prefix(&boss_prefix)?.split_once('.')?.1.to_owned()))
+      .collect::<HashSet<_>>();
+    let translation_keys = redis.keys(gbf_translation_key(prefix, "*")).await?;
+    let translations = redis
+      .mget_protobuf::<Translation, _, _>(translation_keys)
+      .await?
+      .into_iter()
+      .map(|translation| (translation.names().0.to_owned(), translation))
+      .collect::<HashMap<_, _>>();
+
+    Ok::<_, Error>(translator::pairs(&map, &locked, &jp_names, &translations))
+  };
+  let pairs = read.await.map_err(|e| {
+    error!("Cannot read translator pairs, error: {}", e);
+    HttpError::CannotGetRedisKeysError.reject()
+  })?;
+
+  Ok(warp::reply::json(&pairs))
+}
+
+///
+/// Pair two names which are not paired yet, `409` if either of them is paired.
+/// The `RaidBoss` record of the japanese name is created from its raw boss if it does not exist.
+///
+pub async fn create_translation(
+  pair: TranslationPair,
+  app_state: AppState,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  apply(Change::Create(pair.clone()), &app_state).await?;
+
+  Ok(warp::reply::with_status(warp::reply::json(&pair), StatusCode::CREATED))
+}
+
+///
+/// Pair two names, their previous partners are unpaired, `409` if either of the previous pairs is locked.
+///
+pub async fn override_translation(
+  pair: TranslationPair,
+  app_state: AppState,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  apply(Change::Override(pair.clone()), &app_state).await?;
+
+  Ok(warp::reply::json(&pair))
+}
+
+///
+/// Lock or unlock the pair of a name.
+///
+pub async fn lock_translation(request: LockRequest, app_state: AppState) -> Result<impl warp::Reply, warp::Rejection> {
+  let change = Change::Lock {
+    name: request.name,
+    locked: request.locked,
+  };
+  apply(change, &app_state).await?;
+
+  Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+///
+/// Unpair a name and its partner, the translator would match them again once they are tweeted.
+///
+pub async fn delete_translation(
+  query: TranslationQuery,
+  app_state: AppState,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  apply(Change::Delete(query.name), &app_state).await?;
+
+  Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+///
+/// List translations waiting for review, the most similar first.
+/// Their names stay pending, so tweets of english names are not published until they are accepted.
+///
+/// # Example
+///
+/// ```
+/// [{"boss_name": "Lv120 メドゥーサ", "language": "Japanese", "translated_name": "Lvl 120 Medusa", "level": 120,
+///   "image": "https://pbs.twimg.com/media/CYBki-CUkAQVWW_.jpg", "score": 0.22, "margin": 0.03,
+///   "runner_up": "Lvl 120 Metatron"}]
+/// ```
+pub async fn list_reviews(app_state: AppState) -> Result<impl warp::Reply, warp::Rejection> {
+  let mut reviews = get_translation_reviews(&app_state.config.redis_prefix, &app_state.redis)
+    .await
+    .map_err(|e| {
+      error!("Cannot read translation reviews, error: {}", e);
+      HttpError::CannotGetRedisKeysError.reject()
+    })?;
+  reviews.sort_by(|a, b| a.score.partial_cmp(&b.score).unwrap_or(std::cmp::Ordering::Equal));
+
+  Ok(warp::reply::json(&reviews))
+}
+
+///
+/// Accept a translation waiting for review, the names are paired like `create_translation`.
+///
+pub async fn accept_review(request: ReviewRequest, app_state: AppState) -> Result<impl warp::Reply, warp::Rejection> {
+  apply(Change::Accept(request.name), &app_state).await?;
+
+  Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+///
+/// Reject a translation waiting for review, the name would be matched again once it is tweeted.
+/// Pair it with `create_translation` instead if its boss is known.
+///
+pub async fn reject_review(request: ReviewRequest, app_state: AppState) -> Result<impl warp::Reply, warp::Rejection> {
+  apply(Change::Reject(request.name), &app_state).await?;
+
+  Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+///
+/// Apply `change` to the translator map of the tweet actor and redis.
+/// Redis is read without locking the map, the map is only locked to compare and set the entries of the touched names.
+/// If a translator task or another request changed them since the snapshot, the change is rejected.
+/// Redis writes of the pairs are serialized with the translator tasks from the compare through the transaction.
+///
+async fn apply(change: Change, app_state: &AppState) -> std::result::Result<(), warp::Rejection> {
+  let map = app_state
+    .translator_map
+    .as_ref()
+    .ok_or_else(|| HttpError::TranslatorUnavailableError.reject())?;
+  let (redis, config) = (&app_state.redis, &app_state.config);
+
+  let current = map.pairs.read().await.clone();
+  let snapshot = snapshot(&change, &current, redis, &config.redis_prefix)
+    .await
+    .map_err(|e| {
+      error!("Cannot read translator pairs, error: {}", e);
+      HttpError::CannotGetRedisKeysError.reject()
+    })?;
+  let mut plan = translator::plan(&change, &snapshot, &config.redis_prefix, config.boss_ttl).map_err(|e| e.reject())?;
+  let writes = std::mem::take(&mut plan.writes);
+
+  let _writes = map.writes.lock().await;
+  let mut writable_map = map.pairs.write().await;
+  if !translator::has_entries(&writable_map, &snapshot.entries) {
+    return Err(HttpError::TranslationChangedError.reject());
+  }
+  plan.apply(&mut writable_map);
+  let applied = translator::entries(&writable_map, snapshot.entries.keys());
+  // Drop write lock before writing to redis, it will prevent map from getting lock during redis setting operation.
+  drop(writable_map);
+
+  if let Err(e) = redis.transaction(writes).await {
+    error!("Cannot write translator pairs, error: {}", e);
+    let mut writable_map = map.pairs.write().await;
+    if translator::has_entries(&writable_map, &applied) {
+      translator::restore_entries(&mut writable_map, &snapshot.entries);
+    }
+    return Err(HttpError::CannotWriteRedisError.reject());
+  }
+  info!("Translator map is changed by admin: {:?}", change);
+
+  Ok(())
+}
+
+async fn snapshot(change: &Change, map: &HashMap<String, String>, redis: &Redis, prefix: &str) -> Result<Snapshot> {
+  let review = match change.review_name() {
+    Some(name) => match redis.get_bytes(gbf_translation_review_key(prefix, name)).await? {
+      Some(bytes) => Some(Translation::decode(bytes.as_slice()).map_err(|error| Error::ProtobufParse { error })?),
+      None => None,
+    },
+    None => None,
+  };
+  let pair = change.pair(review.as_ref());
+  let names = change.names(pair.as_ref(), map);
+  let partners = names
+    .iter()
+    .filter_map(|name| {
+      map
+        .get(name)
+        .filter(|partner| !partner.is_empty())
+        .map(|partner| (name.clone(), partner.clone()))
+    })
+    .collect();
+
+  let mut boss_keys = vec![];
+  for name in names.iter() {
+    boss_keys.extend(redis.keys(gbf_raid_boss_name_keys(prefix, name)).await?);
+  }
+  let bosses = redis.mget_protobuf::<RaidBoss, _, _>(boss_keys).await?;
+
+  let mut raw = None;
+  if let Some(pair) = &pair {
+    for (language, name) in &[(Language::Japanese, &pair.jp_name), (Language::English, &pair.en_name)] {
+      let keys = redis.keys(gbf_raid_boss_raw_name_keys(prefix, *language, name)).await?;
+      if let Some(raid_boss_raw) = redis.mget_protobuf::<RaidBossRaw, _, _>(keys).await?.into_iter().next() {
+        raw = Some((raid_boss_raw.level, raid_boss_raw.image));
+        break;
+      }
+    }
+  }
+
+  Ok(Snapshot {
+    entries: translator::entries(map, &names),
+    partners,
+    locked: locked_names(redis, prefix).await?,
+    bosses,
+    raw,
+    review,
+  })
+}
+
+/// Names of locked translator pairs.
+async fn locked_names(redis: &Redis, prefix: &str) -> Result<HashSet<String>> {
+  let lock_prefix = gbf_translator_lock_key(prefix, "");
+
+  Ok(
+    redis
+      .keys(gbf_translator_lock_key(prefix, "*"))
+      .await?
+      .into_iter()
+      .map(|key| key.replace(&lock_prefix, ""))
+      .collect(),
+  )
+}
@@ -1,6 +1,14 @@
-use crate::{common::redis::gbf_raid_boss_keys, server::state::AppState, error};
+use crate::{
+  common::redis::gbf_raid_boss_keys,
+  error,
+  proto::{decode_all, raid_boss::RaidBoss, services::GetBossesResponse},
+  server::{
+    negotiation::{self, ResponseFormat},
+    state::AppState,
+  },
+};
 use serde::Deserialize;
-use warp::hyper::StatusCode;
+use warp::Reply;
 
 #[derive(Deserialize, Clone, Copy)]
 pub struct GetBossRequest {
@@ -13,21 +21,39 @@ pub struct GetBossRequest {
 /// 
 /// # Arguments:
 /// * `request`: GetBossRequest should be a json object with level key.
+/// * `format`: negotiated by `Accept`, `RaidBoss` objects for json and `GetBossesResponse` for protobuf.
 /// 
-pub async fn get_bosses(request: GetBossRequest, app_state: AppState) -> Result<impl warp::Reply, warp::Rejection> {
-  let level = request.level;
+pub async fn get_bosses(
+  request: GetBossRequest,
+  format: ResponseFormat,
+  app_state: AppState,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let bosses = find_bosses(&app_state, request.level).await.map_err(|error| error.reject())?;
 
+  let response = match format {
+    ResponseFormat::Legacy => warp::reply::json(&bosses).into_response(),
+    ResponseFormat::Json => warp::reply::json(&decode_all::<RaidBoss>(bosses)).into_response(),
+    ResponseFormat::Protobuf => negotiation::protobuf(&GetBossesResponse {
+      bosses: decode_all(bosses),
+    }),
+  };
+
+  Ok(negotiation::vary_accept(response))
+}
+
+///
+/// Encoded `RaidBoss`es of given level, shared by http and gRPC server.
+///
+pub async fn find_bosses(app_state: &AppState, level: u32) -> Result<Vec<Vec<u8>>, error::HttpError> {
   let boss_keys = app_state
     .redis
-    .keys(gbf_raid_boss_keys(level))
+    .keys(gbf_raid_boss_keys(&app_state.config.redis_prefix, level))
     .await
-    .map_err(|_| error::HttpError::CannotGetRedisKeysError.reject())?;
+    .map_err(|_| error::HttpError::CannotGetRedisKeysError)?;
 
-  let bosses: Vec<Vec<u8>> = app_state
+  app_state
     .redis
     .mget_protobuf_raw(boss_keys)
     .await
-    .map_err(|_| error::HttpError::CannotMGetRedisError.reject())?;
-
-  Ok(warp::reply::with_status(warp::reply::json(&bosses), StatusCode::OK))
+    .map_err(|_| error::HttpError::CannotMGetRedisError)
 }
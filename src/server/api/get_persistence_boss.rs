@@ -1,6 +1,20 @@
-use crate::{common::redis::gbf_persistence_raid_tweets_keys, error, server::state::AppState};
+use crate::{
+  common::redis::{escape_key_pattern, gbf_persistence_raid_tweet_key_parts_of, gbf_persistence_raid_tweets_keys},
+  error,
+  proto::{
+    decode_all,
+    raid_tweet::RaidTweet,
+    services::{GetPersistenceBossResponse, PersistenceRaidTweets},
+  },
+  resources::throttle::{MAX_BOSS_NAMES, MAX_PERSISTENCE_LIMIT},
+  server::{
+    negotiation::{self, ResponseFormat},
+    state::AppState,
+  },
+};
 use serde::Deserialize;
 use std::collections::HashMap;
+use warp::Reply;
 
 #[derive(Deserialize, Clone)]
 pub struct GetPersistenceBossRequest {
@@ -13,36 +27,125 @@ pub struct GetPersistenceBossRequest {
 /// 
 /// # Arguments
 /// * `request` - A JSON object with key of boss_names which is a vector string.
+/// * `format` - negotiated by `Accept`, `RaidTweet` objects for json and `GetPersistenceBossResponse` for protobuf.
 /// 
 pub async fn get_persistence_boss(
   request: GetPersistenceBossRequest,
+  format: ResponseFormat,
   app_state: AppState,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-  let boss_names = request.boss_names;
-  let limit = request.limit;
+  let tweets = find_persistence_raid_tweets(&app_state, &request.boss_names, request.limit)
+    .await
+    .map_err(|error| error.reject())?;
+
+  let response = match format {
+    ResponseFormat::Legacy => warp::reply::json(&tweets).into_response(),
+    ResponseFormat::Json => {
+      let tweets = tweets
+        .into_iter()
+        .map(|(boss_name, bytes)| (boss_name, decode_all::<RaidTweet>(bytes)))
+        .collect::<HashMap<_, _>>();
+      warp::reply::json(&tweets).into_response()
+    }
+    ResponseFormat::Protobuf => {
+      let tweets = tweets
+        .into_iter()
+        .map(|(boss_name, bytes)| (boss_name, PersistenceRaidTweets { tweets: decode_all(bytes) }))
+        .collect();
+      negotiation::protobuf(&GetPersistenceBossResponse { tweets })
+    }
+  };
+
+  Ok(negotiation::vary_accept(response))
+}
+
+///
+/// Latest encoded `RaidTweet`s of each boss, shared by http and gRPC server.
+///
+/// # Arguments
+/// * `boss_names` - bosses to retrieve, at most `MAX_BOSS_NAMES` since each boss costs a redis `KEYS`.
+/// * `limit` - max raid tweets of each boss, `0` means `MAX_PERSISTENCE_LIMIT` and larger ones are capped at it.
+///
+pub async fn find_persistence_raid_tweets(
+  app_state: &AppState,
+  boss_names: &[String],
+  limit: u32,
+) -> Result<HashMap<String, Vec<Vec<u8>>>, error::HttpError> {
+  if boss_names.len() > MAX_BOSS_NAMES {
+    return Err(error::HttpError::BossNamesTooLongError);
+  }
+  let limit = persistence_limit(limit);
+  let prefix = &app_state.config.redis_prefix;
   let mut response = HashMap::new();
 
   for boss_name in boss_names.iter() {
-    let mut persistence_keys = app_state
+    let keys = app_state
       .redis
-      .keys(gbf_persistence_raid_tweets_keys(boss_name))
+      .keys(gbf_persistence_raid_tweets_keys(prefix, escape_key_pattern(boss_name)))
       .await
-      .map_err(|_| error::HttpError::CannotGetRedisKeysError.reject())?;
-    persistence_keys.sort_by(|a, b| {
-      let last = |string: String| string.split('.').last().map(|str| str.to_owned());
-      if let (Some(last_a), Some(last_b)) = (last(a.into()), last(b.into())) {
-        return last_b.cmp(&last_a);
-      }
-      b.cmp(a)
-    });
-    persistence_keys.truncate(limit as usize);
+      .map_err(|_| error::HttpError::CannotGetRedisKeysError)?;
+    let persistence_keys = latest_keys(keys, prefix, boss_name, limit as usize);
     let tweets_bytes: Vec<Vec<u8>> = app_state
       .redis
       .mget_protobuf_raw(persistence_keys)
       .await
-      .map_err(|_| error::HttpError::CannotGetRedisKeysError.reject())?;
-    response.insert(boss_name, tweets_bytes);
+      .map_err(|_| error::HttpError::CannotGetRedisKeysError)?;
+    response.insert(boss_name.clone(), tweets_bytes);
+  }
+
+  Ok(response)
+}
+
+///
+/// Keep the latest `limit` persistence keys of the boss, newest first.
+/// Keys of other bosses matched by the pattern are skipped, like `v1::bosses::page_keys`.
+///
+fn latest_keys(keys: Vec<String>, prefix: &str, boss_name: &str, limit: usize) -> Vec<String> {
+  let mut keys = keys
+    .into_iter()
+    .filter_map(|key| {
+      gbf_persistence_raid_tweet_key_parts_of(prefix, boss_name, &key).map(|(_, created)| (created, key))
+    })
+    .collect::<Vec<_>>();
+  keys.sort_by(|a, b| b.cmp(a));
+  keys.truncate(limit);
+
+  keys.into_iter().map(|(_, key)| key).collect()
+}
+
+/// `0` is the default of an omitted protobuf field, so it means the max limit rather than no raid tweets.
+fn persistence_limit(limit: u32) -> u32 {
+  match limit {
+    0 => MAX_PERSISTENCE_LIMIT,
+    limit => limit.min(MAX_PERSISTENCE_LIMIT),
   }
+}
 
-  Ok(warp::reply::json(&response))
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{common::redis::gbf_persistence_raid_tweet_key, resources::redis::DEFAULT_PREFIX};
+
+  #[test]
+  fn test_latest_keys() {
+    let key =
+      |boss_name, tweet_id, created| gbf_persistence_raid_tweet_key(DEFAULT_PREFIX, boss_name, tweet_id, created);
+    let keys = vec![
+      key("Lv120 Mt. Qilin", 1, 100),
+      key("Lv120 Mt. Qilin", 3, 300),
+      key("Lv120 Mt. Qilin.Impossible", 4, 400),
+      key("Lv120 Mt. Qilin", 2, 200),
+    ];
+    assert_eq!(
+      vec![key("Lv120 Mt. Qilin", 3, 300), key("Lv120 Mt. Qilin", 2, 200)],
+      latest_keys(keys, DEFAULT_PREFIX, "Lv120 Mt. Qilin", 2)
+    );
+  }
+
+  #[test]
+  fn test_persistence_limit() {
+    assert_eq!(MAX_PERSISTENCE_LIMIT, persistence_limit(0));
+    assert_eq!(10, persistence_limit(10));
+    assert_eq!(MAX_PERSISTENCE_LIMIT, persistence_limit(MAX_PERSISTENCE_LIMIT + 1));
+  }
 }
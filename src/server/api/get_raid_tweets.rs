@@ -0,0 +1,71 @@
+use crate::{
+  common::tweet_log::{is_stream_id, read_tweet_log},
+  error,
+  proto::raid_tweet::RaidTweet,
+  server::state::AppState,
+};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+#[derive(Deserialize, Clone)]
+pub struct GetRaidTweetsRequest {
+  /// Stream id of the last entry client has consumed.
+  pub since: Option<String>,
+  pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct RaidTweetEntry {
+  pub id: String,
+  pub tweet: RaidTweet,
+}
+
+#[derive(Serialize)]
+pub struct GetRaidTweetsResponse {
+  pub entries: Vec<RaidTweetEntry>,
+  /// Pass it as `since` to get the next page, `None` if the stream is empty.
+  pub last_id: Option<String>,
+}
+
+///
+/// Read the ordered raid tweet log across all bosses.
+/// Without `since` it returns the latest `limit` raid tweets, otherwise raid tweets after the given stream id.
+/// Entries which cannot be decoded are skipped, `last_id` still moves past them.
+///
+/// # Arguments
+/// * `request` - query string with optional `since` and `limit`.
+///
+pub async fn get_raid_tweets(
+  request: GetRaidTweetsRequest,
+  app_state: AppState,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let limit = request.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+  if matches!(request.since.as_deref(), Some(since) if !is_stream_id(since)) {
+    return Err(error::HttpError::InvalidCursorError.reject());
+  }
+
+  let entries = read_tweet_log(
+    &app_state.redis,
+    &app_state.config.redis_prefix,
+    request.since.as_deref(),
+    limit,
+  )
+  .await
+  .map_err(|_| error::HttpError::CannotReadStreamError.reject())?;
+
+  let last_id = entries.last().map(|entry| entry.0.clone()).or(request.since);
+
+  Ok(warp::reply::json(&GetRaidTweetsResponse {
+    entries: entries
+      .into_iter()
+      .filter_map(|(id, bytes)| {
+        let tweet = RaidTweet::decode(bytes.as_slice()).ok()?;
+        Some(RaidTweetEntry { id, tweet })
+      })
+      .collect(),
+    last_id,
+  }))
+}
@@ -0,0 +1,179 @@
+use crate::{
+  common::{chrono::current_timestamp_u64, health::Health},
+  resources::health::{MAX_TWEET_IDLE_SECS, REDIS_PING_TIMEOUT_SECS},
+  server::state::AppState,
+};
+use serde::Serialize;
+use std::{collections::BTreeMap, time::Duration};
+use tokio::time::timeout;
+use warp::hyper::StatusCode;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+  Ok,
+  Fail,
+  /// The component is not run by this role, ex. the twitter stream of `Role::Server`.
+  Skipped,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Check {
+  pub status: CheckStatus,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub message: Option<String>,
+}
+
+impl Check {
+  fn new<S: Into<String>>(ok: bool, message: S) -> Self {
+    Check {
+      status: if ok { CheckStatus::Ok } else { CheckStatus::Fail },
+      message: Some(message.into()),
+    }
+  }
+
+  fn skipped() -> Self {
+    Check {
+      status: CheckStatus::Skipped,
+      message: None,
+    }
+  }
+}
+
+///
+/// JSON breakdown of each component, the probe fails if any component fails.
+///
+/// # Example
+///
+/// ```
+/// {"status": "fail", "checks": {"actor": {"status": "ok"}, "redis": {"status": "fail", "message": "timeout"}}}
+/// ```
+#[derive(Serialize, Debug)]
+pub struct HealthReport {
+  pub status: CheckStatus,
+  pub checks: BTreeMap<&'static str, Check>,
+}
+
+impl HealthReport {
+  fn new(checks: BTreeMap<&'static str, Check>) -> Self {
+    let status = match checks.values().any(|check| check.status == CheckStatus::Fail) {
+      true => CheckStatus::Fail,
+      false => CheckStatus::Ok,
+    };
+
+    HealthReport { status, checks }
+  }
+
+  fn into_reply(self) -> impl warp::Reply {
+    let status = match self.status {
+      CheckStatus::Fail => StatusCode::SERVICE_UNAVAILABLE,
+      _ => StatusCode::OK,
+    };
+
+    warp::reply::with_status(warp::reply::json(&self), status)
+  }
+}
+
+fn check_actor(health: &Health, ingests: bool) -> Check {
+  if !ingests {
+    return Check::skipped();
+  }
+  let alive = health.is_actor_alive();
+
+  Check::new(alive, if alive { "running" } else { "stopped" })
+}
+
+fn check_stream(health: &Health, ingests: bool) -> Check {
+  if !ingests {
+    return Check::skipped();
+  }
+  let connected = health.is_stream_connected();
+
+  Check::new(connected, if connected { "connected" } else { "disconnected" })
+}
+
+fn check_tweets(health: &Health, ingests: bool, now: u64) -> Check {
+  if !ingests {
+    return Check::skipped();
+  }
+  let idle_secs = health.tweet_idle_secs(now);
+
+  Check::new(
+    idle_secs <= MAX_TWEET_IDLE_SECS,
+    format!("last raid tweet parsed {} seconds ago", idle_secs),
+  )
+}
+
+///
+/// Liveness probe, fails only if the process should be restarted.
+/// A stopped `TweetActor` never recovers, every later tweet would fail with `ActorTaskBeenKilled`.
+///
+pub fn livez(app_state: AppState) -> impl warp::Reply {
+  let ingests = app_state.config.role.ingests();
+  let mut checks = BTreeMap::new();
+  checks.insert("actor", check_actor(&app_state.health, ingests));
+
+  HealthReport::new(checks).into_reply()
+}
+
+///
+/// Readiness probe, fails if this process cannot deliver raid tweets right now.
+/// Checks the twitter stream, time since the last parsed raid tweet, the `TweetActor` and a redis `PING`.
+///
+pub async fn readyz(app_state: AppState) -> Result<impl warp::Reply, warp::Rejection> {
+  let ingests = app_state.config.role.ingests();
+  let health = &app_state.health;
+  let mut checks = BTreeMap::new();
+  checks.insert("actor", check_actor(health, ingests));
+  checks.insert("stream", check_stream(health, ingests));
+  checks.insert("tweets", check_tweets(health, ingests, current_timestamp_u64()));
+  let redis = match timeout(Duration::from_secs(REDIS_PING_TIMEOUT_SECS), app_state.redis.ping()).await {
+    Ok(Ok(())) => Check::new(true, "pong"),
+    Ok(Err(error)) => Check::new(false, error.to_string()),
+    Err(_) => Check::new(false, "timeout"),
+  };
+  checks.insert("redis", redis);
+
+  Ok(HealthReport::new(checks).into_reply())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::Arc;
+
+  #[test]
+  fn test_checks() {
+    let health = Arc::new(Health::new());
+    assert_eq!(CheckStatus::Skipped, check_actor(&health, false).status);
+    assert_eq!(CheckStatus::Fail, check_actor(&health, true).status);
+    let _guard = health.actor_guard();
+    assert_eq!(CheckStatus::Ok, check_actor(&health, true).status);
+
+    assert_eq!(CheckStatus::Fail, check_stream(&health, true).status);
+    health.set_stream_connected(true);
+    assert_eq!(CheckStatus::Ok, check_stream(&health, true).status);
+
+    let now = current_timestamp_u64();
+    assert_eq!(CheckStatus::Ok, check_tweets(&health, true, now).status);
+    let stalled = now + MAX_TWEET_IDLE_SECS + 1;
+    assert_eq!(CheckStatus::Fail, check_tweets(&health, true, stalled).status);
+    assert_eq!(CheckStatus::Skipped, check_tweets(&health, false, stalled).status);
+  }
+
+  #[test]
+  fn test_report() {
+    let mut checks = BTreeMap::new();
+    checks.insert("actor", Check::new(true, "running"));
+    checks.insert("stream", Check::skipped());
+    let report = HealthReport::new(checks.clone());
+    assert_eq!(CheckStatus::Ok, report.status);
+    assert_eq!(
+      serde_json::json!({"status": "ok", "checks": {"actor": {"status": "ok", "message": "running"}, "stream": {"status": "skipped"}}}),
+      serde_json::to_value(&report).unwrap()
+    );
+
+    checks.insert("redis", Check::new(false, "timeout"));
+    assert_eq!(CheckStatus::Fail, HealthReport::new(checks).status);
+  }
+}
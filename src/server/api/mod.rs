@@ -1,4 +1,10 @@
 pub mod get_bosses;
 pub mod stream_bosses;
+pub mod stream_bosses_sse;
 pub mod get_persistence_boss;
-pub mod healthz;
\ No newline at end of file
+pub mod get_raid_tweets;
+pub mod health;
+pub mod stats;
+pub mod v1;
+pub mod web;
+pub mod admin;
@@ -0,0 +1,8 @@
+use crate::server::state::AppState;
+
+///
+/// Counters of the tweet pipeline, ex. how many duplicated raid tweets are dropped.
+///
+pub fn stats(app_state: AppState) -> impl warp::Reply {
+  warp::reply::json(&app_state.stats.snapshot())
+}
@@ -1,40 +1,96 @@
-use crate::{error, resources::ws, server::client::FinderClient, server::state::AppState};
+use crate::{
+  error,
+  resources::ws,
+  server::auth::{max_boss_names, ConnectionPermit},
+  server::client::FinderClient,
+  server::queue::ClientQueue,
+  server::protocol::{ClientRequest, ErrorCode, ServerFrame, PROTOCOL_VERSION},
+  server::replay::{hold_live_tweets, replay_raid_tweets, SinceMarker},
+  server::state::AppState,
+  server::throttle::IpConnectionGuard,
+};
 use futures::stream::SplitSink;
 use futures::{SinkExt, StreamExt};
 use log::{error, info};
 use serde::Deserialize;
-use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex};
-use tokio_stream::wrappers::UnboundedReceiverStream;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{sync::Mutex, time::timeout};
 
 #[derive(Deserialize, Clone)]
 pub struct StreamRequest {
   pub boss_names: Vec<String>,
+  /// Replay raid tweets posted after these markers before switching to live delivery.
+  #[serde(default)]
+  pub since: HashMap<String, SinceMarker>,
+}
+
+///
+/// Query of `/stream_bosses`
+///
+/// # Arguments
+/// * `protocol`: websocket protocol version, `2` to enable the control protocol in `server::protocol`, default to `1`.
+///
+#[derive(Deserialize, Clone, Copy, Default)]
+pub struct StreamQuery {
+  pub protocol: Option<u32>,
+}
+
+impl StreamQuery {
+  fn is_v2(&self) -> bool {
+    self.protocol.unwrap_or(1) >= PROTOCOL_VERSION
+  }
 }
 
 enum WebsocketMsgType {
   Request(String),
   Json(StreamRequest),
+  Control(ClientRequest),
+  Reply(ServerFrame),
   Pong,
   NoneString,
 }
 
-pub async fn stream_bosses(ws: warp::ws::WebSocket, app_state: AppState) {
+///
+/// Serve a websocket client until it is gone
+///
+/// # Arguments
+/// * `permit` - the API key of the client, it is held until the client is gone. `None` if authentication is disabled.
+/// * `_ip_guard` - counts the client in the streams of its IP until the client is gone.
+///
+pub async fn stream_bosses(
+  ws: warp::ws::WebSocket,
+  app_state: AppState,
+  query: StreamQuery,
+  permit: Option<ConnectionPermit>,
+  _ip_guard: Option<IpConnectionGuard>,
+) {
   // Generate client uuid
   let client_id = nanoid::nanoid!();
   // Get client transportation
   let (client_tx, client_rx) = ws.split();
-  // Create a channel between ws and tweet stream
-  let (tx, rx) = mpsc::unbounded_channel();
+  // Create a bounded queue between ws and tweet stream
+  let queue = Arc::new(ClientQueue::new(
+    app_state.config.client_queue_capacity,
+    app_state.config.client_queue_policy,
+  ));
   // Create finder client and inset it into global state
-  let client = FinderClient::new([""], tx);
+  let client = FinderClient::new([""], queue.clone());
   app_state.clients.write().await.insert(client_id.clone(), client);
-  info!("Client: {} incoming...", client_id);
-  // Receiver for tweet stream
-  let receiver = UnboundedReceiverStream::new(rx);
+  match &permit {
+    Some(permit) => info!("Client: {} incoming with API key {}...", client_id, permit.name()),
+    None => info!("Client: {} incoming...", client_id),
+  }
+  let max_boss_names = max_boss_names(&permit);
   let client_tx = Arc::new(Mutex::new(client_tx));
   // Create a new thread to sending message to client
-  sending_message(client_id.clone(), client_tx.clone(), receiver, app_state.clone());
+  sending_message(client_id.clone(), client_tx.clone(), queue, app_state.clone());
+  if query.is_v2() {
+    let hello = ServerFrame::hello(client_id.as_str()).to_message();
+    if client_tx.lock().await.send(hello).await.is_err() {
+      remove_client(&app_state, &client_id).await;
+      return;
+    }
+  }
   // Consuming incoming message
   let stream = client_rx
     .then(|result| async {
@@ -46,6 +102,10 @@ pub async fn stream_bosses(ws: warp::ws::WebSocket, app_state: AppState) {
           },
           Err(_) => match msg.is_close() {
             true => Err(error::Error::WebsocketClientClose),
+            false if msg.is_binary() && query.is_v2() => Ok(WebsocketMsgType::Reply(ServerFrame::error(
+              ErrorCode::UnsupportedMessage,
+              "message should be a JSON text frame",
+            ))),
             false => Ok(WebsocketMsgType::NoneString),
           },
         },
@@ -55,6 +115,11 @@ pub async fn stream_bosses(ws: warp::ws::WebSocket, app_state: AppState) {
     .then(|result| async {
       match result {
         Ok(msg) => match msg {
+          // Protocol 2 replies every request, either with an ack or an error frame.
+          WebsocketMsgType::Request(ref s) if query.is_v2() => match ClientRequest::parse(s) {
+            Ok(request) => Ok(WebsocketMsgType::Control(request)),
+            Err(frame) => Ok(WebsocketMsgType::Reply(frame)),
+          },
           WebsocketMsgType::Request(ref s) => match serde_json::from_str::<StreamRequest>(s) {
             Ok(json) => Ok(WebsocketMsgType::Json(json)),
             Err(_) => Ok(WebsocketMsgType::Request(s.to_owned())),
@@ -68,18 +133,59 @@ pub async fn stream_bosses(ws: warp::ws::WebSocket, app_state: AppState) {
   while let Some(result) = stream.next().await {
     if let Ok(msg) = result {
       match msg {
-        WebsocketMsgType::Json(json) => {
+        WebsocketMsgType::Json(StreamRequest { boss_names, since }) => {
+          // Protocol 1 has no error frame, close the websocket like a slow consumer.
+          if boss_names.len() > max_boss_names {
+            info!("Client: {} subscribes too many boss names, disconnecting...", client_id);
+            let close = warp::ws::Message::close_with(ws::TOO_MANY_BOSS_NAMES_CLOSE_CODE, "too many boss names");
+            let _ = client_tx.lock().await.send(close).await;
+            remove_client(&app_state, &client_id).await;
+            break;
+          }
+          let mut clients = app_state.clients.write().await;
+          let held = hold_live_tweets(&clients, &client_id, &since);
+          clients.update_boss_names(&client_id, |current| *current = boss_names);
+          drop(clients);
+          if !replay(&app_state, &client_id, since, held, &client_tx).await {
+            remove_client(&app_state, &client_id).await;
+            break;
+          }
+        }
+        WebsocketMsgType::Control(mut request) => {
+          let since = request.take_since();
           let mut clients = app_state.clients.write().await;
-          if let Some(client) = clients.get_mut(&client_id) {
-            (*client).boss_names = json.boss_names;
+          let held = hold_live_tweets(&clients, &client_id, &since);
+          let frame = clients.update_boss_names(&client_id, |boss_names| request.apply(boss_names, max_boss_names));
+          let frame = match frame {
+            Some(frame) => frame,
+            None => break,
+          };
+          drop(clients);
+          if client_tx.lock().await.send(frame.to_message()).await.is_err() {
+            remove_client(&app_state, &client_id).await;
+            break;
+          }
+          // Rejected requests leave the subscriptions unchanged, there is nothing to replay.
+          let since = match frame {
+            ServerFrame::Ack { .. } => since,
+            _ => HashMap::new(),
+          };
+          if !replay(&app_state, &client_id, since, held, &client_tx).await {
+            remove_client(&app_state, &client_id).await;
+            break;
+          }
+        }
+        WebsocketMsgType::Reply(frame) => {
+          if client_tx.lock().await.send(frame.to_message()).await.is_err() {
+            remove_client(&app_state, &client_id).await;
+            break;
           }
         }
         WebsocketMsgType::Pong => {
           let pong_result = client_tx.lock().await.send(warp::ws::Message::text(ws::PONG)).await;
           // When server is unable to sent a pong pack to client, it might be disconnected.
           if pong_result.is_err() {
-            app_state.clients.write().await.remove(client_id.as_str());
-            info!("Client: {} gone!", client_id);
+            remove_client(&app_state, &client_id).await;
             break;
           }
         }
@@ -93,51 +199,105 @@ pub async fn stream_bosses(ws: warp::ws::WebSocket, app_state: AppState) {
         }
       }
     } else {
-      app_state.clients.write().await.remove(client_id.as_str());
-      info!("Client: {} gone!", client_id);
+      remove_client(&app_state, &client_id).await;
       break;
     }
   }
 }
 
+///
+/// Replay missed raid tweets after the subscriptions of a client are updated, then release the held live tweets.
+/// Subscriptions are updated first, so no raid tweet is lost between the replay and live delivery.
+/// Replayed tweets are sent to the websocket ahead of the queue, so a long replay would not overflow it.
+/// Return false if the websocket cannot be sent.
+///
+async fn replay(
+  app_state: &AppState,
+  client_id: &str,
+  since: HashMap<String, SinceMarker>,
+  held: Option<FinderClient>,
+  client_tx: &Mutex<SplitSink<warp::ws::WebSocket, warp::ws::Message>>,
+) -> bool {
+  let tweets = match since.is_empty() {
+    true => vec![],
+    false => replay_raid_tweets(app_state, client_id, since)
+      .await
+      .unwrap_or_else(|e| {
+        error!("Client: {}, cannot replay raid tweets, error: {:?}", client_id, e);
+        vec![]
+      }),
+  };
+  let send_timeout = Duration::from_secs(ws::SEND_TIMEOUT_SECS);
+  let count = tweets.len();
+  for bytes in tweets {
+    let message = warp::ws::Message::binary(bytes.to_vec());
+    if !matches!(
+      timeout(send_timeout, client_tx.lock().await.send(message)).await,
+      Ok(Ok(_))
+    ) {
+      info!("Client: {}, cannot send replayed raid tweets.", client_id);
+      return false;
+    }
+  }
+  if count > 0 {
+    info!("Client: {}, replay {} raid tweets.", client_id, count);
+  }
+  if let Some(client) = held {
+    client.release_live_tweets();
+  }
+
+  true
+}
+
+///
+/// Remove the client from global state and close its queue, it is fine to call it more than once.
+///
+pub async fn remove_client(app_state: &AppState, client_id: &str) {
+  if let Some(client) = app_state.clients.write().await.remove(client_id) {
+    client.queue.close();
+    info!("Client: {} gone! {} messages are dropped.", client_id, client.queue.dropped());
+  }
+}
+
 ///
 /// A thread to forward raid tweet message to websocket client
+/// The websocket is closed with `going away` once the queue is closed by a shutdown.
 ///
 /// # Arguments
 /// * `client_id`: the client where we want to send, use to remove the global state when retrieving error.
 /// * `client_tx`: client transportation.
-/// * `receiver`: twitter stream receiver.
+/// * `queue`: raid tweets queued for the client.
 ///
 fn sending_message(
   client_id: String,
   client_tx: Arc<Mutex<SplitSink<warp::ws::WebSocket, warp::ws::Message>>>,
-  receiver: UnboundedReceiverStream<Result<warp::ws::Message, warp::Error>>,
+  queue: Arc<ClientQueue>,
   app_state: AppState,
 ) {
-  tokio::spawn(async move {
-    let stream = receiver.then(|result| async {
-      let result = match result {
-        Ok(message) => client_tx.lock().await.send(message).await,
-        Err(err) => Err(err),
-      };
-
-      match result {
-        Ok(_) => Ok(()),
-        Err(error) => {
-          app_state.clients.write().await.remove(client_id.as_str());
-          info!("Client: {} gone!", client_id);
-          Err(error::Error::WebsocketClient { error })
-        }
-      }
-    });
-    tokio::pin!(stream);
-    while let Some(result) = stream.next().await {
-      if result.is_err() {
-        break;
+  let shutdown = app_state.shutdown.clone();
+  shutdown.spawn(async move {
+    let send_timeout = Duration::from_secs(ws::SEND_TIMEOUT_SECS);
+    while let Some(bytes) = queue.pop().await {
+      // warp only accepts owned bytes, so the shared encoding is copied once per client here.
+      let message = warp::ws::Message::binary(bytes.to_vec());
+      // A client which never reads will block the send forever, treat it as gone.
+      match timeout(send_timeout, client_tx.lock().await.send(message)).await {
+        Ok(Ok(_)) => continue,
+        Ok(Err(error)) => info!("Client: {}, websocket error: {:?}", client_id, error),
+        Err(_) => info!("Client: {}, sending message timeout.", client_id),
       }
+      break;
+    }
+    if queue.is_overflowed() {
+      info!("Client: {} is too slow, disconnecting...", client_id);
+      let close = warp::ws::Message::close_with(ws::SLOW_CONSUMER_CLOSE_CODE, "slow consumer");
+      let _ = timeout(send_timeout, client_tx.lock().await.send(close)).await;
+    } else if app_state.shutdown.is_triggered() {
+      let close = warp::ws::Message::close_with(ws::GOING_AWAY_CLOSE_CODE, "server is shutting down");
+      let _ = timeout(send_timeout, client_tx.lock().await.send(close)).await;
     }
+    remove_client(&app_state, &client_id).await;
 
     info!("Client {} sending message stream end.", client_id);
-    Ok::<(), error::Error>(())
   });
 }
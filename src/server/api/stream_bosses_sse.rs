@@ -0,0 +1,174 @@
+use crate::{
+  error,
+  proto::raid_tweet::RaidTweet,
+  server::{
+    api::stream_bosses::remove_client,
+    auth::{max_boss_names, ConnectionPermit},
+    client::FinderClient,
+    queue::ClientQueue,
+    replay::{find_missed_raid_tweets, SinceMarker},
+    state::AppState,
+    throttle::IpConnectionGuard,
+  },
+};
+use futures::StreamExt;
+use log::{error, info};
+use prost::Message;
+use serde::Deserialize;
+use std::{convert::Infallible, sync::Arc};
+use warp::sse::Event;
+
+#[derive(Deserialize, Clone)]
+pub struct StreamBossesSseRequest {
+  /// Comma separated boss names, ex. `Lv200 アーカーシャ,Lv150 プロトバハムート`.
+  pub boss_names: String,
+}
+
+/// Remove the client from global state and release its connection limits once the event stream is dropped by warp.
+struct SseClientGuard {
+  client_id: String,
+  app_state: AppState,
+  _ip_guard: Option<IpConnectionGuard>,
+  _permit: Option<ConnectionPermit>,
+}
+
+impl Drop for SseClientGuard {
+  fn drop(&mut self) {
+    let client_id = std::mem::take(&mut self.client_id);
+    let app_state = self.app_state.clone();
+    tokio::spawn(async move { remove_client(&app_state, &client_id).await });
+  }
+}
+
+///
+/// Stream raid tweets as server-sent events for clients which cannot use websocket
+///
+/// Each event is a JSON `RaidTweet` with `id` set to its tweet id.
+/// When the client reconnects with `Last-Event-ID`, persisted raid tweets after that tweet are replayed first.
+///
+/// # Arguments
+/// * `request` - query string with `boss_names`.
+/// * `last_event_id` - the last tweet id client has received.
+/// * `ip_guard` - counts the client in the streams of its IP.
+/// * `permit` - the API key of the client, `None` if authentication is disabled.
+///
+pub async fn stream_bosses_sse(
+  request: StreamBossesSseRequest,
+  last_event_id: Option<String>,
+  ip_guard: Option<IpConnectionGuard>,
+  permit: Option<ConnectionPermit>,
+  app_state: AppState,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let boss_names = parse_boss_names(&request.boss_names);
+  if boss_names.is_empty() {
+    return Err(error::HttpError::EmptyBossNamesError.reject());
+  }
+  if boss_names.len() > max_boss_names(&permit) {
+    return Err(error::HttpError::TooManyBossNamesError.reject());
+  }
+  let client_id = nanoid::nanoid!();
+  let queue = Arc::new(ClientQueue::new(
+    app_state.config.client_queue_capacity,
+    app_state.config.client_queue_policy,
+  ));
+  let client = FinderClient::new(boss_names.clone(), queue.clone());
+  // Live tweets are held until the replay is queued, so they are not sent before the missed ones.
+  if last_event_id.is_some() {
+    client.hold_live_tweets();
+  }
+  app_state.clients.write().await.insert(client_id.clone(), client.clone());
+  info!("SSE client: {} incoming...", client_id);
+  let guard = SseClientGuard {
+    client_id: client_id.clone(),
+    app_state: app_state.clone(),
+    _ip_guard: ip_guard,
+    _permit: permit,
+  };
+
+  // Replayed tweets are streamed ahead of the queue, so a long replay would not overflow it.
+  let mut replayed = vec![];
+  if let Some(last_event_id) = last_event_id {
+    let since = boss_names
+      .iter()
+      .map(|boss_name| (boss_name.clone(), SinceMarker::TweetId(last_event_id.clone())))
+      .collect();
+    match find_missed_raid_tweets(&app_state, &boss_names, &since).await {
+      Ok(tweets) => {
+        replayed = tweets
+          .into_iter()
+          .filter(|(tweet_id, _)| client.mark_sent(*tweet_id))
+          .filter_map(|(_, bytes)| raid_tweet_event(&bytes))
+          .map(Ok::<Event, Infallible>)
+          .collect();
+        info!("SSE client: {}, replay {} raid tweets.", client_id, replayed.len());
+      }
+      Err(e) => error!("SSE client: {}, cannot replay raid tweets, error: {:?}", client_id, e),
+    }
+    client.release_live_tweets();
+  }
+
+  let live = futures::stream::unfold((queue, guard), |(queue, guard)| async move {
+    loop {
+      let bytes = queue.pop().await?;
+      if let Some(event) = raid_tweet_event(&bytes) {
+        return Some((Ok::<Event, Infallible>(event), (queue, guard)));
+      }
+    }
+  });
+
+  let events = futures::stream::iter(replayed).chain(live);
+
+  Ok(warp::sse::reply(warp::sse::keep_alive().stream(events)))
+}
+
+fn parse_boss_names(boss_names: &str) -> Vec<String> {
+  let mut result: Vec<String> = vec![];
+  for boss_name in boss_names.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+    if !result.iter().any(|name| name == boss_name) {
+      result.push(boss_name.to_owned());
+    }
+  }
+
+  result
+}
+
+/// Decode a queued raid tweet into a JSON event.
+fn raid_tweet_event(bytes: &[u8]) -> Option<Event> {
+  let raid_tweet = RaidTweet::decode(bytes).ok()?;
+
+  Event::default()
+    .id(raid_tweet.tweet_id.to_string())
+    .event("raid_tweet")
+    .json_data(&raid_tweet)
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::proto::MessageExt;
+
+  #[test]
+  fn test_parse_boss_names() {
+    assert_eq!(
+      vec!["Lv200 アーカーシャ", "Lv150 プロトバハムート"],
+      parse_boss_names("Lv200 アーカーシャ, Lv150 プロトバハムート,,Lv200 アーカーシャ")
+    );
+    assert!(parse_boss_names(" , ").is_empty());
+  }
+
+  #[test]
+  fn test_raid_tweet_event() {
+    let raid_tweet = RaidTweet {
+      tweet_id: 1420698515453000000,
+      boss_name: "Lv200 アーカーシャ".into(),
+      ..Default::default()
+    };
+    let event = raid_tweet_event(&raid_tweet.to_bytes().unwrap()).unwrap().to_string();
+    assert!(event.contains("id:1420698515453000000\n"));
+    assert!(event.contains("event:raid_tweet\n"));
+    assert!(event.contains(r#""tweet_id":"1420698515453000000""#));
+    assert!(event.contains(r#""boss_name":"Lv200 アーカーシャ""#));
+    assert!(raid_tweet_event(&[0xff]).is_none());
+  }
+}
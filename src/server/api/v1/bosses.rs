@@ -0,0 +1,243 @@
+use crate::{
+  common::redis::{
+    escape_key_pattern, gbf_persistence_raid_tweet_key_parts_of, gbf_persistence_raid_tweets_keys,
+    gbf_raid_boss_name_keys,
+  },
+  error::HttpError,
+  proto::{
+    decode_all, messages::RaidTweetsPage, raid_boss::RaidBoss, raid_tweet::RaidTweet, services::GetBossesResponse,
+  },
+  server::{
+    api::get_bosses::find_bosses,
+    cache,
+    negotiation::{Encoded, ResponseFormat},
+    state::AppState,
+  },
+};
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+/// Bosses are only added when a new raid shows up.
+const BOSSES_MAX_AGE: u32 = 60;
+const BOSS_MAX_AGE: u32 = 300;
+/// The first page of raid tweets changes every few seconds.
+const RAID_TWEETS_MAX_AGE: u32 = 5;
+
+#[derive(Deserialize)]
+pub struct ListBossesRequest {
+  /// Zero or absent means every level.
+  pub level: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct ListRaidTweetsRequest {
+  pub limit: Option<usize>,
+  /// Tweet id cursor, only raid tweets older than it are returned.
+  pub before: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RaidTweetsPageJson {
+  tweets: Vec<RaidTweet>,
+  /// Pass it as `before` to get the next page, `None` if there is no more raid tweets.
+  next_before: Option<String>,
+}
+
+///
+/// `GET /v1/bosses?level=200`
+/// Bosses ordered by level then name, JSON `RaidBoss` objects or a `GetBossesResponse` message.
+///
+pub async fn list_bosses(
+  request: ListBossesRequest,
+  format: ResponseFormat,
+  if_none_match: Option<String>,
+  app_state: AppState,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let bytes = find_bosses(&app_state, request.level.unwrap_or(0))
+    .await
+    .map_err(|error| error.reject())?;
+  // Redis returns keys in no particular order, sort them so the `ETag` is stable.
+  let mut bosses = decode_all::<RaidBoss>(bytes);
+  bosses.sort_by(|a, b| (a.level, &a.en_name, &a.jp_name).cmp(&(b.level, &b.en_name, &b.jp_name)));
+
+  let encoded = match format {
+    ResponseFormat::Protobuf => Encoded::protobuf(&GetBossesResponse { bosses }),
+    _ => Encoded::json(&bosses),
+  };
+
+  Ok(cache::reply(encoded, if_none_match.as_deref(), BOSSES_MAX_AGE))
+}
+
+///
+/// `GET /v1/bosses/{name}`
+/// A boss by its english or japanese name, JSON `RaidBoss` object or a `RaidBoss` message.
+///
+pub async fn get_boss(
+  boss_name: String,
+  format: ResponseFormat,
+  if_none_match: Option<String>,
+  app_state: AppState,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let boss_name = decode_boss_name(&boss_name)?;
+  let pattern = escape_key_pattern(&boss_name);
+  let keys = app_state
+    .redis
+    .keys(gbf_raid_boss_name_keys(&app_state.config.redis_prefix, &pattern))
+    .await
+    .map_err(|_| HttpError::CannotGetRedisKeysError.reject())?;
+  let bytes = app_state
+    .redis
+    .mget_protobuf_raw(keys)
+    .await
+    .map_err(|_| HttpError::CannotMGetRedisError.reject())?;
+  // Key patterns are globs, make sure the name is exactly matched.
+  let raid_boss = decode_all::<RaidBoss>(bytes)
+    .into_iter()
+    .find(|raid_boss| raid_boss.en_name == boss_name || raid_boss.jp_name == boss_name)
+    .ok_or_else(|| HttpError::BossNotFoundError.reject())?;
+
+  let encoded = match format {
+    ResponseFormat::Protobuf => Encoded::protobuf(&raid_boss),
+    _ => Encoded::json(&raid_boss),
+  };
+
+  Ok(cache::reply(encoded, if_none_match.as_deref(), BOSS_MAX_AGE))
+}
+
+///
+/// `GET /v1/bosses/{name}/raids?limit=20&before=1391331698651443207`
+/// Persisted raid tweets of a boss, newest first, JSON object or a `RaidTweetsPage` message.
+///
+pub async fn list_raid_tweets(
+  boss_name: String,
+  request: ListRaidTweetsRequest,
+  format: ResponseFormat,
+  if_none_match: Option<String>,
+  app_state: AppState,
+) -> Result<impl warp::Reply, warp::Rejection> {
+  let boss_name = decode_boss_name(&boss_name)?;
+  let limit = request.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+  let before = request
+    .before
+    .map(|before| before.parse::<u64>())
+    .transpose()
+    .map_err(|_| HttpError::InvalidCursorError.reject())?;
+
+  let prefix = &app_state.config.redis_prefix;
+  let keys = app_state
+    .redis
+    .keys(gbf_persistence_raid_tweets_keys(prefix, escape_key_pattern(&boss_name)))
+    .await
+    .map_err(|_| HttpError::CannotGetRedisKeysError.reject())?;
+  let (keys, next_before) = page_keys(keys, prefix, &boss_name, before, limit);
+  let bytes = app_state
+    .redis
+    .mget_protobuf_raw(keys)
+    .await
+    .map_err(|_| HttpError::CannotMGetRedisError.reject())?;
+  let tweets = decode_all::<RaidTweet>(bytes);
+  let next_before = next_before.map(|tweet_id| tweet_id.to_string());
+
+  let encoded = match format {
+    ResponseFormat::Protobuf => Encoded::protobuf(&RaidTweetsPage {
+      tweets,
+      next_before: next_before.unwrap_or_default(),
+    }),
+    _ => Encoded::json(&RaidTweetsPageJson { tweets, next_before }),
+  };
+
+  Ok(cache::reply(encoded, if_none_match.as_deref(), RAID_TWEETS_MAX_AGE))
+}
+
+/// Path segments are not percent-decoded by warp, boss names are mostly japanese.
+fn decode_boss_name(boss_name: &str) -> Result<String, warp::Rejection> {
+  percent_decode_str(boss_name)
+    .decode_utf8()
+    .map(|boss_name| boss_name.into_owned())
+    .map_err(|_| HttpError::BossNotFoundError.reject())
+}
+
+///
+/// Pick a page of persistence keys of the boss older than the cursor, newest first.
+/// Tweet ids are increasing with time, so they are used as the cursor.
+/// Keys of other bosses are skipped, ex. `Lv120 Mt. Qilin.*` also matches keys of `Lv120 Mt. Qilin.Impossible`.
+///
+/// Return the keys and the cursor of the next page.
+///
+fn page_keys(
+  keys: Vec<String>,
+  prefix: &str,
+  boss_name: &str,
+  before: Option<u64>,
+  limit: usize,
+) -> (Vec<String>, Option<u64>) {
+  let before = before.unwrap_or(u64::MAX);
+  let mut keys = keys
+    .into_iter()
+    .filter_map(|key| {
+      gbf_persistence_raid_tweet_key_parts_of(prefix, boss_name, &key).map(|(tweet_id, _)| (tweet_id, key))
+    })
+    .filter(|(tweet_id, _)| *tweet_id < before)
+    .collect::<Vec<_>>();
+  keys.sort_by_key(|(tweet_id, _)| std::cmp::Reverse(*tweet_id));
+  let next_before = match keys.len() > limit {
+    true => Some(keys[limit - 1].0),
+    false => None,
+  };
+  keys.truncate(limit);
+
+  (keys.into_iter().map(|(_, key)| key).collect(), next_before)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{common::redis::gbf_persistence_raid_tweet_key, resources::redis::DEFAULT_PREFIX};
+
+  const BOSS_NAME: &str = "Lv120 Mt. Qilin";
+
+  fn key(tweet_id: u64) -> String {
+    gbf_persistence_raid_tweet_key(DEFAULT_PREFIX, BOSS_NAME, tweet_id, 1620698515453)
+  }
+
+  fn page(keys: Vec<String>, before: Option<u64>, limit: usize) -> (Vec<String>, Option<u64>) {
+    page_keys(keys, DEFAULT_PREFIX, BOSS_NAME, before, limit)
+  }
+
+  #[test]
+  fn test_page_keys() {
+    let keys = vec![key(3), key(1), key(5), key(4), key(2), "gbf:persistence:broken".into()];
+    assert_eq!((vec![key(5), key(4)], Some(4)), page(keys.clone(), None, 2));
+    assert_eq!((vec![key(3), key(2)], Some(2)), page(keys.clone(), Some(4), 2));
+    assert_eq!((vec![key(1)], None), page(keys.clone(), Some(2), 2));
+    assert_eq!((vec![key(2), key(1)], None), page(keys, Some(3), 2));
+  }
+
+  #[test]
+  fn test_page_keys_of_boss() {
+    let other = gbf_persistence_raid_tweet_key(DEFAULT_PREFIX, "Lv120 Mt. Qilin.Impossible", 6, 1620698515453);
+    let keys = vec![key(1), other, key(2)];
+    assert_eq!((vec![key(2), key(1)], None), page(keys.clone(), None, 10));
+    // A `*` boss name only matches keys of a boss named `*`.
+    let star = gbf_persistence_raid_tweet_key(DEFAULT_PREFIX, "*", 3, 1620698515453);
+    let mut with_star = keys.clone();
+    with_star.push(star.clone());
+    assert_eq!(
+      (vec![star], None),
+      page_keys(with_star, DEFAULT_PREFIX, "*", None, 10)
+    );
+    assert_eq!(
+      r"gbf:persistence:\*.*",
+      gbf_persistence_raid_tweets_keys(DEFAULT_PREFIX, escape_key_pattern("*"))
+    );
+  }
+
+  #[test]
+  fn test_decode_boss_name() {
+    let boss_name = decode_boss_name("Lv200%20%E3%82%A2%E3%83%BC%E3%82%AB%E3%83%BC%E3%82%B7%E3%83%A3").ok();
+    assert_eq!(Some("Lv200 アーカーシャ".to_owned()), boss_name);
+    assert!(decode_boss_name("%FF").is_err());
+  }
+}
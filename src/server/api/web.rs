@@ -0,0 +1,80 @@
+use crate::{
+  resources::web::ASSET_MAX_AGE,
+  server::{cache, negotiation::Encoded},
+};
+
+///
+/// A file of the built-in web UI, embedded into the binary so a single container is a complete raid finder.
+///
+struct Asset {
+  name: &'static str,
+  content_type: &'static str,
+  body: &'static str,
+}
+
+const INDEX: Asset = Asset {
+  name: "index.html",
+  content_type: "text/html; charset=utf-8",
+  body: include_str!("../web/index.html"),
+};
+
+const ASSETS: &[Asset] = &[
+  Asset {
+    name: "app.js",
+    content_type: "text/javascript; charset=utf-8",
+    body: include_str!("../web/app.js"),
+  },
+  Asset {
+    name: "app.css",
+    content_type: "text/css; charset=utf-8",
+    body: include_str!("../web/app.css"),
+  },
+];
+
+fn reply(asset: &Asset, if_none_match: Option<String>) -> warp::reply::Response {
+  let encoded = Encoded {
+    content_type: asset.content_type,
+    body: asset.body.as_bytes().to_vec(),
+  };
+
+  cache::reply(encoded, if_none_match.as_deref(), ASSET_MAX_AGE)
+}
+
+///
+/// The page of the built-in web UI.
+///
+pub fn index(if_none_match: Option<String>) -> impl warp::Reply {
+  reply(&INDEX, if_none_match)
+}
+
+///
+/// Scripts and styles of the built-in web UI, ex. `/web/app.js`.
+///
+pub async fn asset(name: String, if_none_match: Option<String>) -> Result<impl warp::Reply, warp::Rejection> {
+  match ASSETS.iter().find(|asset| asset.name == name) {
+    Some(asset) => Ok(reply(asset, if_none_match)),
+    None => Err(warp::reject::not_found()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use warp::{http::StatusCode, Reply};
+
+  #[tokio::test]
+  async fn test_asset() {
+    let response = asset("app.js".to_owned(), None).await.unwrap().into_response();
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!("text/javascript; charset=utf-8", response.headers()["content-type"]);
+    let etag = response.headers()["etag"].to_str().unwrap().to_owned();
+    let response = asset("app.js".to_owned(), Some(etag)).await.unwrap().into_response();
+    assert_eq!(StatusCode::NOT_MODIFIED, response.status());
+
+    assert!(asset("../Cargo.toml".to_owned(), None).await.is_err());
+    // Every asset referenced by the page is embedded.
+    for asset in ASSETS {
+      assert!(INDEX.body.contains(&format!("/web/{}", asset.name)));
+    }
+  }
+}
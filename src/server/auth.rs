@@ -0,0 +1,477 @@
+use crate::{
+  client::redis::Redis,
+  common::{
+    rate_limit::{ConnectionGuard, ConnectionLimiter, RateLimiter},
+    redis::gbf_api_key,
+  },
+  config::{ApiKeyStore, Config},
+  error::{self, HttpError},
+  resources::{
+    auth::{
+      API_KEY_HEADER, API_KEY_PROTOCOL, API_KEY_PROTOCOL_PREFIX, DEFAULT_MAX_BOSS_NAMES, DEFAULT_MAX_CONNECTIONS,
+      DEFAULT_REQUESTS_PER_MINUTE,
+    },
+    throttle::MAX_BOSS_NAMES,
+  },
+  Result,
+};
+use log::error;
+use serde::Deserialize;
+use std::{collections::HashMap, convert::Infallible, sync::Arc, time::Duration};
+use warp::{reply::Response, Filter, Rejection, Reply};
+
+///
+/// Name and limits of a client API key, `0` means unlimited.
+///
+/// Stored as a redis hash of `{prefix}:api_key:{key}` or an entry of the API keys file,
+/// fields which are not set fallback to the defaults in `resources::auth`.
+///
+/// # Example
+///
+/// ```
+/// HSET gbf:api_key:3a0f1c name raid-helper max_connections 2 max_boss_names 20 requests_per_minute 60
+/// {"3a0f1c": {"name": "raid-helper", "max_connections": 2, "max_boss_names": 20, "requests_per_minute": 60}}
+/// HSET gbf:api_key:9e4b27 name maintainer admin true
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ApiKey {
+  pub name: String,
+  /// Concurrent websocket and SSE streams.
+  pub max_connections: u32,
+  /// Subscribed boss names of each stream.
+  pub max_boss_names: u32,
+  /// Http requests, including stream handshakes.
+  pub requests_per_minute: u32,
+  /// Admin keys could call the admin APIs, ex. to fix translator pairs.
+  pub admin: bool,
+}
+
+impl Default for ApiKey {
+  fn default() -> Self {
+    ApiKey {
+      name: "".into(),
+      max_connections: DEFAULT_MAX_CONNECTIONS,
+      max_boss_names: DEFAULT_MAX_BOSS_NAMES,
+      requests_per_minute: DEFAULT_REQUESTS_PER_MINUTE,
+      admin: false,
+    }
+  }
+}
+
+impl ApiKey {
+  /// Read an API key from the fields of its redis hash, `None` if the hash does not exist.
+  fn from_hash(fields: HashMap<String, String>) -> Option<Self> {
+    if fields.is_empty() {
+      return None;
+    }
+    let mut api_key = ApiKey::default();
+    let limit = |name: &str, default: u32| fields.get(name).and_then(|value| value.parse().ok()).unwrap_or(default);
+    api_key.max_connections = limit("max_connections", api_key.max_connections);
+    api_key.max_boss_names = limit("max_boss_names", api_key.max_boss_names);
+    api_key.requests_per_minute = limit("requests_per_minute", api_key.requests_per_minute);
+    api_key.name = fields.get("name").cloned().unwrap_or_default();
+    api_key.admin = matches!(fields.get("admin").map(String::as_str), Some("true") | Some("1"));
+
+    Some(api_key)
+  }
+}
+
+enum KeyStore {
+  None,
+  Redis { redis: Arc<Redis>, prefix: String },
+  File(HashMap<String, ApiKey>),
+}
+
+///
+/// API key authentication of the public http listeners
+///
+/// Request rates and concurrent streams are counted in this process,
+/// so the limits apply to each replica when the server is scaled horizontally.
+///
+/// # Arguments
+/// * `store`: where API keys are looked up, redis keys are looked up on every request so revoking takes effect at once.
+/// * `requests`: token buckets of `ApiKey.requests_per_minute`.
+/// * `connections`: open streams of each API key.
+///
+pub struct Auth {
+  store: KeyStore,
+  requests: RateLimiter<String>,
+  connections: Arc<ConnectionLimiter<String>>,
+}
+
+impl Auth {
+  pub fn new(config: &Config, redis: Arc<Redis>) -> Result<Self> {
+    let store = match &config.api_key_store {
+      ApiKeyStore::None => KeyStore::None,
+      ApiKeyStore::Redis => KeyStore::Redis {
+        redis,
+        prefix: config.redis_prefix.clone(),
+      },
+      ApiKeyStore::File(path) => KeyStore::File(load_api_keys(path)?),
+    };
+
+    Ok(Self::with_store(store))
+  }
+
+  fn with_store(store: KeyStore) -> Self {
+    Auth {
+      store,
+      requests: RateLimiter::new(),
+      connections: Arc::new(ConnectionLimiter::new()),
+    }
+  }
+
+  #[cfg(test)]
+  pub fn with_api_keys(api_keys: serde_json::Value) -> Self {
+    Self::with_store(KeyStore::File(serde_json::from_value(api_keys).unwrap()))
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    !matches!(self.store, KeyStore::None)
+  }
+
+  async fn find(&self, key: &str) -> std::result::Result<Option<ApiKey>, HttpError> {
+    match &self.store {
+      KeyStore::None => Ok(None),
+      KeyStore::Redis { redis, prefix } => match redis.hgetall(gbf_api_key(prefix, key)).await {
+        Ok(fields) => Ok(ApiKey::from_hash(fields)),
+        Err(e) => {
+          error!("Cannot get API key from redis, error: {}", e);
+          Err(HttpError::CannotGetApiKeyError)
+        }
+      },
+      KeyStore::File(api_keys) => Ok(api_keys.get(key).cloned()),
+    }
+  }
+
+  ///
+  /// Check the API key of a request and take a token of its request rate.
+  /// Return `None` if authentication is disabled.
+  ///
+  pub async fn authorize(&self, key: Option<String>) -> std::result::Result<Option<Principal>, HttpError> {
+    if !self.is_enabled() {
+      return Ok(None);
+    }
+    let key = key.filter(|key| !key.is_empty()).ok_or(HttpError::MissingApiKeyError)?;
+    let api_key = self.find(&key).await?.ok_or(HttpError::InvalidApiKeyError)?;
+    let rate = api_key.requests_per_minute;
+    if rate > 0 && !self.requests.check(&key, rate, Duration::from_secs(60)) {
+      return Err(HttpError::RateLimitedError);
+    }
+
+    Ok(Some(Principal { key, api_key }))
+  }
+
+  /// Count a new stream of `principal`, the stream should hold the permit until it is closed.
+  pub fn acquire(&self, principal: Principal) -> std::result::Result<ConnectionPermit, HttpError> {
+    let guard = self
+      .connections
+      .acquire(principal.key, principal.api_key.max_connections)
+      .ok_or(HttpError::TooManyConnectionsError)?;
+
+    Ok(ConnectionPermit {
+      api_key: principal.api_key,
+      _guard: guard,
+    })
+  }
+}
+
+/// Read the API keys file, a JSON object of API keys to their `ApiKey`.
+fn load_api_keys(path: &str) -> Result<HashMap<String, ApiKey>> {
+  let content = std::fs::read_to_string(path).map_err(|error| error::Error::ApiKeysFileRead {
+    path: path.to_owned(),
+    error,
+  })?;
+
+  serde_json::from_str(&content).map_err(|error| error::Error::ApiKeysFileParse {
+    path: path.to_owned(),
+    error,
+  })
+}
+
+/// An authenticated API key.
+#[derive(Debug, Clone)]
+pub struct Principal {
+  key: String,
+  pub api_key: ApiKey,
+}
+
+///
+/// A stream counted in `ApiKey.max_connections`, it is released when dropped.
+///
+pub struct ConnectionPermit {
+  api_key: ApiKey,
+  _guard: ConnectionGuard<String>,
+}
+
+impl ConnectionPermit {
+  pub fn name(&self) -> &str {
+    &self.api_key.name
+  }
+
+  pub fn max_boss_names(&self) -> usize {
+    match self.api_key.max_boss_names {
+      0 => usize::MAX,
+      max => max as usize,
+    }
+  }
+}
+
+/// Max boss names a stream could subscribe, `MAX_BOSS_NAMES` without authentication.
+pub fn max_boss_names(permit: &Option<ConnectionPermit>) -> usize {
+  permit.as_ref().map_or(MAX_BOSS_NAMES, ConnectionPermit::max_boss_names)
+}
+
+/// `?api_key=` of streams, other query parameters are read by the stream handlers.
+#[derive(Deserialize)]
+struct ApiKeyQuery {
+  api_key: Option<String>,
+}
+
+/// The API key of `Sec-WebSocket-Protocol`, it is one of the comma separated subprotocols.
+fn protocol_api_key(protocols: &str) -> Option<String> {
+  protocols
+    .split(',')
+    .find_map(|protocol| protocol.trim().strip_prefix(API_KEY_PROTOCOL_PREFIX))
+    .filter(|key| !key.is_empty())
+    .map(str::to_owned)
+}
+
+///
+/// Select `API_KEY_PROTOCOL` in the websocket handshake if the client sent it,
+/// browsers close websockets whose subprotocols are not answered.
+///
+pub fn with_api_key_protocol(reply: impl Reply, protocols: Option<String>) -> Response {
+  let offered = protocols
+    .iter()
+    .flat_map(|protocols| protocols.split(','))
+    .any(|protocol| protocol.trim() == API_KEY_PROTOCOL);
+
+  match offered {
+    true => warp::reply::with_header(reply, "sec-websocket-protocol", API_KEY_PROTOCOL).into_response(),
+    false => reply.into_response(),
+  }
+}
+
+fn with_auth(auth: Arc<Auth>) -> impl Filter<Extract = (Arc<Auth>,), Error = Infallible> + Clone {
+  warp::any().map(move || auth.clone())
+}
+
+///
+/// Require the API key header if authentication is enabled.
+///
+pub fn authorize(auth: Arc<Auth>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+  warp::header::optional::<String>(API_KEY_HEADER)
+    .and(with_auth(auth))
+    .and_then(|key: Option<String>, auth: Arc<Auth>| async move {
+      auth.authorize(key).await.map(|_| ()).map_err(|e| e.reject())
+    })
+    .untuple_one()
+}
+
+///
+/// Require the API key header of an admin key, admin APIs are disabled without authentication.
+///
+pub fn authorize_admin(auth: Arc<Auth>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+  warp::header::optional::<String>(API_KEY_HEADER)
+    .and(with_auth(auth))
+    .and_then(|key: Option<String>, auth: Arc<Auth>| async move {
+      match auth.authorize(key).await {
+        Ok(Some(principal)) if principal.api_key.admin => Ok(()),
+        Ok(Some(_)) => Err(HttpError::AdminRequiredError.reject()),
+        Ok(None) => Err(HttpError::AdminDisabledError.reject()),
+        Err(e) => Err(e.reject()),
+      }
+    })
+    .untuple_one()
+}
+
+///
+/// Require the API key header, websocket subprotocol or query parameter of a stream if authentication is enabled,
+/// and count the stream in the concurrent streams of the API key.
+///
+pub fn authorize_stream(
+  auth: Arc<Auth>,
+) -> impl Filter<Extract = (Option<ConnectionPermit>,), Error = Rejection> + Clone {
+  warp::header::optional::<String>(API_KEY_HEADER)
+    .and(warp::header::optional::<String>("sec-websocket-protocol"))
+    .and(
+      warp::query::<ApiKeyQuery>()
+        .or(warp::any().map(|| ApiKeyQuery { api_key: None }))
+        .unify(),
+    )
+    .and(with_auth(auth))
+    .and_then(
+      |header: Option<String>, protocols: Option<String>, query: ApiKeyQuery, auth: Arc<Auth>| async move {
+        let key = header
+          .or_else(|| protocols.as_deref().and_then(protocol_api_key))
+          .or(query.api_key);
+        match auth.authorize(key).await {
+          Ok(Some(principal)) => auth.acquire(principal).map(Some).map_err(|e| e.reject()),
+          Ok(None) => Ok(None),
+          Err(e) => Err(e.reject()),
+        }
+      },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn auth(api_keys: serde_json::Value) -> Arc<Auth> {
+    Arc::new(Auth::with_api_keys(api_keys))
+  }
+
+  fn error_code(body: &[u8]) -> String {
+    let body: serde_json::Value = serde_json::from_slice(body).unwrap();
+
+    body["error"]["code"].as_str().unwrap().to_owned()
+  }
+
+  #[test]
+  fn test_api_key_from_hash() {
+    assert_eq!(None, ApiKey::from_hash(HashMap::new()));
+    let mut fields = HashMap::new();
+    fields.insert("name".to_owned(), "raid-helper".to_owned());
+    fields.insert("max_connections".to_owned(), "2".to_owned());
+    fields.insert("requests_per_minute".to_owned(), "invalid".to_owned());
+    let api_key = ApiKey::from_hash(fields.clone()).unwrap();
+    assert_eq!("raid-helper", api_key.name);
+    assert_eq!(2, api_key.max_connections);
+    assert_eq!(DEFAULT_MAX_BOSS_NAMES, api_key.max_boss_names);
+    assert_eq!(DEFAULT_REQUESTS_PER_MINUTE, api_key.requests_per_minute);
+    assert!(!api_key.admin);
+    fields.insert("admin".to_owned(), "true".to_owned());
+    assert!(ApiKey::from_hash(fields).unwrap().admin);
+  }
+
+  #[test]
+  fn test_load_errors() {
+    let missing = std::env::temp_dir().join("raid-finder-missing-api-keys.json");
+    assert!(matches!(
+      load_api_keys(&missing.to_string_lossy()),
+      Err(error::Error::ApiKeysFileRead { .. })
+    ));
+    let invalid = std::env::temp_dir().join(format!("raid-finder-invalid-api-keys-{}.json", std::process::id()));
+    std::fs::write(&invalid, r#"["3a0f1c"]"#).unwrap();
+    assert!(matches!(
+      load_api_keys(&invalid.to_string_lossy()),
+      Err(error::Error::ApiKeysFileParse { .. })
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_authorize() {
+    let auth = auth(serde_json::json!({"3a0f1c": {"name": "raid-helper", "requests_per_minute": 2}}));
+    let filter = authorize(auth)
+      .map(warp::reply)
+      .recover(crate::server::recover::handle_rejection);
+
+    let response = warp::test::request().reply(&filter).await;
+    assert_eq!(401, response.status());
+    assert_eq!("missing_api_key", error_code(response.body()));
+    let response = warp::test::request()
+      .header(API_KEY_HEADER, "unknown")
+      .reply(&filter)
+      .await;
+    assert_eq!(401, response.status());
+    assert_eq!("invalid_api_key", error_code(response.body()));
+    for _ in 0..2 {
+      let response = warp::test::request()
+        .header(API_KEY_HEADER, "3a0f1c")
+        .reply(&filter)
+        .await;
+      assert_eq!(200, response.status());
+    }
+    let response = warp::test::request()
+      .header(API_KEY_HEADER, "3a0f1c")
+      .reply(&filter)
+      .await;
+    assert_eq!(429, response.status());
+    assert_eq!("rate_limited", error_code(response.body()));
+  }
+
+  #[tokio::test]
+  async fn test_authorize_disabled() {
+    let config = Config::default();
+    let auth = Arc::new(Auth::new(&config, Arc::new(Redis::new("redis://127.0.0.1/").unwrap())).unwrap());
+    assert!(!auth.is_enabled());
+    assert!(warp::test::request().filter(&authorize(auth.clone())).await.is_ok());
+    let permit = warp::test::request().filter(&authorize_stream(auth)).await.unwrap();
+    assert_eq!(MAX_BOSS_NAMES, max_boss_names(&permit));
+  }
+
+  #[tokio::test]
+  async fn test_authorize_admin() {
+    let auth = auth(serde_json::json!({"3a0f1c": {}, "9e4b27": {"admin": true}}));
+    let filter = authorize_admin(auth);
+    let reject = |key: &str| {
+      let request = warp::test::request().header(API_KEY_HEADER, key);
+      async { request.filter(&filter).await.err().unwrap() }
+    };
+
+    let rejection = reject("3a0f1c").await;
+    let rejection = rejection.find::<error::HttpRejection>().unwrap();
+    assert_eq!(("admin_required", 403), (rejection.error, rejection.code));
+    let rejection = reject("unknown").await;
+    assert_eq!("invalid_api_key", rejection.find::<error::HttpRejection>().unwrap().error);
+    assert!(warp::test::request()
+      .header(API_KEY_HEADER, "9e4b27")
+      .filter(&filter)
+      .await
+      .is_ok());
+
+    let auth = Arc::new(Auth::new(&Config::default(), Arc::new(Redis::new("redis://127.0.0.1/").unwrap())).unwrap());
+    let rejection = warp::test::request().filter(&authorize_admin(auth)).await.err().unwrap();
+    assert_eq!("admin_disabled", rejection.find::<error::HttpRejection>().unwrap().error);
+  }
+
+  #[tokio::test]
+  async fn test_authorize_stream() {
+    let auth = auth(serde_json::json!({"3a0f1c": {"max_connections": 1, "max_boss_names": 2}}));
+    let filter = authorize_stream(auth.clone());
+
+    let permit = warp::test::request()
+      .path("/stream_bosses?protocol=2&api_key=3a0f1c")
+      .filter(&filter)
+      .await
+      .unwrap();
+    assert_eq!(2, max_boss_names(&permit));
+    let rejection = warp::test::request()
+      .header(API_KEY_HEADER, "3a0f1c")
+      .filter(&filter)
+      .await
+      .err()
+      .unwrap();
+    let rejection = rejection.find::<error::HttpRejection>().unwrap();
+    assert_eq!(("too_many_connections", 429), (rejection.error, rejection.code));
+
+    drop(permit);
+    assert_eq!(0, auth.connections.count(&"3a0f1c".to_owned()));
+    let permit = warp::test::request()
+      .header(API_KEY_HEADER, "3a0f1c")
+      .filter(&filter)
+      .await;
+    assert!(permit.unwrap().is_some());
+  }
+
+  #[tokio::test]
+  async fn test_authorize_stream_protocol() {
+    let auth = auth(serde_json::json!({"3a0f1c": {"max_boss_names": 2}}));
+    let permit = warp::test::request()
+      .header("sec-websocket-protocol", "raid-finder, raid-finder.api-key.3a0f1c")
+      .filter(&authorize_stream(auth))
+      .await
+      .unwrap();
+    assert_eq!(2, max_boss_names(&permit));
+
+    assert_eq!(None, protocol_api_key("raid-finder, raid-finder.api-key."));
+    let response = with_api_key_protocol(warp::reply(), Some("raid-finder, raid-finder.api-key.3a0f1c".to_owned()));
+    assert_eq!("raid-finder", response.headers()["sec-websocket-protocol"]);
+    assert!(!with_api_key_protocol(warp::reply(), None)
+      .headers()
+      .contains_key("sec-websocket-protocol"));
+  }
+}
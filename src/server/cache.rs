@@ -0,0 +1,86 @@
+use crate::server::negotiation::Encoded;
+use sha1::{Digest, Sha1};
+use warp::{
+  http::{
+    header::{HeaderValue, CACHE_CONTROL, ETAG, VARY},
+    StatusCode,
+  },
+  reply::Response,
+  Filter, Reply,
+};
+
+/// Strong `ETag` of a response body.
+pub fn etag(body: &[u8]) -> String {
+  format!("\"{:x}\"", Sha1::digest(body))
+}
+
+///
+/// Whether the client already has the response, `If-None-Match` may contain a list of tags or `*`.
+/// Weak comparison is used as RFC 7232 requires for `If-None-Match`.
+///
+pub fn is_not_modified(if_none_match: Option<&str>, etag: &str) -> bool {
+  if_none_match
+    .into_iter()
+    .flat_map(|tags| tags.split(','))
+    .map(|tag| tag.trim())
+    .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+pub fn if_none_match() -> impl Filter<Extract = (Option<String>,), Error = warp::Rejection> + Clone {
+  warp::header::optional::<String>("if-none-match")
+}
+
+///
+/// Reply with `ETag` and `Cache-Control`, or `304 Not Modified` if the client has the same body.
+///
+/// # Arguments
+/// * `encoded` - the negotiated response body.
+/// * `if_none_match` - `If-None-Match` header of the request.
+/// * `max_age` - seconds shared caches and clients could reuse the response.
+///
+pub fn reply(encoded: Encoded, if_none_match: Option<&str>, max_age: u32) -> Response {
+  let etag = etag(&encoded.body);
+  let mut response = match is_not_modified(if_none_match, &etag) {
+    true => StatusCode::NOT_MODIFIED.into_response(),
+    false => encoded.into_response(),
+  };
+  let headers = response.headers_mut();
+  if let Ok(etag) = HeaderValue::from_str(&etag) {
+    headers.insert(ETAG, etag);
+  }
+  if let Ok(cache_control) = HeaderValue::from_str(&format!("public, max-age={}", max_age)) {
+    headers.insert(CACHE_CONTROL, cache_control);
+  }
+  headers.insert(VARY, HeaderValue::from_static("accept"));
+
+  response
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_is_not_modified() {
+    let etag = etag(b"[]");
+    assert!(!is_not_modified(None, &etag));
+    assert!(!is_not_modified(Some("\"other\""), &etag));
+    assert!(is_not_modified(Some(&etag), &etag));
+    assert!(is_not_modified(Some(&format!("\"other\", W/{}", etag)), &etag));
+    assert!(is_not_modified(Some("*"), &etag));
+  }
+
+  #[test]
+  fn test_reply() {
+    let response = reply(Encoded::json(&vec!["Lv200 アーカーシャ"]), None, 60);
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!("public, max-age=60", response.headers()[CACHE_CONTROL]);
+    let etag = response.headers()[ETAG].to_str().unwrap().to_owned();
+
+    let response = reply(Encoded::json(&vec!["Lv200 アーカーシャ"]), Some(&etag), 60);
+    assert_eq!(StatusCode::NOT_MODIFIED, response.status());
+    assert_eq!(etag, response.headers()[ETAG]);
+    let response = reply(Encoded::json(&vec!["Lv150 プロトバハムート"]), Some(&etag), 60);
+    assert_eq!(StatusCode::OK, response.status());
+  }
+}
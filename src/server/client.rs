@@ -1,30 +1,216 @@
-use tokio::sync::mpsc;
+use crate::{config::OverflowPolicy, server::queue::ClientQueue};
+use bytes::Bytes;
+use std::{
+  collections::{HashSet, VecDeque},
+  sync::{Arc, Mutex},
+};
+
+/// How many delivered tweet ids each client remembers, it only needs to cover the seam of a catch-up replay.
+const RECENT_TWEETS_CAPACITY: usize = 512;
 
 ///
 /// Granblue Fantasy Finder websocket client
 /// 
 /// # Arguments
 /// * `boss_names`: came from client message, should include the raid boss name which client want to receive.
-/// * `sender`: A sender which can send the tweet to the client.
+/// * `queue`: A bounded queue which can send the tweet to the client.
+/// * `delivery`: tweet ids which are recently sent to the client, and live tweets held during a catch-up replay.
 /// 
 #[derive(Clone)]
 pub struct FinderClient {
   pub boss_names: Vec<String>,
-  pub sender: mpsc::UnboundedSender<std::result::Result<warp::ws::Message, warp::Error>>,
+  pub queue: Arc<ClientQueue>,
+  pub delivery: Arc<Mutex<Delivery>>,
 }
 
 impl FinderClient {
-  pub fn new<V, S>(
-    boss_names: V,
-    sender: mpsc::UnboundedSender<std::result::Result<warp::ws::Message, warp::Error>>,
-  ) -> Self
+  pub fn new<V, S>(boss_names: V, queue: Arc<ClientQueue>) -> Self
   where
     S: Into<String>,
     V: IntoIterator<Item = S>,
   {
     FinderClient {
       boss_names: boss_names.into_iter().map(|k| k.into()).collect::<Vec<_>>(),
-      sender,
+      queue,
+      delivery: Arc::new(Mutex::new(Delivery {
+        recent_tweets: RecentTweets::new(RECENT_TWEETS_CAPACITY),
+        held: None,
+      })),
+    }
+  }
+
+  ///
+  /// Send a live raid tweet to the client unless the same tweet is already sent.
+  /// Live tweets are held while a catch-up replay is sent, so the client receives them after the replay.
+  /// Return false if the tweet is skipped or the client is gone.
+  /// The tweet might still be dropped by the overflow policy of the queue.
+  ///
+  pub fn send_raid_tweet(&self, tweet_id: u64, bytes: Bytes) -> bool {
+    let mut delivery = self.delivery.lock().unwrap();
+    match delivery.held.is_some() {
+      true => delivery.hold(&self.queue, tweet_id, bytes),
+      false => delivery.send(&self.queue, tweet_id, bytes),
     }
   }
+
+  ///
+  /// Remember a raid tweet which is sent ahead of the queue, return false if it is already sent.
+  /// Replayed raid tweets are sent this way, the queue only has room for live tweets.
+  ///
+  pub fn mark_sent(&self, tweet_id: u64) -> bool {
+    self.delivery.lock().unwrap().recent_tweets.insert(tweet_id)
+  }
+
+  ///
+  /// Hold live raid tweets until `release_live_tweets`.
+  /// It should be called before the subscriptions are changed, so no live tweet of them is queued before the replay.
+  ///
+  pub fn hold_live_tweets(&self) {
+    self.delivery.lock().unwrap().held.get_or_insert_with(VecDeque::new);
+  }
+
+  /// Send the live raid tweets held during a replay, tweets which are already replayed are skipped.
+  pub fn release_live_tweets(&self) {
+    let mut delivery = self.delivery.lock().unwrap();
+    for (tweet_id, bytes) in delivery.held.take().unwrap_or_default() {
+      delivery.send(&self.queue, tweet_id, bytes);
+    }
+  }
+}
+
+///
+/// Delivery state of a client, shared by live delivery and catch-up replay.
+///
+pub struct Delivery {
+  recent_tweets: RecentTweets,
+  /// Live raid tweets received during a replay, `None` if the client is not replaying.
+  held: Option<VecDeque<(u64, Bytes)>>,
+}
+
+impl Delivery {
+  fn send(&mut self, queue: &ClientQueue, tweet_id: u64, bytes: Bytes) -> bool {
+    self.recent_tweets.insert(tweet_id) && queue.push(bytes)
+  }
+
+  /// Hold a live raid tweet, held tweets are bounded by the capacity and the overflow policy of the queue.
+  fn hold(&mut self, queue: &ClientQueue, tweet_id: u64, bytes: Bytes) -> bool {
+    let held = self.held.get_or_insert_with(VecDeque::new);
+    if held.len() >= queue.capacity() {
+      queue.overflow();
+      match queue.policy() {
+        OverflowPolicy::DropOldest => {
+          held.pop_front();
+        }
+        OverflowPolicy::DropNewest => return true,
+        OverflowPolicy::Disconnect => return false,
+      }
+    }
+    held.push_back((tweet_id, bytes));
+
+    true
+  }
+}
+
+///
+/// A bounded set of tweet ids, the oldest id is forgotten when it is full.
+///
+pub struct RecentTweets {
+  ids: HashSet<u64>,
+  order: VecDeque<u64>,
+  capacity: usize,
+}
+
+impl RecentTweets {
+  pub fn new(capacity: usize) -> Self {
+    RecentTweets {
+      ids: HashSet::with_capacity(capacity),
+      order: VecDeque::with_capacity(capacity),
+      capacity,
+    }
+  }
+
+  /// Return false if the id is already in the set.
+  pub fn insert(&mut self, tweet_id: u64) -> bool {
+    if !self.ids.insert(tweet_id) {
+      return false;
+    }
+    self.order.push_back(tweet_id);
+    if self.order.len() > self.capacity {
+      if let Some(oldest) = self.order.pop_front() {
+        self.ids.remove(&oldest);
+      }
+    }
+
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_recent_tweets() {
+    let mut recent_tweets = RecentTweets::new(2);
+    assert!(recent_tweets.insert(1));
+    assert!(recent_tweets.insert(2));
+    assert!(!recent_tweets.insert(1));
+    assert!(recent_tweets.insert(3));
+    // 1 is forgotten after 3 is inserted.
+    assert!(recent_tweets.insert(1));
+    assert!(!recent_tweets.insert(3));
+  }
+
+  #[test]
+  fn test_send_raid_tweet_once() {
+    let queue = Arc::new(ClientQueue::new(8, OverflowPolicy::DropOldest));
+    let client = FinderClient::new(["Lv200 アーカーシャ"], queue.clone());
+    assert!(client.send_raid_tweet(1, Bytes::from(vec![1])));
+    assert!(!client.clone().send_raid_tweet(1, Bytes::from(vec![1])));
+    assert!(client.send_raid_tweet(2, Bytes::from(vec![2])));
+    assert_eq!(2, queue.len());
+  }
+
+  #[tokio::test]
+  async fn test_replay_before_live_tweets() {
+    let queue = Arc::new(ClientQueue::new(8, OverflowPolicy::DropOldest));
+    let client = FinderClient::new(["Lv200 アーカーシャ"], queue.clone());
+    client.hold_live_tweets();
+    // Live tweets arrive while the replay is being read from redis, 3 is also persisted.
+    assert!(client.send_raid_tweet(3, Bytes::from(vec![3])));
+    assert!(client.send_raid_tweet(4, Bytes::from(vec![4])));
+    assert_eq!(0, queue.len());
+    // The replay is sent ahead of the queue.
+    for tweet_id in 1..=3 {
+      assert!(client.mark_sent(tweet_id));
+    }
+    client.release_live_tweets();
+    assert!(client.send_raid_tweet(5, Bytes::from(vec![5])));
+    for tweet_id in 4..=5u8 {
+      assert_eq!(Some(Bytes::from(vec![tweet_id])), queue.pop().await);
+    }
+    assert_eq!(0, queue.len());
+  }
+
+  #[tokio::test]
+  async fn test_hold_overflow() {
+    let queue = Arc::new(ClientQueue::new(2, OverflowPolicy::DropOldest));
+    let client = FinderClient::new(["Lv200 アーカーシャ"], queue.clone());
+    client.hold_live_tweets();
+    for tweet_id in 1..=3u8 {
+      assert!(client.send_raid_tweet(tweet_id as u64, Bytes::from(vec![tweet_id])));
+    }
+    client.release_live_tweets();
+    assert_eq!(1, queue.dropped());
+    assert_eq!(Some(Bytes::from(vec![2])), queue.pop().await);
+    assert_eq!(Some(Bytes::from(vec![3])), queue.pop().await);
+
+    let queue = Arc::new(ClientQueue::new(2, OverflowPolicy::Disconnect));
+    let client = FinderClient::new(["Lv200 アーカーシャ"], queue.clone());
+    client.hold_live_tweets();
+    assert!(client.send_raid_tweet(1, Bytes::from(vec![1])));
+    assert!(client.send_raid_tweet(2, Bytes::from(vec![2])));
+    assert!(!client.send_raid_tweet(3, Bytes::from(vec![3])));
+    assert!(queue.is_overflowed());
+  }
 }
@@ -0,0 +1,65 @@
+use crate::{config::CorsConfig, resources::cors::MAX_AGE_SECS};
+use warp::{
+  http::{uri::Authority, Uri},
+  Filter, Rejection,
+};
+
+///
+/// Build the CORS policy of the public listeners from `config`.
+///
+/// Requests with an `Origin` header which is not allowed, including websocket upgrades, are rejected with
+/// `warp::cors::CorsForbidden`, so other sites cannot open streams with cookies of the browser.
+/// Requests without `Origin`, ex. from servers or native apps, are not affected.
+///
+pub fn cors(config: &CorsConfig) -> warp::cors::Builder {
+  let builder = warp::cors()
+    .allow_methods(config.allowed_methods.clone())
+    .allow_headers(config.allowed_headers.clone())
+    .max_age(MAX_AGE_SECS);
+
+  if config.allowed_origins.iter().any(|origin| origin == "*") {
+    builder.allow_any_origin()
+  } else {
+    builder.allow_origins(config.allowed_origins.iter().map(String::as_str))
+  }
+}
+
+///
+/// Pass requests whose `Origin` is the host they are sent to, ex. from the built-in web UI, so they are not checked
+/// by the CORS policy. Other requests are rejected as not found, so the CORS policy decides them.
+///
+pub fn same_origin() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+  warp::header::optional::<String>("origin")
+    .and(warp::host::optional())
+    .and_then(|origin: Option<String>, host: Option<Authority>| async move {
+      match (origin, host) {
+        (Some(origin), Some(host)) if is_same_origin(&origin, &host) => Ok(()),
+        _ => Err(warp::reject::not_found()),
+      }
+    })
+    .untuple_one()
+}
+
+/// Whether the authority of `origin` is `host`, the scheme is not compared since TLS could be terminated by a proxy.
+fn is_same_origin(origin: &str, host: &Authority) -> bool {
+  matches!(origin.parse::<Uri>(), Ok(uri) if uri.authority() == Some(host))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_is_same_origin() {
+    let host = Authority::from_static("raid.example.com");
+    assert!(is_same_origin("https://raid.example.com", &host));
+    assert!(is_same_origin("http://RAID.example.com", &host));
+    assert!(!is_same_origin("https://raid.example.com:8080", &host));
+    assert!(!is_same_origin("https://evil.example.com", &host));
+    assert!(!is_same_origin("null", &host));
+    assert!(is_same_origin(
+      "http://127.0.0.1:8080",
+      &Authority::from_static("127.0.0.1:8080")
+    ));
+  }
+}
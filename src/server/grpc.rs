@@ -0,0 +1,402 @@
+use crate::{
+  client::redis::Redis,
+  common::{health::Health, shutdown::Shutdown, stats::Stats},
+  config::Config,
+  error::HttpError,
+  proto::{decode_all, raid_boss::RaidBoss, raid_tweet::RaidTweet},
+  resources::auth::API_KEY_HEADER,
+  server::{
+    api::{get_bosses::find_bosses, get_persistence_boss::find_persistence_raid_tweets, stream_bosses::remove_client},
+    auth::{max_boss_names, Auth, ConnectionPermit, Principal},
+    client::FinderClient,
+    queue::ClientQueue,
+    replay::{hold_live_tweets, replay_raid_tweets, SinceMarker},
+    state::AppState,
+    throttle::{IpConnectionGuard, Throttle},
+  },
+  FinderClients,
+};
+use log::{error, info};
+use prost::Message;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{transport::Server, Code, Request, Response, Status, Streaming};
+
+use crate::proto::services::{
+  raid_finder_server::{RaidFinder, RaidFinderServer},
+  GetBossesRequest, GetBossesResponse, GetPersistenceBossRequest, GetPersistenceBossResponse, PersistenceRaidTweets,
+  StreamBossesRequest,
+};
+
+/// Raid tweets buffered between the client queue and tonic, the queue itself is the real buffer.
+const STREAM_BUFFER: usize = 16;
+
+///
+/// Create a gRPC server listening on `config.grpc_addr`
+///
+/// # Arguments
+/// * `config` - Granblue fantasy finder rs configuration
+/// * `redis` - Granblue fantasy finder rs backend database client
+/// * `finder_clients` - a map of clients, shared with the websocket server.
+/// * `stats` - counters of the tweet pipeline.
+/// * `health` - state of the tweet pipeline for probes.
+/// * `shutdown` - the server stops accepting calls once it is triggered.
+/// * `auth` - API keys of the calls, shared with the http server.
+/// * `throttle` - limits of each client IP, shared with the http server.
+///
+#[allow(clippy::too_many_arguments)]
+pub fn create_grpc_server(
+  config: Arc<Config>,
+  redis: Arc<Redis>,
+  finder_clients: FinderClients,
+  stats: Arc<Stats>,
+  health: Arc<Health>,
+  shutdown: Arc<Shutdown>,
+  auth: Arc<Auth>,
+  throttle: Arc<Throttle>,
+) {
+  let triggered = shutdown.triggered();
+  let addr = config.grpc_addr;
+  let service = RaidFinderService {
+    app_state: AppState::new(config, redis, finder_clients, stats, health, shutdown.clone(), None),
+    auth,
+    throttle,
+  };
+
+  info!("gRPC server listening on {}...", addr);
+
+  shutdown.spawn(async move {
+    if let Err(e) = Server::builder()
+      .add_service(RaidFinderServer::new(service))
+      .serve_with_shutdown(addr, triggered)
+      .await
+    {
+      error!("gRPC server stopped, error: {:?}", e);
+    }
+  });
+}
+
+pub struct RaidFinderService {
+  app_state: AppState,
+  auth: Arc<Auth>,
+  throttle: Arc<Throttle>,
+}
+
+/// The `x-api-key` metadata of a call.
+fn api_key<T>(request: &Request<T>) -> Option<String> {
+  let key = request.metadata().get(API_KEY_HEADER)?.to_str().ok()?;
+
+  Some(key.to_owned()).filter(|key| !key.is_empty())
+}
+
+/// The `x-forwarded-for` metadata of a call, it is only trusted from `Config.trusted_proxies`.
+fn forwarded_for<T>(request: &Request<T>) -> Option<&str> {
+  request.metadata().get("x-forwarded-for")?.to_str().ok()
+}
+
+impl RaidFinderService {
+  ///
+  /// Take a token of the client IP, then check the API key of a call and take a token of its request rate.
+  /// The client IP is throttled first, so calls without an API key cannot bypass it.
+  /// Return `None` if authentication is disabled.
+  ///
+  async fn authorize<T>(&self, request: &Request<T>) -> Result<Option<Principal>, Status> {
+    self
+      .throttle
+      .limit_request(request.remote_addr(), forwarded_for(request))
+      .map_err(status)?;
+
+    self.auth.authorize(api_key(request)).await.map_err(status)
+  }
+
+  ///
+  /// Authorize a stream like `authorize`, and count it in the concurrent streams of its client IP and API key.
+  ///
+  async fn authorize_stream<T>(
+    &self,
+    request: &Request<T>,
+  ) -> Result<(Option<IpConnectionGuard>, Option<ConnectionPermit>), Status> {
+    let ip_guard = self
+      .throttle
+      .limit_stream(request.remote_addr(), forwarded_for(request))
+      .map_err(status)?;
+    let permit = match self.auth.authorize(api_key(request)).await.map_err(status)? {
+      Some(principal) => Some(self.auth.acquire(principal).map_err(status)?),
+      None => None,
+    };
+
+    Ok((ip_guard, permit))
+  }
+}
+
+fn status(error: HttpError) -> Status {
+  let code = match error {
+    HttpError::EmptyBossNamesError
+    | HttpError::InvalidCursorError
+    | HttpError::BossNamesTooLongError
+    | HttpError::InvalidTranslationError => Code::InvalidArgument,
+    HttpError::MissingApiKeyError | HttpError::InvalidApiKeyError => Code::Unauthenticated,
+    HttpError::AdminDisabledError | HttpError::AdminRequiredError => Code::PermissionDenied,
+    HttpError::BossNotFoundError | HttpError::TranslationNotFoundError | HttpError::ReviewNotFoundError => {
+      Code::NotFound
+    }
+    HttpError::TranslationConflictError => Code::AlreadyExists,
+    HttpError::TranslationLockedError => Code::FailedPrecondition,
+    HttpError::TranslationChangedError => Code::Aborted,
+    HttpError::RateLimitedError | HttpError::TooManyConnectionsError | HttpError::TooManyBossNamesError => {
+      Code::ResourceExhausted
+    }
+    HttpError::CannotGetRedisKeysError
+    | HttpError::CannotMGetRedisError
+    | HttpError::CannotReadStreamError
+    | HttpError::CannotGetApiKeyError
+    | HttpError::CannotWriteRedisError => Code::Unavailable,
+    HttpError::TranslatorUnavailableError => Code::Unimplemented,
+  };
+
+  Status::new(code, error.message())
+}
+
+#[tonic::async_trait]
+impl RaidFinder for RaidFinderService {
+  async fn get_bosses(&self, request: Request<GetBossesRequest>) -> Result<Response<GetBossesResponse>, Status> {
+    self.authorize(&request).await?;
+    let bytes = find_bosses(&self.app_state, request.into_inner().level)
+      .await
+      .map_err(status)?;
+
+    Ok(Response::new(GetBossesResponse {
+      bosses: decode_all::<RaidBoss>(bytes),
+    }))
+  }
+
+  async fn get_persistence_boss(
+    &self,
+    request: Request<GetPersistenceBossRequest>,
+  ) -> Result<Response<GetPersistenceBossResponse>, Status> {
+    self.authorize(&request).await?;
+    let request = request.into_inner();
+    let tweets = find_persistence_raid_tweets(&self.app_state, &request.boss_names, request.limit)
+      .await
+      .map_err(status)?
+      .into_iter()
+      .map(|(boss_name, bytes)| {
+        let tweets = decode_all::<RaidTweet>(bytes);
+        (boss_name, PersistenceRaidTweets { tweets })
+      })
+      .collect();
+
+    Ok(Response::new(GetPersistenceBossResponse { tweets }))
+  }
+
+  type StreamBossesStream = ReceiverStream<Result<RaidTweet, Status>>;
+
+  ///
+  /// Stream raid tweets of the subscribed bosses.
+  /// Every incoming request replaces the subscriptions, the call ends when the client cancels it,
+  /// or with `ResourceExhausted` once a request subscribes more than `ApiKey.max_boss_names`.
+  ///
+  async fn stream_bosses(
+    &self,
+    request: Request<Streaming<StreamBossesRequest>>,
+  ) -> Result<Response<Self::StreamBossesStream>, Status> {
+    let (ip_guard, permit) = self.authorize_stream(&request).await?;
+    let max_boss_names = max_boss_names(&permit);
+    let mut inbound = request.into_inner();
+    let app_state = self.app_state.clone();
+    let client_id = nanoid::nanoid!();
+    let queue = Arc::new(ClientQueue::new(
+      app_state.config.client_queue_capacity,
+      app_state.config.client_queue_policy,
+    ));
+    let client = FinderClient::new(Vec::<String>::new(), queue.clone());
+    app_state.clients.write().await.insert(client_id.clone(), client);
+    match &permit {
+      Some(permit) => info!("gRPC client: {} incoming with API key {}...", client_id, permit.name()),
+      None => info!("gRPC client: {} incoming...", client_id),
+    }
+    let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+
+    // Apply subscription updates
+    tokio::spawn({
+      let app_state = app_state.clone();
+      let client_id = client_id.clone();
+      let tx = tx.clone();
+      async move {
+        while let Ok(Some(request)) = inbound.message().await {
+          let StreamBossesRequest { boss_names, since } = request;
+          if boss_names.len() > max_boss_names {
+            info!("gRPC client: {} subscribes too many boss names, disconnecting...", client_id);
+            let _ = tx.send(Err(status(HttpError::TooManyBossNamesError))).await;
+            remove_client(&app_state, &client_id).await;
+            break;
+          }
+          let since = since
+            .into_iter()
+            .map(|(boss_name, tweet_id)| (boss_name, SinceMarker::TweetId(tweet_id)))
+            .collect();
+          let mut clients = app_state.clients.write().await;
+          let held = hold_live_tweets(&clients, &client_id, &since);
+          let updated = clients.update_boss_names(&client_id, |current| *current = boss_names);
+          drop(clients);
+          if updated.is_none() {
+            break;
+          }
+          let held = match held {
+            Some(held) => held,
+            None => continue,
+          };
+          // Replayed tweets are sent ahead of the queue, so a long replay would not overflow it.
+          let tweets = match replay_raid_tweets(&app_state, &client_id, since).await {
+            Ok(tweets) => tweets,
+            Err(e) => {
+              error!("gRPC client: {}, cannot replay raid tweets, error: {:?}", client_id, e);
+              vec![]
+            }
+          };
+          info!("gRPC client: {}, replay {} raid tweets.", client_id, tweets.len());
+          for bytes in tweets {
+            if let Ok(raid_tweet) = RaidTweet::decode(bytes.as_ref()) {
+              if tx.send(Ok(raid_tweet)).await.is_err() {
+                break;
+              }
+            }
+          }
+          held.release_live_tweets();
+        }
+      }
+    });
+
+    // Forward queued raid tweets until the call is cancelled or the queue is closed, the limits are held until then.
+    let shutdown = app_state.shutdown.clone();
+    shutdown.spawn(async move {
+      let _limits = (ip_guard, permit);
+      loop {
+        let bytes = tokio::select! {
+          bytes = queue.pop() => bytes,
+          _ = tx.closed() => None,
+        };
+        let bytes = match bytes {
+          Some(bytes) => bytes,
+          None => break,
+        };
+        if let Ok(raid_tweet) = RaidTweet::decode(bytes.as_ref()) {
+          if tx.send(Ok(raid_tweet)).await.is_err() {
+            break;
+          }
+        }
+      }
+      if queue.is_overflowed() {
+        let _ = tx.send(Err(Status::resource_exhausted("slow consumer"))).await;
+      } else if app_state.shutdown.is_triggered() {
+        // Clients should reconnect to another replica.
+        let _ = tx.send(Err(Status::unavailable("server is shutting down"))).await;
+      }
+      remove_client(&app_state, &client_id).await;
+    });
+
+    Ok(Response::new(ReceiverStream::new(rx)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::server::registry::ClientRegistry;
+  use std::time::Duration;
+  use tokio::{net::TcpListener, sync::RwLock};
+  use tonic::{client::Grpc, codec::ProstCodec, codegen::http::uri::PathAndQuery, transport::Channel};
+
+  fn service(auth: Auth, config: Config) -> RaidFinderService {
+    let app_state = AppState::new(
+      Arc::new(config),
+      Arc::new(Redis::new("redis://127.0.0.1/").unwrap()),
+      Arc::new(RwLock::new(ClientRegistry::new())),
+      Arc::new(Stats::new()),
+      Arc::new(Health::new()),
+      Arc::new(Shutdown::new(Duration::from_secs(5))),
+      None,
+    );
+
+    let throttle = Arc::new(Throttle::new(&app_state.config));
+
+    RaidFinderService {
+      app_state,
+      auth: Arc::new(auth),
+      throttle,
+    }
+  }
+
+  /// Serve `service` on a random port of localhost.
+  async fn serve(service: RaidFinderService) -> std::net::SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let incoming = futures::stream::unfold(listener, |listener| async move {
+      Some((listener.accept().await.map(|(stream, _)| stream), listener))
+    });
+    let server = Server::builder()
+      .add_service(RaidFinderServer::new(service))
+      .serve_with_incoming(incoming);
+    tokio::spawn(server);
+
+    addr
+  }
+
+  async fn get_bosses(addr: std::net::SocketAddr, api_key: Option<&str>) -> Status {
+    let channel = Channel::from_shared(format!("http://{}", addr)).unwrap().connect().await.unwrap();
+    let mut client = Grpc::new(channel);
+    client.ready().await.unwrap();
+    let mut request = Request::new(GetBossesRequest { level: 0 });
+    if let Some(api_key) = api_key {
+      request.metadata_mut().insert(API_KEY_HEADER, api_key.parse().unwrap());
+    }
+    let path = PathAndQuery::from_static("/raid_finder.services.RaidFinder/GetBosses");
+    let codec = ProstCodec::<GetBossesRequest, GetBossesResponse>::default();
+
+    client.unary(request, path, codec).await.unwrap_err()
+  }
+
+  #[tokio::test]
+  async fn test_unauthenticated_call() {
+    let auth = Auth::with_api_keys(serde_json::json!({"3a0f1c": {}}));
+    let addr = serve(service(auth, Config::default())).await;
+
+    let missing = get_bosses(addr, None).await;
+    assert_eq!(Code::Unauthenticated, missing.code());
+    assert_eq!(HttpError::MissingApiKeyError.message(), missing.message());
+    let invalid = get_bosses(addr, Some("unknown")).await;
+    assert_eq!(Code::Unauthenticated, invalid.code());
+    assert_eq!(HttpError::InvalidApiKeyError.message(), invalid.message());
+  }
+
+  #[tokio::test]
+  async fn test_throttled_call() {
+    let auth = Auth::with_api_keys(serde_json::json!({"3a0f1c": {}}));
+    let config = Config {
+      ip_requests_per_minute: 2,
+      ..Config::default()
+    };
+    let addr = serve(service(auth, config)).await;
+
+    // Rejected calls take tokens of the client IP as well, even without an API key.
+    assert_eq!(Code::Unauthenticated, get_bosses(addr, Some("unknown")).await.code());
+    assert_eq!(Code::Unauthenticated, get_bosses(addr, None).await.code());
+    for api_key in [None, Some("unknown")].iter() {
+      let throttled = get_bosses(addr, *api_key).await;
+      assert_eq!(Code::ResourceExhausted, throttled.code());
+      assert_eq!(HttpError::RateLimitedError.message(), throttled.message());
+    }
+  }
+
+  #[test]
+  fn test_status() {
+    assert_eq!(Code::InvalidArgument, status(HttpError::EmptyBossNamesError).code());
+    assert_eq!(Code::NotFound, status(HttpError::BossNotFoundError).code());
+    assert_eq!(Code::Unauthenticated, status(HttpError::InvalidApiKeyError).code());
+    assert_eq!(Code::ResourceExhausted, status(HttpError::TooManyBossNamesError).code());
+    let unavailable = status(HttpError::CannotGetRedisKeysError);
+    assert_eq!(Code::Unavailable, unavailable.code());
+    assert_eq!("Cannot get redis keys.", unavailable.message());
+  }
+}
@@ -1,63 +1,602 @@
 use crate::{
   client::redis::Redis,
-  server::{api, body_parser::post_json, state::AppState},
-  FinderClients,
+  common::{health::Health, shutdown::Shutdown, stats::Stats},
+  config::Config,
+  server::{
+    api,
+    api::stream_bosses::StreamQuery,
+    auth::{authorize, authorize_admin, authorize_stream, with_api_key_protocol, Auth, ConnectionPermit},
+    body_parser::post_json,
+    cache::if_none_match,
+    cors::{cors, same_origin},
+    negotiation::accept,
+    recover::handle_rejection,
+    state::AppState,
+    throttle::{throttle, throttle_stream, IpConnectionGuard, Throttle},
+  },
+  error, FinderClients, Result, TranslatorMap,
 };
+#[cfg(feature = "tls")]
+use crate::server::tls;
 use log::info;
-use std::{net::SocketAddr, sync::Arc};
-use warp::Filter;
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use warp::{Filter, Reply};
 
 ///
-/// Create an http server listening on port 50051
-/// 
+/// Create http listeners of `config.http_addrs` which serve all routes, and `config.admin_addr` which only serves
+/// probes and stats.
+/// Listeners terminate TLS with `config.tls` if it is set, the admin listener is always plaintext.
+/// Public listeners require API keys if `config.api_key_store` is set, and limit each client IP.
+/// Browsers could call them from `config.cors` origins if it is set.
+///
 /// # Arguments
+/// * `config` - Granblue fantasy finder rs configuration
 /// * `redis` - Granblue fantasy finder rs backend database client
 /// * `finder_clients` - a map of clients.
-/// 
-pub fn create_http_server(redis: Arc<Redis>, finder_clients: FinderClients) {
-  let app_state = AppState::new(redis, finder_clients);
+/// * `stats` - counters of the tweet pipeline.
+/// * `health` - state of the tweet pipeline for probes.
+/// * `shutdown` - listeners stop accepting connections once it is triggered.
+/// * `translator_map` - the translator map of the tweet actor, admin APIs update it along with redis.
+/// * `auth` - API keys of the public listeners, shared with the gRPC server.
+/// * `throttle` - limits of each client IP, shared with the gRPC server.
+///
+#[allow(clippy::too_many_arguments)]
+pub fn create_http_server(
+  config: Arc<Config>,
+  redis: Arc<Redis>,
+  finder_clients: FinderClients,
+  stats: Arc<Stats>,
+  health: Arc<Health>,
+  shutdown: Arc<Shutdown>,
+  translator_map: Option<TranslatorMap>,
+  auth: Arc<Auth>,
+  throttle: Arc<Throttle>,
+) -> Result<()> {
+  let app_state = AppState::new(
+    config.clone(),
+    redis,
+    finder_clients,
+    stats,
+    health,
+    shutdown.clone(),
+    translator_map,
+  );
+
+  #[cfg(feature = "tls")]
+  let certificates = match &config.tls {
+    Some(tls) => {
+      let certificates = Arc::new(tls::Certificates::new(tls.clone())?);
+      tls::reload_on_hangup(certificates.clone());
+      Some(certificates)
+    }
+    None => None,
+  };
+  #[cfg(not(feature = "tls"))]
+  if config.tls.is_some() {
+    return Err(error::Error::TlsConfig {
+      message: "raid-finder is built without the `tls` feature".to_owned(),
+    });
+  }
+
+  for addr in config.http_addrs.iter() {
+    #[cfg(feature = "tls")]
+    if let Some(certificates) = &certificates {
+      let addr = tls::serve(
+        routes(app_state.clone(), auth.clone(), throttle.clone()),
+        *addr,
+        certificates.clone(),
+        shutdown.clone(),
+      )?;
+      info!("HTTPS server listening on {}...", addr);
+      continue;
+    }
+    let addr = serve(
+      routes(app_state.clone(), auth.clone(), throttle.clone()),
+      *addr,
+      &shutdown,
+    )?;
+    info!("HTTP server listening on {}...", addr);
+  }
+
+  if let Some(addr) = config.admin_addr {
+    let addr = serve(admin_routes(app_state), addr, &shutdown)?;
+    info!("Admin HTTP server listening on {}...", addr);
+  }
+
+  Ok(())
+}
+
+///
+/// Bind a plaintext listener and serve `filter` on it in the background until `shutdown` is triggered.
+/// In-flight requests are finished before the server stops, `shutdown.drain()` waits for it.
+/// Return the bound address, so port `0` could be used to pick a free port.
+///
+pub fn serve<F>(filter: F, addr: SocketAddr, shutdown: &Shutdown) -> Result<SocketAddr>
+where
+  F: Filter<Error = Infallible> + Clone + Send + Sync + 'static,
+  F::Extract: Reply,
+{
+  let (addr, server) = warp::serve(filter)
+    .try_bind_with_graceful_shutdown(addr, shutdown.triggered())
+    .map_err(|error| error::Error::HttpBind { addr, error })?;
+  shutdown.spawn(server);
+
+  Ok(addr)
+}
+
+fn with_state(app_state: AppState) -> impl Filter<Extract = (AppState,), Error = Infallible> + Clone {
+  warp::any().map(move || app_state.clone())
+}
+
+///
+/// Probes and stats, served by both the public and the admin listener.
+///
+fn probe_routes(app_state: AppState) -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+  let server = with_state(app_state);
 
-  let server = warp::any().map(move || app_state.clone());
+  let livez_route = warp::get()
+    .and(warp::path("livez"))
+    .and(warp::path::end())
+    .and(server.clone())
+    .map(api::health::livez);
 
-  let healthz_route = warp::get()
-    .and(warp::path("healthz"))
+  let readyz_route = warp::get()
+    .and(warp::path("readyz"))
     .and(warp::path::end())
     .and(server.clone())
-    .map(api::healthz::healthz);
+    .and_then(api::health::readyz);
+
+  let stats_route = warp::get()
+    .and(warp::path("stats"))
+    .and(warp::path::end())
+    .and(server)
+    .map(api::stats::stats);
+
+  livez_route.or(readyz_route).or(stats_route)
+}
+
+///
+/// Routes of the admin listener, it should not be exposed to the public.
+///
+pub fn admin_routes(app_state: AppState) -> impl Filter<Extract = (impl Reply,), Error = Infallible> + Clone {
+  probe_routes(app_state).recover(handle_rejection)
+}
+
+///
+/// All routes of the public listeners, every route except probes and the built-in web UI is limited by client IP and
+/// requires an API key if `auth` is enabled.
+/// Limits are checked after paths are matched, so a rejection of one route does not shadow others.
+/// With `config.cors`, requests from other origins are rejected before any route, error responses carry CORS
+/// headers as well. Requests from the origin of the listener itself, ex. the built-in web UI, are always allowed.
+/// Without it, websocket upgrades are not checked to keep existing clients working.
+///
+pub fn routes(
+  app_state: AppState,
+  auth: Arc<Auth>,
+  throttle: Arc<Throttle>,
+) -> impl Filter<Extract = (impl Reply,), Error = Infallible> + Clone {
+  let cors_config = app_state.config.cors.clone();
+  let server = with_state(app_state.clone());
+  let guard = self::throttle(throttle.clone()).and(authorize(auth.clone()));
+  let admin_guard = self::throttle(throttle.clone()).and(authorize_admin(auth.clone()));
+  let stream_guard = throttle_stream(throttle).and(authorize_stream(auth));
 
   let get_bosses_route = warp::post()
     .and(warp::path("get_bosses"))
     .and(warp::path::end())
+    .and(guard.clone())
     .and(post_json())
+    .and(accept())
     .and(server.clone())
     .and_then(api::get_bosses::get_bosses);
 
   let get_persistence_boss = warp::post()
     .and(warp::path("get_persistence_boss"))
     .and(warp::path::end())
+    .and(guard.clone())
     .and(post_json())
+    .and(accept())
     .and(server.clone())
     .and_then(api::get_persistence_boss::get_persistence_boss);
 
+  let get_raid_tweets_route = warp::get()
+    .and(warp::path("raid_tweets"))
+    .and(warp::path::end())
+    .and(guard.clone())
+    .and(warp::query())
+    .and(server.clone())
+    .and_then(api::get_raid_tweets::get_raid_tweets);
+
+  let stream_bosses_sse_route = warp::get()
+    .and(warp::path!("stream_bosses" / "sse"))
+    .and(warp::query())
+    .and(warp::header::optional::<String>("last-event-id"))
+    .and(stream_guard.clone())
+    .and(server.clone())
+    .and_then(api::stream_bosses_sse::stream_bosses_sse);
+
   let stream_bosses_route = warp::path("stream_bosses")
     // The `ws()` filter will prepare the Websocket handshake.
     .and(warp::ws())
+    .and(stream_guard)
+    .and(server.clone())
+    // `warp::query` rejects requests without query string, fallback to protocol 1.
+    .and(
+      warp::query::<StreamQuery>()
+        .or(warp::any().map(StreamQuery::default))
+        .unify(),
+    )
+    .and(warp::header::optional::<String>("sec-websocket-protocol"))
+    .map(
+      |ws: warp::ws::Ws,
+       ip_guard: Option<IpConnectionGuard>,
+       permit: Option<ConnectionPermit>,
+       state: AppState,
+       query: StreamQuery,
+       protocols: Option<String>| {
+        // And then our closure will be called when it completes...
+        let reply =
+          ws.on_upgrade(move |websocket| api::stream_bosses::stream_bosses(websocket, state, query, permit, ip_guard));
+        with_api_key_protocol(reply, protocols)
+      },
+    );
+
+  let v1_list_bosses_route = warp::path!("bosses")
+    .and(warp::query())
+    .and(accept())
+    .and(if_none_match())
+    .and(server.clone())
+    .and_then(api::v1::bosses::list_bosses);
+
+  let v1_get_boss_route = warp::path!("bosses" / String)
+    .and(accept())
+    .and(if_none_match())
+    .and(server.clone())
+    .and_then(api::v1::bosses::get_boss);
+
+  let v1_list_raid_tweets_route = warp::path!("bosses" / String / "raids")
+    .and(warp::query())
+    .and(accept())
+    .and(if_none_match())
+    .and(server.clone())
+    .and_then(api::v1::bosses::list_raid_tweets);
+
+  let list_translations_route = warp::get()
+    .and(warp::path::end())
+    .and(server.clone())
+    .and_then(api::admin::list_translations);
+
+  let create_translation_route = warp::post()
+    .and(warp::path::end())
+    .and(post_json())
+    .and(server.clone())
+    .and_then(api::admin::create_translation);
+
+  let override_translation_route = warp::put()
+    .and(warp::path::end())
+    .and(post_json())
+    .and(server.clone())
+    .and_then(api::admin::override_translation);
+
+  let lock_translation_route = warp::post()
+    .and(warp::path!("lock"))
+    .and(post_json())
+    .and(server.clone())
+    .and_then(api::admin::lock_translation);
+
+  let delete_translation_route = warp::delete()
+    .and(warp::path::end())
+    .and(warp::query())
+    .and(server.clone())
+    .and_then(api::admin::delete_translation);
+
+  let list_reviews_route = warp::get()
+    .and(warp::path!("reviews"))
+    .and(server.clone())
+    .and_then(api::admin::list_reviews);
+
+  let accept_review_route = warp::post()
+    .and(warp::path!("reviews" / "accept"))
+    .and(post_json())
+    .and(server.clone())
+    .and_then(api::admin::accept_review);
+
+  let reject_review_route = warp::post()
+    .and(warp::path!("reviews" / "reject"))
+    .and(post_json())
     .and(server)
-    .map(|ws: warp::ws::Ws, state: AppState| {
-      // And then our closure will be called when it completes...
-      ws.on_upgrade(move |websocket| api::stream_bosses::stream_bosses(websocket, state))
-    });
+    .and_then(api::admin::reject_review);
+
+  // The built-in web UI, it only serves embedded files so it is not limited.
+  let web_index_route = warp::get()
+    .and(warp::path::end())
+    .and(if_none_match())
+    .map(api::web::index);
+
+  let web_asset_route = warp::get()
+    .and(warp::path!("web" / String))
+    .and(if_none_match())
+    .and_then(api::web::asset);
 
-  let routes = healthz_route
+  // Versioned RESTful routes, reads are plain `GET`s so they could be cached.
+  let v1_routes = warp::get().and(warp::path("v1")).and(guard).and(
+    v1_list_bosses_route
+      .or(v1_get_boss_route)
+      .or(v1_list_raid_tweets_route),
+  );
+
+  // Admin APIs require an admin API key, so they are disabled without authentication.
+  let admin_translator_routes = warp::path!("admin" / "translator" / ..).and(admin_guard).and(
+    list_translations_route
+      .or(create_translation_route)
+      .or(override_translation_route)
+      .or(lock_translation_route)
+      .or(delete_translation_route)
+      .or(list_reviews_route)
+      .or(accept_review_route)
+      .or(reject_review_route),
+  );
+
+  let routes = probe_routes(app_state)
     .or(get_bosses_route)
     .or(get_persistence_boss)
-    .or(stream_bosses_route);
+    .or(get_raid_tweets_route)
+    .or(stream_bosses_sse_route)
+    .or(stream_bosses_route)
+    .or(v1_routes)
+    .or(admin_translator_routes)
+    .or(web_index_route)
+    .or(web_asset_route)
+    .recover(handle_rejection);
+
+  let routes = match cors_config {
+    // `routes` never rejects, so a same origin request is not routed twice.
+    Some(cors_config) => same_origin()
+      .and(routes.clone())
+      .map(Reply::into_response)
+      .or(routes.with(cors(&cors_config)).map(Reply::into_response))
+      .unify()
+      .boxed(),
+    None => routes.map(Reply::into_response).boxed(),
+  };
+
+  routes.recover(handle_rejection)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::server::registry::ClientRegistry;
+  use std::time::Duration;
+  use tokio::sync::RwLock;
+  use warp::http::StatusCode;
+
+  fn app_state() -> AppState {
+    AppState::new(
+      Arc::new(Config::default()),
+      Arc::new(Redis::new("redis://127.0.0.1/").unwrap()),
+      Arc::new(RwLock::new(ClientRegistry::new())),
+      Arc::new(Stats::new()),
+      Arc::new(Health::new()),
+      Arc::new(Shutdown::new(Duration::from_secs(5))),
+      None,
+    )
+  }
+
+  async fn get(addr: SocketAddr, path: &str) -> StatusCode {
+    let uri = format!("http://{}{}", addr, path).parse().unwrap();
+
+    hyper::Client::new().get(uri).await.unwrap().status()
+  }
+
+  #[tokio::test]
+  async fn test_admin_listener() {
+    let app_state = app_state();
+    let _guard = app_state.health.actor_guard();
+    let shutdown = app_state.shutdown.clone();
+    let admin_addr = serve(admin_routes(app_state.clone()), "127.0.0.1:0".parse().unwrap(), &shutdown).unwrap();
+    let auth = Arc::new(Auth::new(&app_state.config, app_state.redis.clone()).unwrap());
+    let throttle = Arc::new(Throttle::new(&app_state.config));
+    let routes = routes(app_state, auth, throttle);
+    let addr = serve(routes, "127.0.0.1:0".parse().unwrap(), &shutdown).unwrap();
+    assert_ne!(admin_addr, addr);
+
+    assert_eq!(StatusCode::OK, get(admin_addr, "/livez").await);
+    assert_eq!(StatusCode::OK, get(admin_addr, "/stats").await);
+    assert_eq!(StatusCode::NOT_FOUND, get(admin_addr, "/v1/bosses").await);
+    assert_eq!(StatusCode::OK, get(addr, "/livez").await);
+    assert_eq!(StatusCode::NOT_FOUND, get(admin_addr, "/").await);
+    assert_eq!(StatusCode::OK, get(addr, "/").await);
+    assert_eq!(StatusCode::OK, get(addr, "/web/app.css").await);
+    // Cursors are validated before redis is read.
+    assert_eq!(StatusCode::BAD_REQUEST, get(addr, "/raid_tweets?since=latest").await);
+  }
+
+  #[tokio::test]
+  async fn test_api_key_routes() {
+    let app_state = app_state();
+    let api_keys = serde_json::json!({"3a0f1c": {"max_boss_names": 1}});
+    let auth = Arc::new(Auth::with_api_keys(api_keys));
+    let throttle = Arc::new(Throttle::new(&app_state.config));
+    let shutdown = app_state.shutdown.clone();
+    let routes = routes(app_state, auth, throttle);
+    let addr = serve(routes, "127.0.0.1:0".parse().unwrap(), &shutdown).unwrap();
+
+    // Probes stay open for kubelet, the web UI asks users for their API key.
+    assert_eq!(StatusCode::OK, get(addr, "/stats").await);
+    assert_eq!(StatusCode::OK, get(addr, "/").await);
+    assert_eq!(StatusCode::UNAUTHORIZED, get(addr, "/v1/bosses").await);
+    assert_eq!(StatusCode::UNAUTHORIZED, get(addr, "/raid_tweets?boss_name=a").await);
+    assert_eq!(
+      StatusCode::UNAUTHORIZED,
+      get(addr, "/stream_bosses/sse?boss_names=a").await
+    );
+    assert_eq!(
+      StatusCode::TOO_MANY_REQUESTS,
+      get(addr, "/stream_bosses/sse?boss_names=a,b&api_key=3a0f1c").await
+    );
+  }
+
+  #[tokio::test]
+  async fn test_admin_routes() {
+    let app_state = app_state();
+    let throttle = Arc::new(Throttle::new(&app_state.config));
+    let open_routes = routes(
+      app_state.clone(),
+      Arc::new(Auth::new(&app_state.config, app_state.redis.clone()).unwrap()),
+      throttle.clone(),
+    );
+    let request = |method: &str, key: &str| {
+      warp::test::request()
+        .method(method)
+        .path("/admin/translator")
+        .header("x-api-key", key)
+        .json(&serde_json::json!({"jp_name": "Lv200 アーカーシャ", "en_name": "Lvl 200 Akasha"}))
+    };
+    // Admin APIs are disabled without authentication.
+    let response = request("POST", "").reply(&open_routes).await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    let api_keys = serde_json::json!({"3a0f1c": {}, "9e4b27": {"admin": true}});
+    let routes = routes(app_state, Arc::new(Auth::with_api_keys(api_keys)), throttle);
+    assert_eq!(StatusCode::UNAUTHORIZED, request("PUT", "").reply(&routes).await.status());
+    assert_eq!(StatusCode::FORBIDDEN, request("PUT", "3a0f1c").reply(&routes).await.status());
+    // This process does not ingest tweets, so it has no translator map to update.
+    assert_eq!(
+      StatusCode::SERVICE_UNAVAILABLE,
+      request("PUT", "9e4b27").reply(&routes).await.status()
+    );
+    let response = warp::test::request()
+      .method("DELETE")
+      .path("/admin/translator?name=Lvl%20200%20Akasha")
+      .header("x-api-key", "9e4b27")
+      .reply(&routes)
+      .await;
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, response.status());
+  }
+
+  #[tokio::test]
+  async fn test_ip_routes() {
+    let app_state = app_state();
+    let auth = Arc::new(Auth::new(&app_state.config, app_state.redis.clone()).unwrap());
+    let config = Config {
+      ip_requests_per_minute: 3,
+      ..Config::default()
+    };
+    let throttle = Arc::new(Throttle::new(&config));
+    let shutdown = app_state.shutdown.clone();
+    let routes = routes(app_state, auth, throttle);
+    let addr = serve(routes, "127.0.0.1:0".parse().unwrap(), &shutdown).unwrap();
+
+    // Rejected requests take tokens as well.
+    for _ in 0..3 {
+      assert_eq!(
+        StatusCode::BAD_REQUEST,
+        get(addr, "/stream_bosses/sse?boss_names=").await
+      );
+    }
+    assert_eq!(
+      StatusCode::TOO_MANY_REQUESTS,
+      get(addr, "/stream_bosses/sse?boss_names=").await
+    );
+    assert_eq!(StatusCode::OK, get(addr, "/stats").await);
+  }
+
+  fn ws_upgrade(origin: &str) -> warp::test::RequestBuilder {
+    warp::test::request()
+      .path("/stream_bosses")
+      .header("origin", origin)
+      .header("connection", "upgrade")
+      .header("upgrade", "websocket")
+      .header("sec-websocket-version", "13")
+      .header("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==")
+  }
+
+  #[tokio::test]
+  async fn test_cors_routes() {
+    let app_state = app_state();
+    let auth = Arc::new(Auth::new(&app_state.config, app_state.redis.clone()).unwrap());
+    let throttle = Arc::new(Throttle::new(&app_state.config));
+    let open_routes = routes(app_state.clone(), auth.clone(), throttle.clone());
+    // Websocket upgrades are not checked without CORS configuration.
+    let response = ws_upgrade("https://evil.example.com").reply(&open_routes).await;
+    assert_eq!(StatusCode::SWITCHING_PROTOCOLS, response.status());
+
+    let mut app_state = app_state;
+    app_state.config = Arc::new(Config {
+      cors: Some(crate::config::CorsConfig {
+        allowed_origins: vec!["https://raid.example.com".to_owned()],
+        allowed_methods: vec![warp::http::Method::GET],
+        allowed_headers: vec![warp::http::header::HeaderName::from_static("x-api-key")],
+      }),
+      ..Config::default()
+    });
+    let routes = routes(app_state, auth, throttle);
+
+    let response = ws_upgrade("https://evil.example.com").reply(&routes).await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    let response = ws_upgrade("https://raid.example.com").reply(&routes).await;
+    assert_eq!(StatusCode::SWITCHING_PROTOCOLS, response.status());
+    // The built-in web UI is served from an origin which is not configured.
+    let response = ws_upgrade("https://finder.example.com")
+      .header("host", "finder.example.com")
+      .reply(&routes)
+      .await;
+    assert_eq!(StatusCode::SWITCHING_PROTOCOLS, response.status());
+    let response = ws_upgrade("https://evil.example.com")
+      .header("host", "finder.example.com")
+      .reply(&routes)
+      .await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    let request = |path: &str, origin: &str| warp::test::request().path(path).header("origin", origin);
+    assert_eq!(
+      StatusCode::FORBIDDEN,
+      request("/stats", "https://evil.example.com").reply(&routes).await.status()
+    );
+    // Error responses carry CORS headers, so browsers could read them.
+    for (path, status) in &[("/stats", StatusCode::OK), ("/stream_bosses/sse?boss_names=", StatusCode::BAD_REQUEST)] {
+      let response = request(path, "https://raid.example.com").reply(&routes).await;
+      assert_eq!(*status, response.status());
+      assert_eq!("https://raid.example.com", response.headers()["access-control-allow-origin"]);
+    }
+    // Requests without `Origin` are not from browsers.
+    assert_eq!(
+      StatusCode::OK,
+      warp::test::request().path("/stats").reply(&routes).await.status()
+    );
+
+    let preflight = |method: &str| {
+      request("/v1/bosses", "https://raid.example.com")
+        .method("OPTIONS")
+        .header("access-control-request-method", method)
+        .header("access-control-request-headers", "x-api-key")
+    };
+    let response = preflight("GET").reply(&routes).await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!("600", response.headers()["access-control-max-age"]);
+    assert_eq!(StatusCode::FORBIDDEN, preflight("DELETE").reply(&routes).await.status());
+  }
 
-  let addr = "0.0.0.0:50051".parse::<SocketAddr>().unwrap();
+  #[tokio::test]
+  async fn test_bind_error() {
+    let app_state = app_state();
+    let addr = serve(admin_routes(app_state.clone()), "127.0.0.1:0".parse().unwrap(), &app_state.shutdown).unwrap();
+    assert!(matches!(
+      serve(admin_routes(app_state.clone()), addr, &app_state.shutdown),
+      Err(error::Error::HttpBind { .. })
+    ));
+  }
 
-  info!("gRPC server listening on {}...", addr);
+  #[tokio::test]
+  async fn test_graceful_shutdown() {
+    let app_state = app_state();
+    let shutdown = app_state.shutdown.clone();
+    let addr = serve(admin_routes(app_state), "127.0.0.1:0".parse().unwrap(), &shutdown).unwrap();
+    assert_eq!(StatusCode::OK, get(addr, "/stats").await);
 
-  tokio::spawn(async move {
-    warp::serve(routes).run(addr).await;
-  });
+    shutdown.trigger();
+    assert!(shutdown.drain().await);
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+  }
 }
@@ -1,5 +1,19 @@
+pub mod auth;
 pub mod http;
+pub mod cache;
 pub mod client;
+pub mod cors;
+pub mod grpc;
+pub mod negotiation;
+pub mod protocol;
+pub mod queue;
+pub mod recover;
+pub mod registry;
+pub mod replay;
+pub mod throttle;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod translator;
 mod body_parser;
 mod state;
 mod api;
\ No newline at end of file
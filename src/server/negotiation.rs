@@ -0,0 +1,195 @@
+use warp::{
+  http::header::{HeaderValue, CONTENT_TYPE, VARY},
+  hyper::Body,
+  reply::Response,
+  Filter, Reply,
+};
+
+pub const APPLICATION_JSON: &str = "application/json";
+pub const APPLICATION_PROTOBUF: &str = "application/x-protobuf";
+
+///
+/// Response format of the http APIs, negotiated by the `Accept` header.
+///
+/// * `Legacy` - encoded protobuf messages as JSON arrays of numbers, used if a client does not ask for a format.
+/// * `Json` - decoded messages as JSON objects.
+/// * `Protobuf` - a length-delimited protobuf response message.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResponseFormat {
+  Legacy,
+  Json,
+  Protobuf,
+}
+
+impl ResponseFormat {
+  ///
+  /// The supported media type with the highest quality value in the `Accept` header wins, the first one on ties.
+  /// Only a missing `Accept` header or wildcards keep the legacy format, which existing clients rely on.
+  /// Media ranges with `q=0` or an invalid quality value are not acceptable.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// ResponseFormat::from_accept(Some("application/x-protobuf, application/json;q=0.9")); // => Protobuf
+  /// ResponseFormat::from_accept(Some("application/json, text/plain, */*")); // => Json
+  /// ResponseFormat::from_accept(Some("*/*")); // => Legacy
+  /// ```
+  pub fn from_accept(accept: Option<&str>) -> Self {
+    let mut best: Option<(f32, ResponseFormat)> = None;
+    for media_range in accept.into_iter().flat_map(|accept| accept.split(',')) {
+      let mut params = media_range.split(';').map(str::trim);
+      let media_type = params.next().unwrap_or_default().to_ascii_lowercase();
+      let quality = params
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+        .map(|(_, value)| value.trim().trim_matches('"').parse::<f32>().unwrap_or(0.0))
+        .unwrap_or(1.0);
+      let format = match media_type.as_str() {
+        APPLICATION_JSON => ResponseFormat::Json,
+        APPLICATION_PROTOBUF => ResponseFormat::Protobuf,
+        "application/*" | "*/*" => ResponseFormat::Legacy,
+        _ => continue,
+      };
+      if quality > 0.0 && !matches!(best, Some((best_quality, _)) if best_quality >= quality) {
+        best = Some((quality, format));
+      }
+    }
+
+    best.map(|(_, format)| format).unwrap_or(ResponseFormat::Legacy)
+  }
+}
+
+pub fn accept() -> impl Filter<Extract = (ResponseFormat,), Error = warp::Rejection> + Clone {
+  warp::header::optional::<String>("accept")
+    .map(|accept: Option<String>| ResponseFormat::from_accept(accept.as_deref()))
+}
+
+/// Reply with a length-delimited protobuf message.
+pub fn protobuf<M: prost::Message>(message: &M) -> Response {
+  Encoded::protobuf(message).into_response()
+}
+
+///
+/// Encoded response body with its content type, the body is kept for computing `ETag`s.
+///
+pub struct Encoded {
+  pub content_type: &'static str,
+  pub body: Vec<u8>,
+}
+
+impl Encoded {
+  pub fn json<T: serde::Serialize>(value: &T) -> Self {
+    Self {
+      content_type: APPLICATION_JSON,
+      // Serializing response messages never fails, they only contain strings, numbers and maps with string keys.
+      body: serde_json::to_vec(value).unwrap_or_default(),
+    }
+  }
+
+  pub fn protobuf<M: prost::Message>(message: &M) -> Self {
+    Self {
+      content_type: APPLICATION_PROTOBUF,
+      body: message.encode_length_delimited_to_vec(),
+    }
+  }
+}
+
+impl Reply for Encoded {
+  fn into_response(self) -> Response {
+    let mut response = Response::new(Body::from(self.body));
+    response
+      .headers_mut()
+      .insert(CONTENT_TYPE, HeaderValue::from_static(self.content_type));
+
+    response
+  }
+}
+
+/// Responses of the same url differ by `Accept`, caches should not mix them up.
+pub fn vary_accept<R: Reply>(reply: R) -> Response {
+  let mut response = reply.into_response();
+  response.headers_mut().insert(VARY, HeaderValue::from_static("accept"));
+
+  response
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::proto::raid_boss::RaidBoss;
+  use prost::Message;
+
+  #[test]
+  fn test_from_accept() {
+    assert_eq!(ResponseFormat::Legacy, ResponseFormat::from_accept(None));
+    assert_eq!(ResponseFormat::Legacy, ResponseFormat::from_accept(Some("*/*")));
+    assert_eq!(ResponseFormat::Legacy, ResponseFormat::from_accept(Some("text/html")));
+    assert_eq!(
+      ResponseFormat::Json,
+      ResponseFormat::from_accept(Some("Application/JSON"))
+    );
+    assert_eq!(
+      ResponseFormat::Json,
+      ResponseFormat::from_accept(Some("application/json, text/plain, */*"))
+    );
+    assert_eq!(
+      ResponseFormat::Protobuf,
+      ResponseFormat::from_accept(Some("text/html, application/x-protobuf;q=0.9, */*;q=0.8"))
+    );
+  }
+
+  #[test]
+  fn test_from_accept_defaults_to_legacy() {
+    // Defaults of curl, fetch and browsers.
+    for accept in &[
+      "*/*",
+      "application/*",
+      "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+    ] {
+      assert_eq!(ResponseFormat::Legacy, ResponseFormat::from_accept(Some(accept)), "{}", accept);
+    }
+  }
+
+  #[test]
+  fn test_from_accept_quality() {
+    assert_eq!(
+      ResponseFormat::Json,
+      ResponseFormat::from_accept(Some("application/x-protobuf;q=0, application/json"))
+    );
+    assert_eq!(
+      ResponseFormat::Json,
+      ResponseFormat::from_accept(Some("application/x-protobuf;q=0.5, application/json;q=0.9"))
+    );
+    assert_eq!(
+      ResponseFormat::Protobuf,
+      ResponseFormat::from_accept(Some("application/json;q=0.5, application/x-protobuf;q=0.9"))
+    );
+    assert_eq!(
+      ResponseFormat::Json,
+      ResponseFormat::from_accept(Some("application/json, application/x-protobuf"))
+    );
+    assert_eq!(
+      ResponseFormat::Legacy,
+      ResponseFormat::from_accept(Some("application/json;q=0.5, */*"))
+    );
+    assert_eq!(
+      ResponseFormat::Legacy,
+      ResponseFormat::from_accept(Some("application/x-protobuf;q=high"))
+    );
+  }
+
+  #[tokio::test]
+  async fn test_protobuf() {
+    let raid_boss = RaidBoss {
+      en_name: "Lvl 200 Akasha".into(),
+      level: 200,
+      ..Default::default()
+    };
+    let response = vary_accept(protobuf(&raid_boss));
+    assert_eq!(APPLICATION_PROTOBUF, response.headers()[CONTENT_TYPE]);
+    assert_eq!("accept", response.headers()[VARY]);
+    let body = warp::hyper::body::to_bytes(response.into_body()).await.unwrap();
+    assert_eq!(raid_boss, RaidBoss::decode_length_delimited(body).unwrap());
+  }
+}
@@ -0,0 +1,326 @@
+use crate::server::replay::SinceMarker;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Websocket control protocol version, negotiated by `/stream_bosses?protocol=2`.
+/// Protocol 1 is the legacy `{"boss_names": [...]}` replacement.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+///
+/// Control request from websocket client
+///
+/// Every request may carry an `id`, the ack/error frame of the request will echo the same `id`.
+/// `subscribe` and `set` may carry `since` markers to replay raid tweets missed while reconnecting.
+///
+/// # Example
+///
+/// ```
+/// {"op": "subscribe", "id": "1", "boss_names": ["Lv200 アーカーシャ"]}
+/// {"op": "unsubscribe", "id": "2", "boss_names": ["Lv200 アーカーシャ"]}
+/// {"op": "set", "id": "3", "boss_names": ["Lv150 プロトバハムート"], "since": {"Lv150 プロトバハムート": 1620698515453}}
+/// {"op": "list_subscriptions", "id": "4"}
+/// {"op": "ping", "id": "5"}
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ClientRequest {
+  Subscribe {
+    id: Option<String>,
+    boss_names: Vec<String>,
+    #[serde(default)]
+    since: HashMap<String, SinceMarker>,
+  },
+  Unsubscribe {
+    id: Option<String>,
+    boss_names: Vec<String>,
+  },
+  Set {
+    id: Option<String>,
+    boss_names: Vec<String>,
+    #[serde(default)]
+    since: HashMap<String, SinceMarker>,
+  },
+  ListSubscriptions {
+    id: Option<String>,
+  },
+  Ping {
+    id: Option<String>,
+  },
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+  /// Message is not a valid JSON request.
+  InvalidRequest,
+  /// Only text messages are accepted.
+  UnsupportedMessage,
+  /// Subscriptions would exceed `max_boss_names` of the API key.
+  TooManyBossNames,
+}
+
+///
+/// Text frame sent from server, raid tweets are still sent as binary protobuf frames.
+///
+/// # Example
+///
+/// ```
+/// {"type": "hello", "protocol": 2, "server_version": "1.0.4", "client_id": "V1StGXR8_Z5jdHi6B-myT"}
+/// {"type": "ack", "id": "1", "op": "subscribe", "boss_names": ["Lv200 アーカーシャ"]}
+/// {"type": "error", "id": "6", "code": "invalid_request", "message": "unknown variant `foo`"}
+/// {"type": "pong", "id": "5"}
+/// ```
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerFrame {
+  Hello {
+    protocol: u32,
+    server_version: String,
+    client_id: String,
+  },
+  Ack {
+    id: Option<String>,
+    op: String,
+    /// Subscriptions after the request is applied.
+    boss_names: Vec<String>,
+  },
+  Error {
+    id: Option<String>,
+    code: ErrorCode,
+    message: String,
+  },
+  Pong {
+    id: Option<String>,
+  },
+}
+
+impl ServerFrame {
+  pub fn hello<S: Into<String>>(client_id: S) -> Self {
+    ServerFrame::Hello {
+      protocol: PROTOCOL_VERSION,
+      server_version: env!("CARGO_PKG_VERSION").to_owned(),
+      client_id: client_id.into(),
+    }
+  }
+
+  pub fn error<S: Into<String>>(code: ErrorCode, message: S) -> Self {
+    ServerFrame::Error {
+      id: None,
+      code,
+      message: message.into(),
+    }
+  }
+
+  pub fn to_message(&self) -> warp::ws::Message {
+    // Serializing these frames never fails, they only contain strings and numbers.
+    warp::ws::Message::text(serde_json::to_string(self).unwrap_or_default())
+  }
+}
+
+impl ClientRequest {
+  ///
+  /// Parse a request, the error frame echoes the `id` of the message if the message is still a JSON object.
+  ///
+  pub fn parse(message: &str) -> Result<Self, ServerFrame> {
+    serde_json::from_str::<ClientRequest>(message).map_err(|error| ServerFrame::Error {
+      id: request_id(message),
+      code: ErrorCode::InvalidRequest,
+      message: error.to_string(),
+    })
+  }
+
+  /// Take the `since` markers out of the request.
+  pub fn take_since(&mut self) -> HashMap<String, SinceMarker> {
+    match self {
+      ClientRequest::Subscribe { since, .. } | ClientRequest::Set { since, .. } => std::mem::take(since),
+      _ => HashMap::new(),
+    }
+  }
+
+  ///
+  /// Apply the request to the subscriptions of a client, return the frame which should be sent back.
+  /// Subscriptions are left unchanged if they would exceed `max_boss_names`.
+  ///
+  /// # Arguments
+  /// * `boss_names` - current subscriptions of the client.
+  /// * `max_boss_names` - max subscriptions of the client, `usize::MAX` if unlimited.
+  ///
+  pub fn apply(self, boss_names: &mut Vec<String>, max_boss_names: usize) -> ServerFrame {
+    let previous = boss_names.clone();
+    let (id, op) = match self {
+      ClientRequest::Subscribe {
+        id, boss_names: names, ..
+      } => {
+        for name in names {
+          if !boss_names.contains(&name) {
+            boss_names.push(name);
+          }
+        }
+        (id, "subscribe")
+      }
+      ClientRequest::Unsubscribe { id, boss_names: names } => {
+        boss_names.retain(|name| !names.contains(name));
+        (id, "unsubscribe")
+      }
+      ClientRequest::Set {
+        id, boss_names: names, ..
+      } => {
+        boss_names.clear();
+        for name in names {
+          if !boss_names.contains(&name) {
+            boss_names.push(name);
+          }
+        }
+        (id, "set")
+      }
+      ClientRequest::ListSubscriptions { id } => (id, "list_subscriptions"),
+      ClientRequest::Ping { id } => return ServerFrame::Pong { id },
+    };
+    // Legacy clients are created with an empty boss name placeholder.
+    boss_names.retain(|name| !name.is_empty());
+    if boss_names.len() > max_boss_names {
+      *boss_names = previous;
+      return ServerFrame::Error {
+        id,
+        code: ErrorCode::TooManyBossNames,
+        message: format!("boss_names should not be more than {}", max_boss_names),
+      };
+    }
+
+    ServerFrame::Ack {
+      id,
+      op: op.to_owned(),
+      boss_names: boss_names.clone(),
+    }
+  }
+}
+
+/// `id` of an invalid request, numeric ids are echoed as strings.
+fn request_id(message: &str) -> Option<String> {
+  match serde_json::from_str::<serde_json::Value>(message).ok()?.get("id")? {
+    serde_json::Value::String(id) => Some(id.clone()),
+    serde_json::Value::Number(id) => Some(id.to_string()),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ack(id: &str, op: &str, boss_names: &[&str]) -> ServerFrame {
+    ServerFrame::Ack {
+      id: Some(id.to_owned()),
+      op: op.to_owned(),
+      boss_names: boss_names.iter().map(|name| name.to_string()).collect(),
+    }
+  }
+
+  #[test]
+  fn test_parse_request() {
+    let request = ClientRequest::parse(r#"{"op": "subscribe", "id": "1", "boss_names": ["Lv200 アーカーシャ"]}"#);
+    assert_eq!(
+      Ok(ClientRequest::Subscribe {
+        id: Some("1".into()),
+        boss_names: vec!["Lv200 アーカーシャ".into()],
+        since: HashMap::new(),
+      }),
+      request
+    );
+    let mut request =
+      ClientRequest::parse(r#"{"op": "set", "boss_names": ["a"], "since": {"a": "1420698515453000000"}}"#).unwrap();
+    let since = request.take_since();
+    assert_eq!(
+      Some(&SinceMarker::TweetId("1420698515453000000".into())),
+      since.get("a")
+    );
+    assert!(request.take_since().is_empty());
+    let request = ClientRequest::parse(r#"{"op": "ping"}"#);
+    assert_eq!(Ok(ClientRequest::Ping { id: None }), request);
+  }
+
+  #[test]
+  fn test_parse_invalid_request() {
+    for message in &[r#"{"boss_names": []}"#, r#"{"op": "foo"}"#, "__PONG__"] {
+      match ClientRequest::parse(message) {
+        Err(ServerFrame::Error { code, .. }) => assert_eq!(ErrorCode::InvalidRequest, code),
+        other => panic!("{} should be invalid, got {:?}", message, other),
+      }
+    }
+  }
+
+  #[test]
+  fn test_parse_invalid_request_id() {
+    let id = |message| match ClientRequest::parse(message) {
+      Err(ServerFrame::Error { id, .. }) => id,
+      other => panic!("{} should be invalid, got {:?}", message, other),
+    };
+    assert_eq!(Some("1".to_owned()), id(r#"{"op": "foo", "id": "1"}"#));
+    assert_eq!(Some("2".to_owned()), id(r#"{"op": "subscribe", "id": 2, "boss_names": "a"}"#));
+    assert_eq!(None, id(r#"{"op": "foo", "id": ["1"]}"#));
+    assert_eq!(None, id(r#"{"op": "foo", "id": "1""#));
+  }
+
+  #[test]
+  fn test_apply_requests() {
+    let mut boss_names = vec!["".to_owned()];
+    let request = ClientRequest::parse(r#"{"op": "subscribe", "id": "1", "boss_names": ["a", "b", "a"]}"#).unwrap();
+    assert_eq!(
+      ack("1", "subscribe", &["a", "b"]),
+      request.apply(&mut boss_names, usize::MAX)
+    );
+    let request = ClientRequest::parse(r#"{"op": "unsubscribe", "id": "2", "boss_names": ["a", "c"]}"#).unwrap();
+    assert_eq!(
+      ack("2", "unsubscribe", &["b"]),
+      request.apply(&mut boss_names, usize::MAX)
+    );
+    let request = ClientRequest::parse(r#"{"op": "set", "id": "3", "boss_names": ["c", "d"]}"#).unwrap();
+    assert_eq!(ack("3", "set", &["c", "d"]), request.apply(&mut boss_names, usize::MAX));
+    let request = ClientRequest::parse(r#"{"op": "list_subscriptions", "id": "4"}"#).unwrap();
+    assert_eq!(
+      ack("4", "list_subscriptions", &["c", "d"]),
+      request.apply(&mut boss_names, usize::MAX)
+    );
+    let request = ClientRequest::parse(r#"{"op": "ping", "id": "5"}"#).unwrap();
+    assert_eq!(
+      ServerFrame::Pong { id: Some("5".into()) },
+      request.apply(&mut boss_names, usize::MAX)
+    );
+    assert_eq!(vec!["c", "d"], boss_names);
+  }
+
+  #[test]
+  fn test_apply_max_boss_names() {
+    let mut boss_names = vec!["a".to_owned()];
+    let request = ClientRequest::parse(r#"{"op": "subscribe", "id": "1", "boss_names": ["a", "b"]}"#).unwrap();
+    assert_eq!(ack("1", "subscribe", &["a", "b"]), request.apply(&mut boss_names, 2));
+    let request = ClientRequest::parse(r#"{"op": "subscribe", "id": "2", "boss_names": ["c"]}"#).unwrap();
+    match request.apply(&mut boss_names, 2) {
+      ServerFrame::Error { id, code, .. } => {
+        assert_eq!(Some("2".to_owned()), id);
+        assert_eq!(ErrorCode::TooManyBossNames, code);
+      }
+      other => panic!("subscribe should be rejected, got {:?}", other),
+    }
+    assert_eq!(vec!["a", "b"], boss_names);
+    let request = ClientRequest::parse(r#"{"op": "set", "id": "3", "boss_names": ["c"]}"#).unwrap();
+    assert_eq!(ack("3", "set", &["c"]), request.apply(&mut boss_names, 2));
+  }
+
+  #[test]
+  fn test_serialize_frames() {
+    let frame = serde_json::to_value(ack("1", "subscribe", &["a"])).unwrap();
+    assert_eq!(
+      serde_json::json!({"type": "ack", "id": "1", "op": "subscribe", "boss_names": ["a"]}),
+      frame
+    );
+    let frame = serde_json::to_value(ServerFrame::error(ErrorCode::UnsupportedMessage, "binary")).unwrap();
+    assert_eq!(
+      serde_json::json!({"type": "error", "id": null, "code": "unsupported_message", "message": "binary"}),
+      frame
+    );
+    let frame = serde_json::to_value(ServerFrame::hello("client")).unwrap();
+    assert_eq!("hello", frame["type"]);
+    assert_eq!(PROTOCOL_VERSION, frame["protocol"]);
+  }
+}
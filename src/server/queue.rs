@@ -0,0 +1,226 @@
+use crate::config::OverflowPolicy;
+use bytes::Bytes;
+use std::{
+  collections::VecDeque,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+  },
+};
+use tokio::sync::Notify;
+
+struct QueueState {
+  messages: VecDeque<Bytes>,
+  closed: bool,
+  overflowed: bool,
+}
+
+impl QueueState {
+  /// Close the queue by `OverflowPolicy::Disconnect`, queued messages are dropped.
+  fn disconnect(&mut self) {
+    self.messages.clear();
+    self.closed = true;
+    self.overflowed = true;
+  }
+}
+
+///
+/// A bounded message queue between the tweet stream and a websocket client
+///
+/// Messages are encoded raid tweets, the same `Bytes` is shared by every queue it is pushed into.
+/// The websocket consumer copies a message into a warp frame, SSE and gRPC consumers decode it instead.
+/// Producers never wait, when the queue is full the `OverflowPolicy` decides which message is dropped.
+/// There should be only one consumer which forwards messages to the websocket.
+///
+pub struct ClientQueue {
+  state: Mutex<QueueState>,
+  notify: Notify,
+  capacity: usize,
+  policy: OverflowPolicy,
+  dropped: AtomicU64,
+}
+
+impl ClientQueue {
+  pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+    ClientQueue {
+      state: Mutex::new(QueueState {
+        messages: VecDeque::with_capacity(capacity),
+        closed: false,
+        overflowed: false,
+      }),
+      notify: Notify::new(),
+      capacity: capacity.max(1),
+      policy,
+      dropped: AtomicU64::new(0),
+    }
+  }
+
+  ///
+  /// Push a message into the queue.
+  /// Return false if the queue is closed, either the client is gone or it is disconnected by `OverflowPolicy::Disconnect`.
+  ///
+  pub fn push(&self, message: Bytes) -> bool {
+    let mut state = self.state.lock().unwrap();
+    if state.closed {
+      return false;
+    }
+    if state.messages.len() >= self.capacity {
+      self.dropped.fetch_add(1, Ordering::Relaxed);
+      match self.policy {
+        OverflowPolicy::DropOldest => {
+          state.messages.pop_front();
+        }
+        OverflowPolicy::DropNewest => return true,
+        OverflowPolicy::Disconnect => {
+          state.disconnect();
+          drop(state);
+          self.notify.notify_one();
+          return false;
+        }
+      }
+    }
+    state.messages.push_back(message);
+    drop(state);
+    self.notify.notify_one();
+
+    true
+  }
+
+  /// Wait for the next message, return None once the queue is closed and drained.
+  pub async fn pop(&self) -> Option<Bytes> {
+    loop {
+      {
+        let mut state = self.state.lock().unwrap();
+        if let Some(message) = state.messages.pop_front() {
+          return Some(message);
+        }
+        if state.closed {
+          return None;
+        }
+      }
+      // `notify_one` stores a permit if nobody is waiting, so a push between the check and here is not lost.
+      self.notify.notified().await;
+    }
+  }
+
+  ///
+  /// Count a message dropped by a buffer in front of the queue, which is bounded by the capacity of the queue.
+  /// The queue is closed if its policy is `OverflowPolicy::Disconnect`, otherwise the buffer drops a message itself.
+  ///
+  pub fn overflow(&self) {
+    self.dropped.fetch_add(1, Ordering::Relaxed);
+    if self.policy == OverflowPolicy::Disconnect {
+      self.state.lock().unwrap().disconnect();
+      self.notify.notify_one();
+    }
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.capacity
+  }
+
+  pub fn policy(&self) -> OverflowPolicy {
+    self.policy
+  }
+
+  /// Close the queue, queued messages can still be popped.
+  pub fn close(&self) {
+    self.state.lock().unwrap().closed = true;
+    self.notify.notify_one();
+  }
+
+  /// Whether the queue is closed by `OverflowPolicy::Disconnect`.
+  pub fn is_overflowed(&self) -> bool {
+    self.state.lock().unwrap().overflowed
+  }
+
+  /// How many messages are dropped because the queue is full.
+  pub fn dropped(&self) -> u64 {
+    self.dropped.load(Ordering::Relaxed)
+  }
+
+  #[cfg(test)]
+  pub fn len(&self) -> usize {
+    self.state.lock().unwrap().messages.len()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::{sync::Arc, time::Duration};
+
+  fn message(i: u8) -> Bytes {
+    Bytes::from(vec![i])
+  }
+
+  /// A client which never reads, nothing is popped from its queue.
+  fn fill(queue: &ClientQueue, count: u8) -> Vec<bool> {
+    (0..count).map(|i| queue.push(message(i))).collect()
+  }
+
+  #[tokio::test]
+  async fn test_drop_oldest() {
+    let queue = ClientQueue::new(3, OverflowPolicy::DropOldest);
+    assert!(fill(&queue, 5).into_iter().all(|pushed| pushed));
+    assert_eq!(3, queue.len());
+    assert_eq!(2, queue.dropped());
+    assert_eq!(Some(message(2)), queue.pop().await);
+    assert_eq!(Some(message(3)), queue.pop().await);
+    assert_eq!(Some(message(4)), queue.pop().await);
+  }
+
+  #[tokio::test]
+  async fn test_drop_newest() {
+    let queue = ClientQueue::new(3, OverflowPolicy::DropNewest);
+    fill(&queue, 5);
+    assert_eq!(3, queue.len());
+    assert_eq!(2, queue.dropped());
+    assert_eq!(Some(message(0)), queue.pop().await);
+    assert_eq!(Some(message(1)), queue.pop().await);
+    assert_eq!(Some(message(2)), queue.pop().await);
+  }
+
+  #[tokio::test]
+  async fn test_disconnect() {
+    let queue = ClientQueue::new(3, OverflowPolicy::Disconnect);
+    assert_eq!(vec![true, true, true, false, false], fill(&queue, 5));
+    assert!(queue.is_overflowed());
+    assert_eq!(1, queue.dropped());
+    assert_eq!(None, queue.pop().await);
+  }
+
+  #[tokio::test]
+  async fn test_overflow() {
+    let queue = ClientQueue::new(3, OverflowPolicy::DropOldest);
+    fill(&queue, 2);
+    queue.overflow();
+    assert_eq!((1, 2), (queue.dropped(), queue.len()));
+    assert!(!queue.is_overflowed());
+
+    let queue = ClientQueue::new(3, OverflowPolicy::Disconnect);
+    fill(&queue, 2);
+    queue.overflow();
+    assert!(queue.is_overflowed());
+    assert_eq!(None, queue.pop().await);
+  }
+
+  #[tokio::test]
+  async fn test_pop_waits_for_push() {
+    let queue = Arc::new(ClientQueue::new(3, OverflowPolicy::DropOldest));
+    let consumer = tokio::spawn({
+      let queue = queue.clone();
+      async move { (queue.pop().await, queue.pop().await) }
+    });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    queue.push(message(0));
+    queue.close();
+    let result = tokio::time::timeout(Duration::from_secs(1), consumer)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!((Some(message(0)), None), result);
+    assert!(!queue.push(message(1)));
+    assert!(!queue.is_overflowed());
+  }
+}
@@ -0,0 +1,165 @@
+use crate::error::HttpRejection;
+use log::error;
+use serde::Serialize;
+use std::convert::Infallible;
+use warp::{
+  filters::{body::BodyDeserializeError, cors::CorsForbidden},
+  http::StatusCode,
+  reject::{
+    InvalidHeader, InvalidQuery, LengthRequired, MethodNotAllowed, MissingHeader, PayloadTooLarge, UnsupportedMediaType,
+  },
+  Rejection, Reply,
+};
+
+///
+/// JSON error envelope of every failed http request.
+///
+/// # Example
+///
+/// ```
+/// {"error": {"code": "boss_not_found", "message": "Cannot find the boss."}}
+/// ```
+#[derive(Serialize, Debug)]
+struct ErrorEnvelope {
+  error: ErrorBody,
+}
+
+#[derive(Serialize, Debug)]
+struct ErrorBody {
+  code: &'static str,
+  message: String,
+}
+
+fn envelope<S: Into<String>>(status: StatusCode, code: &'static str, message: S) -> warp::reply::Response {
+  let envelope = ErrorEnvelope {
+    error: ErrorBody {
+      code,
+      message: message.into(),
+    },
+  };
+
+  warp::reply::with_status(warp::reply::json(&envelope), status).into_response()
+}
+
+///
+/// Map rejections of http routes to the JSON error envelope with a stable code.
+/// Rejections which are not known are logged and returned as `500 internal_error`.
+///
+pub async fn handle_rejection(rejection: Rejection) -> Result<impl Reply, Infallible> {
+  if rejection.is_not_found() {
+    return Ok(envelope(StatusCode::NOT_FOUND, "not_found", "Not found."));
+  }
+
+  let response = if let Some(error) = rejection.find::<HttpRejection>() {
+    let status = StatusCode::from_u16(error.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    envelope(status, error.error, error.message.as_str())
+  } else if let Some(error) = rejection.find::<BodyDeserializeError>() {
+    envelope(StatusCode::BAD_REQUEST, "invalid_body", error.to_string())
+  } else if let Some(error) = rejection.find::<PayloadTooLarge>() {
+    envelope(StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", error.to_string())
+  } else if let Some(error) = rejection.find::<LengthRequired>() {
+    envelope(StatusCode::LENGTH_REQUIRED, "length_required", error.to_string())
+  } else if let Some(error) = rejection.find::<UnsupportedMediaType>() {
+    envelope(
+      StatusCode::UNSUPPORTED_MEDIA_TYPE,
+      "unsupported_media_type",
+      error.to_string(),
+    )
+  } else if let Some(error) = rejection.find::<InvalidQuery>() {
+    envelope(StatusCode::BAD_REQUEST, "invalid_query", error.to_string())
+  } else if let Some(error) = rejection.find::<MissingHeader>() {
+    envelope(StatusCode::BAD_REQUEST, "missing_header", error.to_string())
+  } else if let Some(error) = rejection.find::<InvalidHeader>() {
+    envelope(StatusCode::BAD_REQUEST, "invalid_header", error.to_string())
+  } else if let Some(error) = rejection.find::<MethodNotAllowed>() {
+    envelope(StatusCode::METHOD_NOT_ALLOWED, "method_not_allowed", error.to_string())
+  } else if let Some(error) = rejection.find::<CorsForbidden>() {
+    envelope(StatusCode::FORBIDDEN, "cors_forbidden", error.to_string())
+  } else {
+    error!("Unhandled http rejection: {:?}", rejection);
+    envelope(
+      StatusCode::INTERNAL_SERVER_ERROR,
+      "internal_error",
+      "Internal server error.",
+    )
+  };
+
+  Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{error::HttpError, server::body_parser::post_json};
+  use std::collections::HashMap;
+  use warp::Filter;
+
+  async fn error_of<F>(filter: F, request: warp::test::RequestBuilder) -> (StatusCode, serde_json::Value)
+  where
+    F: Filter<Error = Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: Reply + Send,
+  {
+    let response = request.reply(&filter.recover(handle_rejection)).await;
+    let body = serde_json::from_slice(response.body()).unwrap();
+
+    (response.status(), body)
+  }
+
+  fn post_route() -> impl Filter<Extract = (String,), Error = Rejection> + Clone {
+    warp::post()
+      .and(warp::path("get_bosses"))
+      .and(post_json::<HashMap<String, u32>>())
+      .map(|_| "ok".to_owned())
+  }
+
+  #[tokio::test]
+  async fn test_http_error() {
+    let filter = warp::path("v1").and_then(|| async { Err::<String, _>(HttpError::BossNotFoundError.reject()) });
+    let (status, body) = error_of(filter, warp::test::request().path("/v1")).await;
+    assert_eq!(StatusCode::NOT_FOUND, status);
+    assert_eq!(
+      serde_json::json!({"error": {"code": "boss_not_found", "message": "Cannot find the boss."}}),
+      body
+    );
+    let filter = warp::path("v1").and_then(|| async { Err::<String, _>(HttpError::CannotMGetRedisError.reject()) });
+    let (status, body) = error_of(filter, warp::test::request().path("/v1")).await;
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, status);
+    assert_eq!("cannot_mget_redis", body["error"]["code"]);
+  }
+
+  #[tokio::test]
+  async fn test_body_errors() {
+    let request = warp::test::request().method("POST").path("/get_bosses").body("{");
+    let (status, body) = error_of(post_route(), request).await;
+    assert_eq!(StatusCode::BAD_REQUEST, status);
+    assert_eq!("invalid_body", body["error"]["code"]);
+
+    let request = warp::test::request()
+      .method("POST")
+      .path("/get_bosses")
+      .body(vec![b' '; 1024 * 17]);
+    let (status, body) = error_of(post_route(), request).await;
+    assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, status);
+    assert_eq!("payload_too_large", body["error"]["code"]);
+  }
+
+  #[tokio::test]
+  async fn test_routing_errors() {
+    let (status, body) = error_of(post_route(), warp::test::request().method("POST").path("/unknown")).await;
+    assert_eq!(StatusCode::NOT_FOUND, status);
+    assert_eq!("not_found", body["error"]["code"]);
+
+    let (status, body) = error_of(post_route(), warp::test::request().method("GET").path("/get_bosses")).await;
+    assert_eq!(StatusCode::METHOD_NOT_ALLOWED, status);
+    assert_eq!("method_not_allowed", body["error"]["code"]);
+  }
+
+  #[tokio::test]
+  async fn test_cors_forbidden() {
+    let filter = warp::path("v1").map(warp::reply).with(warp::cors().allow_origin("https://raid.example.com"));
+    let request = warp::test::request().path("/v1").header("origin", "https://evil.example.com");
+    let (status, body) = error_of(filter, request).await;
+    assert_eq!(StatusCode::FORBIDDEN, status);
+    assert_eq!("cors_forbidden", body["error"]["code"]);
+  }
+}
@@ -0,0 +1,286 @@
+use crate::server::client::FinderClient;
+use std::collections::{HashMap, HashSet};
+
+///
+/// Websocket clients of this process with an index from boss name to its subscribers
+///
+/// Subscriptions should only be changed through the registry, so the index is always in sync with
+/// `FinderClient.boss_names`.
+///
+#[derive(Default)]
+pub struct ClientRegistry {
+  clients: HashMap<String, FinderClient>,
+  subscribers: HashMap<String, HashSet<String>>,
+}
+
+impl ClientRegistry {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn insert<S: Into<String>>(&mut self, client_id: S, client: FinderClient) {
+    let client_id = client_id.into();
+    self.remove(&client_id);
+    self.index(&client_id, &client.boss_names);
+    self.clients.insert(client_id, client);
+  }
+
+  pub fn remove(&mut self, client_id: &str) -> Option<FinderClient> {
+    let client = self.clients.remove(client_id)?;
+    self.unindex(client_id, &client.boss_names);
+
+    Some(client)
+  }
+
+  pub fn get(&self, client_id: &str) -> Option<&FinderClient> {
+    self.clients.get(client_id)
+  }
+
+  ///
+  /// Change the subscriptions of a client and keep the index in sync.
+  /// Return None if the client is not found.
+  ///
+  /// # Arguments
+  /// * `client_id`: the client to update.
+  /// * `update`: a function which changes the boss names of the client.
+  ///
+  pub fn update_boss_names<F, R>(&mut self, client_id: &str, update: F) -> Option<R>
+  where
+    F: FnOnce(&mut Vec<String>) -> R,
+  {
+    let mut boss_names = self.clients.get(client_id)?.boss_names.clone();
+    let result = update(&mut boss_names);
+    let client = self.clients.get_mut(client_id)?;
+    let previous = std::mem::replace(&mut client.boss_names, boss_names.clone());
+    self.unindex(client_id, &previous);
+    self.index(client_id, &boss_names);
+
+    Some(result)
+  }
+
+  /// Clients which subscribe the boss.
+  pub fn subscribers<'a>(&'a self, boss_name: &str) -> impl Iterator<Item = &'a FinderClient> + 'a {
+    self.subscribers.get(boss_name).into_iter().flat_map(move |client_ids| {
+      client_ids
+        .iter()
+        .filter_map(move |client_id| self.clients.get(client_id))
+    })
+  }
+
+  pub fn len(&self) -> usize {
+    self.clients.len()
+  }
+
+  ///
+  /// Close the queue of every client when the server is shutting down.
+  /// Consumers deliver what is already queued and then disconnect their clients.
+  ///
+  pub fn close_all(&self) {
+    for client in self.clients.values() {
+      client.queue.close();
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.clients.is_empty()
+  }
+
+  fn index(&mut self, client_id: &str, boss_names: &[String]) {
+    // Legacy clients are created with an empty boss name placeholder.
+    for boss_name in boss_names.iter().filter(|name| !name.is_empty()) {
+      self
+        .subscribers
+        .entry(boss_name.clone())
+        .or_default()
+        .insert(client_id.to_owned());
+    }
+  }
+
+  fn unindex(&mut self, client_id: &str, boss_names: &[String]) {
+    for boss_name in boss_names {
+      if let Some(client_ids) = self.subscribers.get_mut(boss_name) {
+        client_ids.remove(client_id);
+        if client_ids.is_empty() {
+          self.subscribers.remove(boss_name);
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{config::OverflowPolicy, proto::{raid_tweet::RaidTweet, MessageExt}, server::queue::ClientQueue};
+  use bytes::Bytes;
+  use std::{sync::Arc, time::Instant};
+
+  fn client(boss_names: &[&str]) -> FinderClient {
+    FinderClient::new(
+      boss_names.iter().copied(),
+      Arc::new(ClientQueue::new(16, OverflowPolicy::DropOldest)),
+    )
+  }
+
+  fn subscriber_count(registry: &ClientRegistry, boss_name: &str) -> usize {
+    registry.subscribers(boss_name).count()
+  }
+
+  #[test]
+  fn test_insert_and_remove() {
+    let mut registry = ClientRegistry::new();
+    registry.insert("1", client(&["a", "b"]));
+    registry.insert("2", client(&["b"]));
+    registry.insert("3", client(&[""]));
+    assert_eq!(3, registry.len());
+    assert_eq!(1, subscriber_count(&registry, "a"));
+    assert_eq!(2, subscriber_count(&registry, "b"));
+    assert_eq!(0, subscriber_count(&registry, ""));
+
+    // Replacing a client should drop its previous subscriptions.
+    registry.insert("1", client(&["c"]));
+    assert_eq!(0, subscriber_count(&registry, "a"));
+    assert_eq!(1, subscriber_count(&registry, "c"));
+
+    assert!(registry.remove("2").is_some());
+    assert!(registry.remove("2").is_none());
+    assert_eq!(0, subscriber_count(&registry, "b"));
+    assert!(!registry.subscribers.contains_key("b"));
+  }
+
+  #[test]
+  fn test_update_boss_names() {
+    let mut registry = ClientRegistry::new();
+    registry.insert("1", client(&["a", "b"]));
+    let result = registry.update_boss_names("1", |boss_names| {
+      boss_names.retain(|name| name != "a");
+      boss_names.push("c".into());
+      boss_names.len()
+    });
+    assert_eq!(Some(2), result);
+    assert_eq!(vec!["b", "c"], registry.get("1").unwrap().boss_names);
+    assert_eq!(0, subscriber_count(&registry, "a"));
+    assert_eq!(1, subscriber_count(&registry, "b"));
+    assert_eq!(1, subscriber_count(&registry, "c"));
+    assert_eq!(None, registry.update_boss_names("2", |_| ()));
+  }
+
+  #[tokio::test]
+  async fn test_close_all() {
+    let mut registry = ClientRegistry::new();
+    registry.insert("1", client(&["a"]));
+    registry.insert("2", client(&["b"]));
+    let queue = registry.get("1").unwrap().queue.clone();
+    assert!(queue.push(Bytes::from(vec![1])));
+    registry.close_all();
+    // Queued raid tweets are still delivered before the client is disconnected.
+    assert!(!registry.get("2").unwrap().queue.push(Bytes::from(vec![2])));
+    assert_eq!(Some(Bytes::from(vec![1])), queue.pop().await);
+    assert_eq!(None, queue.pop().await);
+  }
+
+  #[tokio::test]
+  async fn test_fan_out() {
+    const CLIENTS: usize = 100;
+    const BOSSES: usize = 20;
+    let boss_names = (0..BOSSES).map(|i| format!("Lv{} boss", i)).collect::<Vec<_>>();
+    let mut registry = ClientRegistry::new();
+    for i in 0..CLIENTS {
+      let subscriptions = [i % BOSSES, (i * 7) % BOSSES, (i * 13) % BOSSES];
+      let client = FinderClient::new(
+        subscriptions.iter().map(|&boss| boss_names[boss].clone()),
+        Arc::new(ClientQueue::new(BOSSES, OverflowPolicy::DropOldest)),
+      );
+      registry.insert(i.to_string(), client);
+    }
+
+    for boss_name in boss_names.iter() {
+      // The index visits exactly the clients which a full scan would find.
+      let scanned = registry
+        .clients
+        .values()
+        .filter(|client| client.boss_names.contains(boss_name))
+        .count();
+      assert_eq!(scanned, subscriber_count(&registry, boss_name));
+
+      let raid_tweet = RaidTweet {
+        boss_name: boss_name.clone(),
+        ..Default::default()
+      };
+      let bytes = Bytes::from(raid_tweet.to_bytes().unwrap());
+      for client in registry.subscribers(boss_name) {
+        client.queue.push(bytes.clone());
+      }
+    }
+
+    // Every queue shares the single encoding of a raid tweet.
+    let first = registry.subscribers(&boss_names[0]).map(|client| client.queue.clone()).collect::<Vec<_>>();
+    let mut encodings = HashSet::new();
+    for queue in first {
+      encodings.insert(queue.pop().await.unwrap().as_ptr());
+    }
+    assert_eq!(1, encodings.len());
+  }
+
+  ///
+  /// Compare the previous full scan with the boss index, run with `cargo test --release -- --ignored bench_fan_out`.
+  ///
+  #[test]
+  #[ignore]
+  fn bench_fan_out() {
+    const CLIENTS: usize = 10_000;
+    const BOSSES: usize = 200;
+    let boss_names = (0..BOSSES).map(|i| format!("Lv{} boss", i)).collect::<Vec<_>>();
+    let mut registry = ClientRegistry::new();
+    for i in 0..CLIENTS {
+      let subscriptions = [i % BOSSES, (i * 7) % BOSSES, (i * 13) % BOSSES];
+      let client = FinderClient::new(
+        subscriptions.iter().map(|&boss| boss_names[boss].clone()),
+        Arc::new(ClientQueue::new(BOSSES, OverflowPolicy::DropOldest)),
+      );
+      registry.insert(i.to_string(), client);
+    }
+    let tweets = boss_names
+      .iter()
+      .enumerate()
+      .map(|(i, boss_name)| RaidTweet {
+        tweet_id: i as u64,
+        boss_name: boss_name.clone(),
+        text: "参加者募集！".into(),
+        ..Default::default()
+      })
+      .collect::<Vec<_>>();
+
+    // Previous fan-out: scan every client and encode once per matching client.
+    let start = Instant::now();
+    let mut scanned = 0;
+    for raid_tweet in tweets.iter() {
+      for client in registry.clients.values() {
+        if client.boss_names.contains(&raid_tweet.boss_name) {
+          client.queue.push(Bytes::from(raid_tweet.to_bytes().unwrap()));
+          scanned += 1;
+        }
+      }
+    }
+    let scan_elapsed = start.elapsed();
+
+    // Indexed fan-out: only visit subscribers and encode once per tweet.
+    let start = Instant::now();
+    let mut indexed = 0;
+    for raid_tweet in tweets.iter() {
+      let bytes = Bytes::from(raid_tweet.to_bytes().unwrap());
+      for client in registry.subscribers(&raid_tweet.boss_name) {
+        client.queue.push(bytes.clone());
+        indexed += 1;
+      }
+    }
+    let index_elapsed = start.elapsed();
+
+    println!(
+      "{} clients, {} bosses, {} deliveries: scan {:?}, index {:?}",
+      CLIENTS, BOSSES, indexed, scan_elapsed, index_elapsed
+    );
+    assert_eq!(scanned, indexed);
+    assert!(index_elapsed < scan_elapsed);
+  }
+}
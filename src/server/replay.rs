@@ -0,0 +1,204 @@
+use crate::{
+  common::redis::{escape_key_pattern, gbf_persistence_raid_tweet_key_parts_of, gbf_persistence_raid_tweets_keys},
+  resources::ws::MAX_REPLAY_TWEETS,
+  server::{client::FinderClient, registry::ClientRegistry, state::AppState},
+  Result,
+};
+use bytes::Bytes;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+///
+/// The last raid tweet a reconnecting client has seen of a boss
+///
+/// A JSON string is a tweet id, since tweet ids are beyond the safe integer range of javascript.
+/// A JSON number is the created timestamp(ms) of the tweet.
+///
+/// # Example
+///
+/// ```
+/// {"Lv200 アーカーシャ": "1420698515453000000", "Lv150 プロトバハムート": 1620698515453}
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum SinceMarker {
+  TweetId(String),
+  Created(u64),
+}
+
+impl SinceMarker {
+  /// Whether the tweet is posted after this marker, an unparsable tweet id matches nothing.
+  pub fn is_before(&self, tweet_id: u64, created: u64) -> bool {
+    match self {
+      SinceMarker::TweetId(id) => id.parse::<u64>().map(|id| tweet_id > id).unwrap_or(false),
+      SinceMarker::Created(timestamp) => created > *timestamp,
+    }
+  }
+}
+
+///
+/// Pick persistence keys of the boss which are newer than the marker, oldest first.
+/// Only the newest `limit` keys are kept, keys of other bosses matched by the pattern are skipped.
+///
+pub fn keys_since(
+  keys: Vec<String>,
+  prefix: &str,
+  boss_name: &str,
+  since: &SinceMarker,
+  limit: usize,
+) -> Vec<(u64, String)> {
+  let mut keys = keys
+    .into_iter()
+    .filter_map(|key| {
+      gbf_persistence_raid_tweet_key_parts_of(prefix, boss_name, &key)
+        .filter(|(tweet_id, created)| since.is_before(*tweet_id, *created))
+        .map(|(tweet_id, created)| (created, tweet_id, key))
+    })
+    .collect::<Vec<_>>();
+  keys.sort();
+  let skip = keys.len().saturating_sub(limit);
+
+  keys
+    .into_iter()
+    .skip(skip)
+    .map(|(_, tweet_id, key)| (tweet_id, key))
+    .collect()
+}
+
+///
+/// Hold live raid tweets of a client which is going to replay, it should be called before its subscriptions change.
+/// Return the client, whose live tweets should be released after the replay.
+///
+pub fn hold_live_tweets(
+  clients: &ClientRegistry,
+  client_id: &str,
+  since: &HashMap<String, SinceMarker>,
+) -> Option<FinderClient> {
+  if since.is_empty() {
+    return None;
+  }
+  let client = clients.get(client_id)?.clone();
+  client.hold_live_tweets();
+
+  Some(client)
+}
+
+///
+/// Find persisted raid tweets which a client missed before it is switched to live delivery.
+///
+/// The replay could be far larger than the queue of the client, so the caller sends it ahead of the queue.
+/// The client should already subscribe these bosses with live tweets held by `FinderClient::hold_live_tweets`,
+/// the caller releases them after the replay is sent, so they are sent after the replayed ones.
+/// Both paths share the recently sent tweet ids of the client, a tweet at the seam is only sent once.
+///
+/// # Arguments
+/// * `app_state`: server state.
+/// * `client_id`: the client which is reconnecting.
+/// * `since`: the marker of each boss, bosses which the client does not subscribe are ignored.
+///
+/// Return encoded raid tweets to replay, they are already marked as sent.
+///
+pub async fn replay_raid_tweets(
+  app_state: &AppState,
+  client_id: &str,
+  since: HashMap<String, SinceMarker>,
+) -> Result<Vec<Bytes>> {
+  let client = match app_state.clients.read().await.get(client_id) {
+    Some(client) => client.clone(),
+    None => return Ok(vec![]),
+  };
+  let tweets = find_missed_raid_tweets(app_state, &client.boss_names, &since).await?;
+
+  Ok(
+    tweets
+      .into_iter()
+      .filter(|(tweet_id, _)| client.mark_sent(*tweet_id))
+      .map(|(_, bytes)| bytes)
+      .collect(),
+  )
+}
+
+///
+/// Find persisted raid tweets posted after the markers, tweets of each boss are sorted from the oldest.
+///
+/// # Arguments
+/// * `app_state`: server state.
+/// * `boss_names`: bosses which the client subscribes, markers of other bosses are ignored.
+/// * `since`: the marker of each boss.
+///
+/// Return tweet ids and encoded raid tweets.
+///
+pub async fn find_missed_raid_tweets(
+  app_state: &AppState,
+  boss_names: &[String],
+  since: &HashMap<String, SinceMarker>,
+) -> Result<Vec<(u64, Bytes)>> {
+  let mut tweets = vec![];
+
+  for (boss_name, marker) in since.iter() {
+    if !boss_names.contains(boss_name) {
+      continue;
+    }
+    let prefix = &app_state.config.redis_prefix;
+    let keys = app_state
+      .redis
+      .keys(gbf_persistence_raid_tweets_keys(prefix, escape_key_pattern(boss_name)))
+      .await?;
+    let (tweet_ids, keys): (Vec<u64>, Vec<String>) = keys_since(keys, prefix, boss_name, marker, MAX_REPLAY_TWEETS)
+      .into_iter()
+      .unzip();
+    let tweets_bytes = app_state.redis.mget_protobuf_raw(keys).await?;
+    for (tweet_id, bytes) in tweet_ids.into_iter().zip(tweets_bytes) {
+      // Persistence key might expire between KEYS and MGET.
+      if bytes.is_empty() {
+        continue;
+      }
+      tweets.push((tweet_id, Bytes::from(bytes)));
+    }
+  }
+
+  Ok(tweets)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{common::redis::gbf_persistence_raid_tweet_key, resources::redis::DEFAULT_PREFIX};
+
+  #[test]
+  fn test_deserialize_since_marker() {
+    let since: HashMap<String, SinceMarker> =
+      serde_json::from_str(r#"{"a": "1420698515453000000", "b": 1620698515453}"#).unwrap();
+    assert_eq!(
+      Some(&SinceMarker::TweetId("1420698515453000000".into())),
+      since.get("a")
+    );
+    assert_eq!(Some(&SinceMarker::Created(1620698515453)), since.get("b"));
+  }
+
+  #[test]
+  fn test_keys_since() {
+    const BOSS_NAME: &str = "Lv120 Mt. Qilin";
+    let key = |tweet_id, created| gbf_persistence_raid_tweet_key(DEFAULT_PREFIX, BOSS_NAME, tweet_id, created);
+    let keys = vec![key(3, 300), key(1, 100), key(4, 400), key(2, 200)];
+    let since = |keys, marker: SinceMarker, limit| keys_since(keys, DEFAULT_PREFIX, BOSS_NAME, &marker, limit);
+
+    let result = since(keys.clone(), SinceMarker::TweetId("1".into()), 10);
+    assert_eq!(vec![(2, key(2, 200)), (3, key(3, 300)), (4, key(4, 400))], result);
+
+    let result = since(keys.clone(), SinceMarker::Created(200), 10);
+    assert_eq!(vec![(3, key(3, 300)), (4, key(4, 400))], result);
+
+    // Keep the newest tweets when there are too many.
+    let result = since(keys.clone(), SinceMarker::Created(0), 2);
+    assert_eq!(vec![(3, key(3, 300)), (4, key(4, 400))], result);
+
+    // The pattern of the boss matches other bosses as well.
+    let other = gbf_persistence_raid_tweet_key(DEFAULT_PREFIX, "Lv120 Mt. Qilin.Impossible", 5, 500);
+    let result = since(vec![key(1, 100), other], SinceMarker::Created(0), 10);
+    assert_eq!(vec![(1, key(1, 100))], result);
+
+    let result = since(keys, SinceMarker::TweetId("not a tweet id".into()), 10);
+    assert!(result.is_empty());
+  }
+}
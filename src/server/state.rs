@@ -1,20 +1,41 @@
-use crate::{client::redis::Redis, common::chrono::current_timestamp_u64, FinderClients};
+use crate::{
+  client::redis::Redis,
+  common::{health::Health, shutdown::Shutdown, stats::Stats},
+  config::Config,
+  FinderClients, TranslatorMap,
+};
 use std::sync::Arc;
-use std::sync::atomic::AtomicU64;
 
 #[derive(Clone)]
 pub struct AppState {
+  pub config: Arc<Config>,
   pub redis: Arc<Redis>,
   pub clients: FinderClients,
-  pub health_check: Arc<AtomicU64>,
+  pub stats: Arc<Stats>,
+  pub health: Arc<Health>,
+  pub shutdown: Arc<Shutdown>,
+  /// The translator map of the tweet actor, `None` if this process does not ingest tweets.
+  pub translator_map: Option<TranslatorMap>,
 }
 
 impl AppState {
-  pub fn new(redis: Arc<Redis>, clients: FinderClients) -> Self {
+  pub fn new(
+    config: Arc<Config>,
+    redis: Arc<Redis>,
+    clients: FinderClients,
+    stats: Arc<Stats>,
+    health: Arc<Health>,
+    shutdown: Arc<Shutdown>,
+    translator_map: Option<TranslatorMap>,
+  ) -> Self {
     AppState {
+      config,
       redis,
       clients,
-      health_check: Arc::new(AtomicU64::new(current_timestamp_u64())),
+      stats,
+      health,
+      shutdown,
+      translator_map,
     }
   }
 }
@@ -0,0 +1,269 @@
+use crate::{
+  common::rate_limit::{ConnectionGuard, ConnectionLimiter, RateLimiter},
+  config::{Config, IpNetwork},
+  error::HttpError,
+};
+use std::{
+  net::{IpAddr, SocketAddr},
+  sync::Arc,
+  time::Duration,
+};
+use warp::{Filter, Rejection};
+
+///
+/// Address of the peer which is connected to the listener.
+/// `warp::addr::remote` only works with `warp::serve`, the https listeners attach it to each request instead.
+///
+#[derive(Clone, Copy, Debug)]
+pub struct RemoteAddr(pub SocketAddr);
+
+/// A stream counted in `Config.ip_max_connections`, it is released when dropped.
+pub type IpConnectionGuard = ConnectionGuard<IpAddr>;
+
+///
+/// Per client IP limits of the public http listeners and the gRPC server
+///
+/// Requests from `trusted_proxies` are counted to the client in `X-Forwarded-For`,
+/// requests without a known peer address, ex. in unit tests, are not limited.
+///
+/// # Arguments
+/// * `requests`: token buckets of `Config.ip_requests_per_minute`.
+/// * `connections`: open streams of each client IP.
+///
+pub struct Throttle {
+  trusted_proxies: Vec<IpNetwork>,
+  requests_per_minute: u32,
+  max_connections: u32,
+  requests: RateLimiter<IpAddr>,
+  connections: Arc<ConnectionLimiter<IpAddr>>,
+}
+
+impl Throttle {
+  pub fn new(config: &Config) -> Self {
+    Throttle {
+      trusted_proxies: config.trusted_proxies.clone(),
+      requests_per_minute: config.ip_requests_per_minute,
+      max_connections: config.ip_max_connections,
+      requests: RateLimiter::new(),
+      connections: Arc::new(ConnectionLimiter::new()),
+    }
+  }
+
+  fn is_trusted(&self, ip: IpAddr) -> bool {
+    self.trusted_proxies.iter().any(|network| network.contains(ip))
+  }
+
+  ///
+  /// Find the client IP of a request.
+  /// `X-Forwarded-For` is read from right to left while the hop is a trusted proxy, so clients cannot spoof it.
+  ///
+  /// # Arguments
+  /// * `remote`: the peer which is connected to the listener.
+  /// * `forwarded_for`: `X-Forwarded-For` header, ex. `203.0.113.7, 10.0.0.3`.
+  ///
+  fn client_ip(&self, remote: Option<SocketAddr>, forwarded_for: Option<&str>) -> Option<IpAddr> {
+    let mut ip = remote?.ip();
+    for hop in forwarded_for.unwrap_or_default().rsplit(',') {
+      if !self.is_trusted(ip) {
+        break;
+      }
+      match hop.trim().parse::<IpAddr>() {
+        Ok(hop) => ip = hop,
+        Err(_) => break,
+      }
+    }
+
+    Some(ip)
+  }
+
+  fn check(&self, ip: IpAddr) -> Result<(), HttpError> {
+    let rate = self.requests_per_minute;
+    if rate > 0 && !self.requests.check(&ip, rate, Duration::from_secs(60)) {
+      return Err(HttpError::RateLimitedError);
+    }
+
+    Ok(())
+  }
+
+  fn acquire(&self, ip: IpAddr) -> Result<IpConnectionGuard, HttpError> {
+    self
+      .connections
+      .acquire(ip, self.max_connections)
+      .ok_or(HttpError::TooManyConnectionsError)
+  }
+
+  ///
+  /// Take a token of the client IP of a request.
+  ///
+  /// # Arguments
+  /// * `remote`: the peer which is connected to the listener, requests without it are not limited.
+  /// * `forwarded_for`: `X-Forwarded-For` header or metadata.
+  ///
+  pub fn limit_request(&self, remote: Option<SocketAddr>, forwarded_for: Option<&str>) -> Result<(), HttpError> {
+    match self.client_ip(remote, forwarded_for) {
+      Some(ip) => self.check(ip),
+      None => Ok(()),
+    }
+  }
+
+  /// Take a token of the client IP and count the stream in the concurrent streams of the client IP.
+  pub fn limit_stream(
+    &self,
+    remote: Option<SocketAddr>,
+    forwarded_for: Option<&str>,
+  ) -> Result<Option<IpConnectionGuard>, HttpError> {
+    match self.client_ip(remote, forwarded_for) {
+      Some(ip) => {
+        self.check(ip)?;
+        self.acquire(ip).map(Some)
+      }
+      None => Ok(None),
+    }
+  }
+}
+
+fn remote(
+  throttle: Arc<Throttle>,
+) -> impl Filter<Extract = (Option<SocketAddr>, Option<String>, Arc<Throttle>), Error = Rejection> + Clone {
+  warp::addr::remote()
+    .and(warp::ext::optional::<RemoteAddr>())
+    .and(warp::header::optional::<String>("x-forwarded-for"))
+    .map(
+      move |remote: Option<SocketAddr>, attached: Option<RemoteAddr>, forwarded_for: Option<String>| {
+        let remote = remote.or_else(|| attached.map(|attached| attached.0));
+        (remote, forwarded_for, throttle.clone())
+      },
+    )
+    .untuple_one()
+}
+
+///
+/// Take a token of the client IP.
+///
+pub fn throttle(throttle: Arc<Throttle>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+  remote(throttle)
+    .and_then(
+      |remote: Option<SocketAddr>, forwarded_for: Option<String>, throttle: Arc<Throttle>| async move {
+        throttle
+          .limit_request(remote, forwarded_for.as_deref())
+          .map_err(|e| e.reject())
+      },
+    )
+    .untuple_one()
+}
+
+///
+/// Take a token of the client IP and count the stream in the concurrent streams of the client IP.
+///
+pub fn throttle_stream(
+  throttle: Arc<Throttle>,
+) -> impl Filter<Extract = (Option<IpConnectionGuard>,), Error = Rejection> + Clone {
+  remote(throttle).and_then(
+    |remote: Option<SocketAddr>, forwarded_for: Option<String>, throttle: Arc<Throttle>| async move {
+      throttle
+        .limit_stream(remote, forwarded_for.as_deref())
+        .map_err(|e| e.reject())
+    },
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn throttle_of(trusted_proxies: &[&str], requests_per_minute: u32, max_connections: u32) -> Arc<Throttle> {
+    let config = Config {
+      trusted_proxies: trusted_proxies.iter().map(|network| network.parse().unwrap()).collect(),
+      ip_requests_per_minute: requests_per_minute,
+      ip_max_connections: max_connections,
+      ..Config::default()
+    };
+
+    Arc::new(Throttle::new(&config))
+  }
+
+  fn ip(ip: &str) -> IpAddr {
+    ip.parse().unwrap()
+  }
+
+  #[test]
+  fn test_ip_network() {
+    let network = "10.0.0.0/8".parse::<IpNetwork>().unwrap();
+    assert!(network.contains(ip("10.20.30.40")));
+    assert!(!network.contains(ip("11.0.0.1")));
+    assert!(!network.contains(ip("::1")));
+    let network = "::1".parse::<IpNetwork>().unwrap();
+    assert!(network.contains(ip("::1")));
+    assert!(!network.contains(ip("::2")));
+    assert!("0.0.0.0/0".parse::<IpNetwork>().unwrap().contains(ip("203.0.113.7")));
+    for invalid in &["10.0.0.0/33", "10.0.0.0/", "localhost"] {
+      assert!(invalid.parse::<IpNetwork>().is_err());
+    }
+  }
+
+  #[test]
+  fn test_client_ip() {
+    let throttle = throttle_of(&["10.0.0.0/8"], 0, 0);
+    let proxy = Some("10.0.0.3:443".parse().unwrap());
+    let client = Some("203.0.113.7:50000".parse().unwrap());
+
+    assert_eq!(None, throttle.client_ip(None, Some("203.0.113.7")));
+    assert_eq!(Some(ip("10.0.0.3")), throttle.client_ip(proxy, None));
+    // Only trusted proxies could forward the client IP.
+    assert_eq!(
+      Some(ip("203.0.113.7")),
+      throttle.client_ip(client, Some("198.51.100.1"))
+    );
+    assert_eq!(Some(ip("203.0.113.7")), throttle.client_ip(proxy, Some("203.0.113.7")));
+    // Hops prepended by the client are ignored.
+    assert_eq!(
+      Some(ip("203.0.113.7")),
+      throttle.client_ip(proxy, Some("198.51.100.1, 203.0.113.7, 10.0.0.2"))
+    );
+    assert_eq!(Some(ip("10.0.0.3")), throttle.client_ip(proxy, Some("unknown")));
+  }
+
+  #[tokio::test]
+  async fn test_throttle() {
+    let filter = throttle(throttle_of(&["10.0.0.0/8"], 2, 0))
+      .map(warp::reply)
+      .recover(crate::server::recover::handle_rejection);
+    let request = |forwarded_for: &str| {
+      warp::test::request()
+        .remote_addr("10.0.0.3:443".parse().unwrap())
+        .header("x-forwarded-for", forwarded_for)
+    };
+
+    for _ in 0..2 {
+      assert_eq!(200, request("203.0.113.7").reply(&filter).await.status());
+    }
+    assert_eq!(429, request("203.0.113.7").reply(&filter).await.status());
+    assert_eq!(200, request("203.0.113.8").reply(&filter).await.status());
+    // Requests without a peer address are not limited.
+    assert_eq!(200, warp::test::request().reply(&filter).await.status());
+  }
+
+  #[tokio::test]
+  async fn test_throttle_stream() {
+    let throttle = throttle_of(&[], 0, 1);
+    let filter = throttle_stream(throttle.clone());
+    let request = |remote_addr: &str| warp::test::request().remote_addr(remote_addr.parse().unwrap());
+
+    let guard = request("203.0.113.7:50000").filter(&filter).await.unwrap();
+    assert!(guard.is_some());
+    let rejection = request("203.0.113.7:50001").filter(&filter).await.err().unwrap();
+    let rejection = rejection.find::<crate::error::HttpRejection>().unwrap();
+    assert_eq!(("too_many_connections", 429), (rejection.error, rejection.code));
+    assert!(request("203.0.113.8:50000").filter(&filter).await.is_ok());
+
+    drop(guard);
+    assert!(request("203.0.113.7:50002").filter(&filter).await.unwrap().is_some());
+    // The https listeners attach the peer address.
+    let attached = warp::test::request()
+      .extension(RemoteAddr("203.0.113.9:50000".parse().unwrap()))
+      .filter(&filter)
+      .await;
+    assert!(attached.unwrap().is_some());
+    assert_eq!(0, throttle.connections.count(&ip("203.0.113.9")));
+  }
+}
@@ -0,0 +1,323 @@
+use crate::{common::shutdown::Shutdown, config::TlsConfig, error, server::throttle::RemoteAddr, Result};
+use hyper::{
+  server::conn::Http,
+  service::{service_fn, Service},
+  Body, Request,
+};
+use log::{error as log_error, info};
+use std::{
+  convert::Infallible,
+  fs::File,
+  io::BufReader,
+  net::SocketAddr,
+  sync::{Arc, RwLock},
+  time::Duration,
+};
+use tokio::{
+  net::TcpListener,
+  signal::unix::{signal, SignalKind},
+};
+use tokio_rustls::{
+  rustls::{internal::pemfile, Certificate, NoClientAuth, PrivateKey, ServerConfig},
+  TlsAcceptor,
+};
+use warp::{Filter, Reply};
+
+///
+/// Certificates of the https listeners, which could be reloaded while serving.
+/// New connections take the latest certificates, established connections keep the ones they are accepted with.
+///
+pub struct Certificates {
+  tls: TlsConfig,
+  server_config: RwLock<Arc<ServerConfig>>,
+}
+
+impl Certificates {
+  pub fn new(tls: TlsConfig) -> Result<Self> {
+    let server_config = RwLock::new(load_server_config(&tls)?);
+
+    Ok(Self { tls, server_config })
+  }
+
+  ///
+  /// Read certificate and private key files again, the previous ones are kept if they are invalid.
+  ///
+  pub fn reload(&self) -> Result<()> {
+    let server_config = load_server_config(&self.tls)?;
+    *self
+      .server_config
+      .write()
+      .unwrap_or_else(|poisoned| poisoned.into_inner()) = server_config;
+
+    Ok(())
+  }
+
+  pub fn acceptor(&self) -> TlsAcceptor {
+    let server_config = self
+      .server_config
+      .read()
+      .unwrap_or_else(|poisoned| poisoned.into_inner())
+      .clone();
+
+    TlsAcceptor::from(server_config)
+  }
+}
+
+fn open(path: &str) -> Result<BufReader<File>> {
+  File::open(path)
+    .map(BufReader::new)
+    .map_err(|error| error::Error::TlsReadFile {
+      path: path.to_owned(),
+      error,
+    })
+}
+
+fn load_certificates(path: &str) -> Result<Vec<Certificate>> {
+  let certificates = pemfile::certs(&mut open(path)?).unwrap_or_default();
+  if certificates.is_empty() {
+    return Err(error::Error::TlsCertificateNotFound { path: path.to_owned() });
+  }
+
+  Ok(certificates)
+}
+
+fn load_private_key(path: &str) -> Result<PrivateKey> {
+  let mut keys = pemfile::pkcs8_private_keys(&mut open(path)?).unwrap_or_default();
+  if keys.is_empty() {
+    keys = pemfile::rsa_private_keys(&mut open(path)?).unwrap_or_default();
+  }
+
+  keys
+    .into_iter()
+    .next()
+    .ok_or_else(|| error::Error::TlsPrivateKeyNotFound { path: path.to_owned() })
+}
+
+fn load_server_config(tls: &TlsConfig) -> Result<Arc<ServerConfig>> {
+  let certificates = load_certificates(&tls.cert_path)?;
+  let private_key = load_private_key(&tls.key_path)?;
+  let mut server_config = ServerConfig::new(NoClientAuth::new());
+  server_config
+    .set_single_cert(certificates, private_key)
+    .map_err(|error| error::Error::TlsConfig {
+      message: error.to_string(),
+    })?;
+  server_config.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
+
+  Ok(Arc::new(server_config))
+}
+
+///
+/// Reload `certificates` whenever the process receives SIGHUP, ex. after cert-manager renews them.
+///
+pub fn reload_on_hangup(certificates: Arc<Certificates>) {
+  tokio::spawn(async move {
+    let mut hangup = match signal(SignalKind::hangup()) {
+      Ok(hangup) => hangup,
+      Err(error) => {
+        log_error!(
+          "Cannot listen SIGHUP, TLS certificates will not be reloaded, error: {}",
+          error
+        );
+        return;
+      }
+    };
+    while hangup.recv().await.is_some() {
+      match certificates.reload() {
+        Ok(()) => info!("TLS certificates are reloaded from {}.", certificates.tls.cert_path),
+        Err(error) => log_error!(
+          "Cannot reload TLS certificates, keep the previous ones, error: {}",
+          error
+        ),
+      }
+    }
+  });
+}
+
+///
+/// Bind an https listener and serve `filter` on it in the background until `shutdown` is triggered.
+/// In-flight requests are finished before connections are closed, `shutdown.drain()` waits for them.
+/// Return the bound address, so port `0` could be used to pick a free port.
+///
+pub fn serve<F>(
+  filter: F,
+  addr: SocketAddr,
+  certificates: Arc<Certificates>,
+  shutdown: Arc<Shutdown>,
+) -> Result<SocketAddr>
+where
+  F: Filter<Error = Infallible> + Clone + Send + Sync + 'static,
+  F::Extract: Reply,
+{
+  let listener = std::net::TcpListener::bind(addr)
+    .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+    .and_then(TcpListener::from_std)
+    .map_err(|error| error::Error::TlsBind { addr, error })?;
+  let local_addr = listener
+    .local_addr()
+    .map_err(|error| error::Error::TlsBind { addr, error })?;
+  let service = warp::service(filter);
+
+  let triggered = shutdown.triggered();
+
+  shutdown.clone().spawn(async move {
+    tokio::pin!(triggered);
+    loop {
+      let accepted = tokio::select! {
+        accepted = listener.accept() => accepted,
+        _ = &mut triggered => break,
+      };
+      let (stream, remote_addr) = match accepted {
+        Ok(connection) => connection,
+        Err(error) => {
+          // Ex. too many open files, wait for other connections to be closed like hyper does.
+          log_error!("Cannot accept https connection, error: {}", error);
+          tokio::time::sleep(Duration::from_secs(1)).await;
+          continue;
+        }
+      };
+      let acceptor = certificates.acceptor();
+      let service = service.clone();
+      let triggered = shutdown.triggered();
+      shutdown.spawn(async move {
+        let stream = match acceptor.accept(stream).await {
+          Ok(stream) => stream,
+          Err(error) => {
+            info!("TLS handshake with {} failed, error: {}", remote_addr, error);
+            return;
+          }
+        };
+        // `warp::addr::remote` only works with `warp::serve`, attach the peer address for `server::throttle`.
+        let service = service_fn(move |mut request: Request<Body>| {
+          request.extensions_mut().insert(RemoteAddr(remote_addr));
+          service.clone().call(request)
+        });
+        let connection = Http::new().serve_connection(stream, service).with_upgrades();
+        tokio::pin!(connection);
+        let result = tokio::select! {
+          result = &mut connection => result,
+          _ = triggered => {
+            // Finish in-flight requests and close the connection instead of waiting for the next one.
+            connection.as_mut().graceful_shutdown();
+            connection.await
+          }
+        };
+        if let Err(error) = result {
+          info!("Https connection with {} is closed, error: {}", remote_addr, error);
+        }
+      });
+    }
+  });
+
+  Ok(local_addr)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::{Path, PathBuf};
+  use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+  };
+  use tokio_rustls::{rustls::ClientConfig, webpki::DNSNameRef, TlsConnector};
+
+  fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("raid-finder-tls-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    dir
+  }
+
+  /// Write a self-signed certificate of `localhost` into `dir`, return the certificate in PEM format.
+  fn write_self_signed(dir: &Path) -> (TlsConfig, String) {
+    let certificate = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+    let cert_pem = certificate.serialize_pem().unwrap();
+    let tls = TlsConfig {
+      cert_path: dir.join("cert.pem").to_string_lossy().into_owned(),
+      key_path: dir.join("key.pem").to_string_lossy().into_owned(),
+    };
+    std::fs::write(&tls.cert_path, &cert_pem).unwrap();
+    std::fs::write(&tls.key_path, certificate.serialize_private_key_pem()).unwrap();
+
+    (tls, cert_pem)
+  }
+
+  async fn get(addr: SocketAddr, root_pem: &str, path: &str) -> std::io::Result<String> {
+    let mut client_config = ClientConfig::new();
+    client_config.root_store.add_pem_file(&mut root_pem.as_bytes()).unwrap();
+    let connector = TlsConnector::from(Arc::new(client_config));
+    let stream = TcpStream::connect(addr).await?;
+    let mut stream = connector
+      .connect(DNSNameRef::try_from_ascii_str("localhost").unwrap(), stream)
+      .await?;
+    let request = format!("GET {} HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = vec![];
+    // The connection might be closed without close_notify, which is fine after the response is read.
+    let _ = stream.read_to_end(&mut response).await;
+
+    Ok(String::from_utf8_lossy(&response).into_owned())
+  }
+
+  #[tokio::test]
+  async fn test_serve() {
+    let (tls, cert_pem) = write_self_signed(&temp_dir("serve"));
+    let certificates = Arc::new(Certificates::new(tls).unwrap());
+    let filter = warp::path("livez")
+      .map(|| "ok")
+      .recover(crate::server::recover::handle_rejection);
+    let shutdown = Arc::new(Shutdown::new(Duration::from_secs(5)));
+    let addr = serve(filter, "127.0.0.1:0".parse().unwrap(), certificates, shutdown.clone()).unwrap();
+
+    let response = get(addr, &cert_pem, "/livez").await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("ok"));
+    let response = get(addr, &cert_pem, "/unknown").await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+
+    shutdown.trigger();
+    assert!(shutdown.drain().await);
+    assert!(TcpStream::connect(addr).await.is_err());
+  }
+
+  #[tokio::test]
+  async fn test_reload() {
+    let dir = temp_dir("reload");
+    let (tls, old_pem) = write_self_signed(&dir);
+    let certificates = Arc::new(Certificates::new(tls.clone()).unwrap());
+    let filter = warp::any().map(|| "ok");
+    let shutdown = Arc::new(Shutdown::new(Duration::from_secs(5)));
+    let addr = serve(filter, "127.0.0.1:0".parse().unwrap(), certificates.clone(), shutdown).unwrap();
+    assert!(get(addr, &old_pem, "/").await.unwrap().starts_with("HTTP/1.1 200 OK"));
+
+    let (_, new_pem) = write_self_signed(&dir);
+    certificates.reload().unwrap();
+    assert!(get(addr, &new_pem, "/").await.unwrap().starts_with("HTTP/1.1 200 OK"));
+    assert!(get(addr, &old_pem, "/").await.is_err());
+
+    // Broken files are rejected and the previous certificates keep serving.
+    std::fs::write(&tls.key_path, "not a key").unwrap();
+    assert!(matches!(
+      certificates.reload(),
+      Err(error::Error::TlsPrivateKeyNotFound { .. })
+    ));
+    assert!(get(addr, &new_pem, "/").await.unwrap().starts_with("HTTP/1.1 200 OK"));
+  }
+
+  #[test]
+  fn test_load_errors() {
+    let dir = temp_dir("errors");
+    let missing = dir.join("missing.pem").to_string_lossy().into_owned();
+    assert!(matches!(
+      load_certificates(&missing),
+      Err(error::Error::TlsReadFile { .. })
+    ));
+    let empty = dir.join("empty.pem");
+    std::fs::write(&empty, "").unwrap();
+    assert!(matches!(
+      load_certificates(&empty.to_string_lossy()),
+      Err(error::Error::TlsCertificateNotFound { .. })
+    ));
+  }
+}
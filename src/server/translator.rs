@@ -0,0 +1,697 @@
+use crate::{
+  client::redis::RedisWrite,
+  common::redis::{
+    gbf_raid_boss_key, gbf_translation_key, gbf_translation_review_key, gbf_translator_key, gbf_translator_lock_key,
+  },
+  error::HttpError,
+  models::Language,
+  proto::{
+    raid_boss::{RaidBoss, RaidBossExt},
+    translation::{Translation, TranslationExt},
+  },
+};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+///
+/// A translated boss, both names map to each other in the translator map.
+/// A locked pair cannot be overridden or deleted until it is unlocked.
+/// Pairs matched by the translator keep their `Translation` scores, which are listed along with them.
+///
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TranslationPair {
+  pub jp_name: String,
+  pub en_name: String,
+  #[serde(default)]
+  pub locked: bool,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub score: Option<f64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub margin: Option<f64>,
+}
+
+///
+/// Changes of the admin translator APIs.
+///
+/// * `Create` - pair two unpaired names.
+/// * `Override` - pair two names, their previous partners become unpaired.
+/// * `Delete` - unpair a name and its partner, the translator may match them again.
+/// * `Lock` - lock or unlock the pair of a name.
+/// * `Accept` - pair a name waiting for review with its match, like `Create`.
+/// * `Reject` - remove a name from the review queue, the translator may match it again.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+  Create(TranslationPair),
+  Override(TranslationPair),
+  Delete(String),
+  Lock { name: String, locked: bool },
+  Accept(String),
+  Reject(String),
+}
+
+impl Change {
+  /// The pair to write, an accepted review is paired by the names of its `Translation`.
+  pub fn pair(&self, review: Option<&Translation>) -> Option<TranslationPair> {
+    match (self, review) {
+      (Change::Create(pair), _) | (Change::Override(pair), _) => Some(pair.clone()),
+      (Change::Accept(_), Some(review)) => {
+        let (jp_name, en_name) = review.names();
+        Some(TranslationPair {
+          jp_name: jp_name.into(),
+          en_name: en_name.into(),
+          locked: false,
+          score: Some(review.score),
+          margin: Some(review.margin),
+        })
+      }
+      _ => None,
+    }
+  }
+
+  /// The reviewed name of `Accept` and `Reject`.
+  pub fn review_name(&self) -> Option<&str> {
+    match self {
+      Change::Accept(name) | Change::Reject(name) => Some(name),
+      _ => None,
+    }
+  }
+
+  /// Names whose records are read, the names of the change and `pair`, and their current partners.
+  pub fn names(&self, pair: Option<&TranslationPair>, map: &HashMap<String, String>) -> Vec<String> {
+    let mut names = match self {
+      Change::Delete(name) | Change::Lock { name, .. } | Change::Accept(name) | Change::Reject(name) => {
+        vec![name.clone()]
+      }
+      _ => vec![],
+    };
+    if let Some(pair) = pair {
+      names.extend(vec![pair.jp_name.clone(), pair.en_name.clone()]);
+    }
+    let partners = names
+      .iter()
+      .filter_map(|name| map.get(name))
+      .filter(|partner| !partner.is_empty())
+      .cloned()
+      .collect::<Vec<_>>();
+
+    let mut names = names.into_iter().chain(partners).collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+  }
+}
+
+///
+/// State of the names touched by a change, it is read without locking the translator map.
+///
+#[derive(Debug, Default)]
+pub struct Snapshot {
+  /// Translator map entries of the touched names, `None` if a name is not in the map.
+  pub entries: Entries,
+  /// Translated names of the touched names, pending translations are not included.
+  pub partners: HashMap<String, String>,
+  /// Names of locked pairs.
+  pub locked: HashSet<String>,
+  /// `RaidBoss` records of the touched japanese names.
+  pub bosses: Vec<RaidBoss>,
+  /// Level and image of a new `RaidBoss` record, from a raw boss of either name.
+  pub raw: Option<(i32, String)>,
+  /// The queued `Translation` of the reviewed name.
+  pub review: Option<Translation>,
+}
+
+/// Translator map entries of some names, `None` if a name is not in the map.
+pub type Entries = HashMap<String, Option<String>>;
+
+/// Entries of `names` in the translator map.
+pub fn entries<'a, I>(map: &HashMap<String, String>, names: I) -> Entries
+where
+  I: IntoIterator<Item = &'a String>,
+{
+  names
+    .into_iter()
+    .map(|name| (name.clone(), map.get(name).cloned()))
+    .collect()
+}
+
+/// Whether the translator map still has `entries`, so a change planned from them could be applied.
+pub fn has_entries(map: &HashMap<String, String>, entries: &Entries) -> bool {
+  entries.iter().all(|(name, entry)| map.get(name) == entry.as_ref())
+}
+
+/// Put `entries` back into the translator map.
+pub fn restore_entries(map: &mut HashMap<String, String>, entries: &Entries) {
+  for (name, entry) in entries {
+    match entry {
+      Some(entry) => map.insert(name.clone(), entry.clone()),
+      None => map.remove(name),
+    };
+  }
+}
+
+///
+/// Redis writes and translator map updates of a change, the map is updated first so the translator map is not
+/// locked while redis is written in one transaction.
+///
+#[derive(Debug, Default, PartialEq)]
+pub struct Plan {
+  pub writes: Vec<RedisWrite>,
+  pub removed: Vec<String>,
+  pub inserted: Vec<(String, String)>,
+}
+
+impl Plan {
+  /// Apply the plan to the translator map, before redis is written.
+  pub fn apply(&self, map: &mut HashMap<String, String>) {
+    for name in &self.removed {
+      map.remove(name);
+    }
+    map.extend(self.inserted.iter().cloned());
+  }
+
+  fn set_boss(&mut self, prefix: &str, raid_boss: RaidBoss, ttl: u32) {
+    self.writes.push(RedisWrite::Set {
+      key: gbf_raid_boss_key(prefix, Language::Japanese, &raid_boss),
+      value: raid_boss.encode_to_vec(),
+      ttl: ttl as usize,
+    });
+  }
+
+  fn lock(&mut self, prefix: &str, names: &[&str], locked: bool) {
+    for name in names {
+      let key = gbf_translator_lock_key(prefix, name);
+      self.writes.push(match locked {
+        true => RedisWrite::Set {
+          key,
+          value: b"1".to_vec(),
+          ttl: 0,
+        },
+        false => RedisWrite::Del { key },
+      });
+    }
+  }
+
+  /// Unpair `name` and `partner`, `RaidBoss` records of either name lose their english name.
+  fn unpair(&mut self, prefix: &str, snapshot: &Snapshot, name: &str, partner: &str, boss_ttl: u32) {
+    for name in &[name, partner] {
+      self.writes.push(RedisWrite::Del {
+        key: gbf_translator_key(prefix, name),
+      });
+      self.writes.push(RedisWrite::Del {
+        key: gbf_translation_key(prefix, name),
+      });
+      self.removed.push(name.to_string());
+    }
+    self.lock(prefix, &[name, partner], false);
+    for boss in snapshot
+      .bosses
+      .iter()
+      .filter(|boss| boss.jp_name == name || boss.jp_name == partner)
+    {
+      self.set_boss(
+        prefix,
+        RaidBoss::apply_args("", boss.jp_name.as_str(), boss.level, boss.image.as_str()),
+        boss_ttl,
+      );
+    }
+  }
+
+  ///
+  /// Pair the names of `pair`, their queued reviews and stale scores are removed.
+  /// Previous partners are unpaired if `replace`, otherwise a paired name is a conflict.
+  ///
+  fn pair(
+    &mut self,
+    pair: &TranslationPair,
+    snapshot: &Snapshot,
+    prefix: &str,
+    boss_ttl: u32,
+    replace: bool,
+  ) -> Result<(), HttpError> {
+    let partner = |name: &str| snapshot.partners.get(name).cloned();
+    let is_locked = |name: &str| snapshot.locked.contains(name);
+    let (jp_name, en_name) = (pair.jp_name.as_str(), pair.en_name.as_str());
+    if jp_name.is_empty() || en_name.is_empty() || jp_name == en_name {
+      return Err(HttpError::InvalidTranslationError);
+    }
+    let old_en_name = partner(jp_name).filter(|name| name != en_name);
+    let old_jp_name = partner(en_name).filter(|name| name != jp_name);
+    if !replace && (partner(jp_name).is_some() || partner(en_name).is_some()) {
+      return Err(HttpError::TranslationConflictError);
+    }
+    if [jp_name, en_name]
+      .iter()
+      .any(|name| partner(name).is_some() && is_locked(name))
+    {
+      return Err(HttpError::TranslationLockedError);
+    }
+
+    // Previous partners are unpaired from the other names of their pairs, which are paired below.
+    if let Some(old_en_name) = &old_en_name {
+      self.unpair(prefix, snapshot, jp_name, old_en_name, boss_ttl);
+    }
+    if let Some(old_jp_name) = &old_jp_name {
+      self.unpair(prefix, snapshot, old_jp_name, en_name, boss_ttl);
+    }
+
+    for (name, translated) in &[(jp_name, en_name), (en_name, jp_name)] {
+      self.writes.push(RedisWrite::Set {
+        key: gbf_translator_key(prefix, name),
+        value: translated.as_bytes().to_vec(),
+        ttl: 0,
+      });
+      self.inserted.push((name.to_string(), translated.to_string()));
+    }
+    self.lock(prefix, &[jp_name, en_name], pair.locked);
+
+    let bosses = snapshot
+      .bosses
+      .iter()
+      .filter(|boss| boss.jp_name == jp_name)
+      .map(|boss| (boss.level, boss.image.clone()))
+      .collect::<Vec<_>>();
+    let bosses = match (bosses.is_empty(), &snapshot.raw) {
+      (false, _) => bosses,
+      (true, Some(raw)) => vec![raw.clone()],
+      (true, None) => return Err(HttpError::BossNotFoundError),
+    };
+    for (level, image) in bosses {
+      self.set_boss(prefix, RaidBoss::apply_args(en_name, jp_name, level, image), boss_ttl);
+    }
+    for name in &[jp_name, en_name] {
+      self.writes.push(RedisWrite::Del {
+        key: gbf_translation_review_key(prefix, name),
+      });
+    }
+    self.writes.push(RedisWrite::Del {
+      key: gbf_translation_key(prefix, jp_name),
+    });
+
+    Ok(())
+  }
+}
+
+///
+/// Plan the redis writes and translator map updates of `change`.
+///
+/// # Arguments
+/// * `change` - the requested change.
+/// * `snapshot` - current state of the names of `change`.
+/// * `prefix` - Redis namespace, `Config.redis_prefix`.
+/// * `boss_ttl` - TTL of `RaidBoss` records, `Config.boss_ttl`.
+///
+pub fn plan(change: &Change, snapshot: &Snapshot, prefix: &str, boss_ttl: u32) -> Result<Plan, HttpError> {
+  let mut plan = Plan::default();
+  let partner = |name: &str| snapshot.partners.get(name).cloned();
+  let is_locked = |name: &str| snapshot.locked.contains(name);
+
+  match change {
+    Change::Create(pair) => plan.pair(pair, snapshot, prefix, boss_ttl, false)?,
+    Change::Override(pair) => plan.pair(pair, snapshot, prefix, boss_ttl, true)?,
+    Change::Delete(name) => {
+      let partner = partner(name).ok_or(HttpError::TranslationNotFoundError)?;
+      if is_locked(name) {
+        return Err(HttpError::TranslationLockedError);
+      }
+      plan.unpair(prefix, snapshot, name, &partner, boss_ttl);
+    }
+    Change::Lock { name, locked } => {
+      let partner = partner(name).ok_or(HttpError::TranslationNotFoundError)?;
+      plan.lock(prefix, &[name, &partner], *locked);
+    }
+    Change::Accept(_) => {
+      let review = snapshot.review.as_ref().ok_or(HttpError::ReviewNotFoundError)?;
+      let pair = change.pair(Some(review)).ok_or(HttpError::ReviewNotFoundError)?;
+      plan.pair(&pair, snapshot, prefix, boss_ttl, false)?;
+      plan.writes.push(RedisWrite::Set {
+        key: gbf_translation_key(prefix, &pair.jp_name),
+        value: review.encode_to_vec(),
+        ttl: 0,
+      });
+    }
+    Change::Reject(name) => {
+      snapshot.review.as_ref().ok_or(HttpError::ReviewNotFoundError)?;
+      plan.writes.push(RedisWrite::Del {
+        key: gbf_translation_review_key(prefix, name),
+      });
+      // The pending name is matched again on its next tweet.
+      if partner(name).is_none() {
+        plan.removed.push(name.clone());
+      }
+    }
+  }
+
+  Ok(plan)
+}
+
+///
+/// Pairs of a translator map, sorted by their japanese names.
+///
+/// # Arguments
+/// * `map` - names to their translated names, see `common::redis::get_translator_map`.
+/// * `locked` - names of locked pairs.
+/// * `jp_names` - japanese names of `RaidBoss` records, names without records are japanese if they are not ASCII.
+/// * `translations` - scores of pairs matched by the translator, by their japanese names.
+///
+pub fn pairs(
+  map: &HashMap<String, String>,
+  locked: &HashSet<String>,
+  jp_names: &HashSet<String>,
+  translations: &HashMap<String, Translation>,
+) -> Vec<TranslationPair> {
+  let is_jp = |name: &str| jp_names.contains(name) || !name.is_ascii();
+  let mut pairs = map
+    .iter()
+    .filter(|(name, translated)| map.get(translated.as_str()) == Some(name))
+    .filter(|(name, translated)| match (is_jp(name), is_jp(translated)) {
+      (true, false) => true,
+      (false, true) => false,
+      _ => name < translated,
+    })
+    .map(|(jp_name, en_name)| TranslationPair {
+      jp_name: jp_name.clone(),
+      en_name: en_name.clone(),
+      locked: locked.contains(jp_name),
+      score: translations.get(jp_name).map(|translation| translation.score),
+      margin: translations.get(jp_name).map(|translation| translation.margin),
+    })
+    .collect::<Vec<_>>();
+  pairs.sort_by(|a, b| a.jp_name.cmp(&b.jp_name));
+
+  pairs
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::resources::redis::DEFAULT_PREFIX;
+
+  const IMAGE: &str = r"https://pbs.twimg.com/media/DumtNdnUYAE9PCr.jpg";
+
+  fn pair(jp_name: &str, en_name: &str) -> TranslationPair {
+    TranslationPair {
+      jp_name: jp_name.into(),
+      en_name: en_name.into(),
+      locked: false,
+      score: None,
+      margin: None,
+    }
+  }
+
+  fn snapshot(pairs: &[(&str, &str)]) -> Snapshot {
+    let mut snapshot = Snapshot::default();
+    for (jp_name, en_name) in pairs {
+      snapshot.partners.insert(jp_name.to_string(), en_name.to_string());
+      snapshot.partners.insert(en_name.to_string(), jp_name.to_string());
+      snapshot
+        .bosses
+        .push(RaidBoss::apply_args(*en_name, *jp_name, 200, IMAGE));
+    }
+    snapshot
+  }
+
+  fn boss_write(en_name: &str, jp_name: &str) -> RedisWrite {
+    RedisWrite::Set {
+      key: format!("gbf:boss:200.{}", jp_name),
+      value: RaidBoss::apply_args(en_name, jp_name, 200, IMAGE).encode_to_vec(),
+      ttl: 3600,
+    }
+  }
+
+  fn set(key: &str, value: &str) -> RedisWrite {
+    RedisWrite::Set {
+      key: key.into(),
+      value: value.as_bytes().to_vec(),
+      ttl: 0,
+    }
+  }
+
+  fn del(key: &str) -> RedisWrite {
+    RedisWrite::Del { key: key.into() }
+  }
+
+  /// Writes of pairing `jp_name` and `en_name` after their `RaidBoss` record.
+  fn paired_writes(jp_name: &str, en_name: &str) -> Vec<RedisWrite> {
+    vec![
+      del(&format!("gbf:translation_review:{}", jp_name)),
+      del(&format!("gbf:translation_review:{}", en_name)),
+      del(&format!("gbf:translation:{}", jp_name)),
+    ]
+  }
+
+  fn unpaired_writes(name: &str, partner: &str) -> Vec<RedisWrite> {
+    vec![
+      del(&format!("gbf:translator:{}", name)),
+      del(&format!("gbf:translation:{}", name)),
+      del(&format!("gbf:translator:{}", partner)),
+      del(&format!("gbf:translation:{}", partner)),
+      del(&format!("gbf:translator_lock:{}", name)),
+      del(&format!("gbf:translator_lock:{}", partner)),
+    ]
+  }
+
+  #[test]
+  fn test_plan_create() {
+    let change = Change::Create(pair("Lv200 アーカーシャ", "Lvl 200 Akasha"));
+    let mut snapshot = snapshot(&[]);
+    assert!(matches!(
+      plan(&change, &snapshot, DEFAULT_PREFIX, 3600),
+      Err(HttpError::BossNotFoundError)
+    ));
+
+    snapshot.raw = Some((200, IMAGE.into()));
+    let plan = plan(&change, &snapshot, DEFAULT_PREFIX, 3600).unwrap();
+    let mut writes = vec![
+      set("gbf:translator:Lv200 アーカーシャ", "Lvl 200 Akasha"),
+      set("gbf:translator:Lvl 200 Akasha", "Lv200 アーカーシャ"),
+      del("gbf:translator_lock:Lv200 アーカーシャ"),
+      del("gbf:translator_lock:Lvl 200 Akasha"),
+      boss_write("Lvl 200 Akasha", "Lv200 アーカーシャ"),
+    ];
+    writes.extend(paired_writes("Lv200 アーカーシャ", "Lvl 200 Akasha"));
+    assert_eq!(writes, plan.writes);
+
+    let mut map = HashMap::new();
+    map.insert("Lv200 アーカーシャ".to_owned(), "".to_owned());
+    plan.apply(&mut map);
+    assert_eq!("Lvl 200 Akasha", map["Lv200 アーカーシャ"]);
+    assert_eq!("Lv200 アーカーシャ", map["Lvl 200 Akasha"]);
+  }
+
+  #[test]
+  fn test_plan_invalid() {
+    let snapshot = snapshot(&[("Lv200 アーカーシャ", "Lvl 200 Akasha")]);
+    for change in &[
+      Change::Create(pair("", "Lvl 200 Akasha")),
+      Change::Override(pair("Lvl 200 Akasha", "Lvl 200 Akasha")),
+    ] {
+      assert!(matches!(
+        plan(change, &snapshot, DEFAULT_PREFIX, 3600),
+        Err(HttpError::InvalidTranslationError)
+      ));
+    }
+    let change = Change::Create(pair("Lv200 アーカーシャ", "Lvl 200 Grand Order"));
+    assert!(matches!(
+      plan(&change, &snapshot, DEFAULT_PREFIX, 3600),
+      Err(HttpError::TranslationConflictError)
+    ));
+    let change = Change::Delete("Lv200 ジ・オーダー・グランデ".into());
+    assert!(matches!(
+      plan(&change, &snapshot, DEFAULT_PREFIX, 3600),
+      Err(HttpError::TranslationNotFoundError)
+    ));
+  }
+
+  #[test]
+  fn test_plan_override() {
+    let mut snapshot = snapshot(&[
+      ("Lv200 アーカーシャ", "Lvl 200 Grand Order"),
+      ("Lv200 ジ・オーダー・グランデ", "Lvl 200 Akasha"),
+    ]);
+    let change = Change::Override(TranslationPair {
+      locked: true,
+      ..pair("Lv200 アーカーシャ", "Lvl 200 Akasha")
+    });
+    let plan = plan(&change, &snapshot, DEFAULT_PREFIX, 3600).unwrap();
+
+    let mut writes = unpaired_writes("Lv200 アーカーシャ", "Lvl 200 Grand Order");
+    writes.push(boss_write("", "Lv200 アーカーシャ"));
+    writes.extend(unpaired_writes("Lv200 ジ・オーダー・グランデ", "Lvl 200 Akasha"));
+    writes.push(boss_write("", "Lv200 ジ・オーダー・グランデ"));
+    writes.extend(vec![
+      set("gbf:translator:Lv200 アーカーシャ", "Lvl 200 Akasha"),
+      set("gbf:translator:Lvl 200 Akasha", "Lv200 アーカーシャ"),
+      set("gbf:translator_lock:Lv200 アーカーシャ", "1"),
+      set("gbf:translator_lock:Lvl 200 Akasha", "1"),
+      boss_write("Lvl 200 Akasha", "Lv200 アーカーシャ"),
+    ]);
+    writes.extend(paired_writes("Lv200 アーカーシャ", "Lvl 200 Akasha"));
+    assert_eq!(writes, plan.writes);
+
+    let mut map = snapshot.partners.clone();
+    plan.apply(&mut map);
+    assert_eq!(2, map.len());
+    assert_eq!("Lvl 200 Akasha", map["Lv200 アーカーシャ"]);
+    assert_eq!("Lv200 アーカーシャ", map["Lvl 200 Akasha"]);
+
+    // Locked pairs are kept until they are unlocked.
+    snapshot.locked.insert("Lv200 ジ・オーダー・グランデ".into());
+    snapshot.locked.insert("Lvl 200 Akasha".into());
+    assert!(matches!(
+      super::plan(&change, &snapshot, DEFAULT_PREFIX, 3600),
+      Err(HttpError::TranslationLockedError)
+    ));
+  }
+
+  #[test]
+  fn test_plan_delete_and_lock() {
+    let mut snapshot = snapshot(&[("Lv200 アーカーシャ", "Lvl 200 Akasha")]);
+    let change = Change::Delete("Lvl 200 Akasha".into());
+    let plan = plan(&change, &snapshot, DEFAULT_PREFIX, 3600).unwrap();
+    let mut writes = unpaired_writes("Lvl 200 Akasha", "Lv200 アーカーシャ");
+    writes.push(boss_write("", "Lv200 アーカーシャ"));
+    assert_eq!(writes, plan.writes);
+    let mut map = snapshot.partners.clone();
+    plan.apply(&mut map);
+    assert!(map.is_empty());
+
+    let change = Change::Lock {
+      name: "Lvl 200 Akasha".into(),
+      locked: true,
+    };
+    let plan = super::plan(&change, &snapshot, DEFAULT_PREFIX, 3600).unwrap();
+    assert_eq!(2, plan.writes.len());
+    assert!(plan.removed.is_empty() && plan.inserted.is_empty());
+
+    snapshot.locked.insert("Lvl 200 Akasha".into());
+    assert!(matches!(
+      super::plan(
+        &Change::Delete("Lvl 200 Akasha".into()),
+        &snapshot,
+        DEFAULT_PREFIX,
+        3600
+      ),
+      Err(HttpError::TranslationLockedError)
+    ));
+  }
+
+  #[test]
+  fn test_plan_review() {
+    let review = Translation {
+      boss_name: "Lvl 120 Medusa".into(),
+      language: Language::English.to_string(),
+      translated_name: "Lv120 メドゥーサ".into(),
+      level: 120,
+      image: IMAGE.into(),
+      score: 0.22,
+      margin: 0.03,
+      runner_up: "Lv120 メタトロン".into(),
+    };
+    let accept = Change::Accept("Lvl 120 Medusa".into());
+    let reject = Change::Reject("Lvl 120 Medusa".into());
+    let mut snapshot = snapshot(&[]);
+    for change in &[&accept, &reject] {
+      assert!(matches!(
+        plan(change, &snapshot, DEFAULT_PREFIX, 3600),
+        Err(HttpError::ReviewNotFoundError)
+      ));
+    }
+
+    snapshot.review = Some(review.clone());
+    snapshot.raw = Some((200, IMAGE.into()));
+    let pair = accept.pair(snapshot.review.as_ref()).unwrap();
+    assert_eq!(
+      ("Lv120 メドゥーサ", "Lvl 120 Medusa"),
+      (pair.jp_name.as_str(), pair.en_name.as_str())
+    );
+    let plan = plan(&accept, &snapshot, DEFAULT_PREFIX, 3600).unwrap();
+    assert_eq!(
+      Some(&RedisWrite::Set {
+        key: "gbf:translation:Lv120 メドゥーサ".into(),
+        value: review.encode_to_vec(),
+        ttl: 0,
+      }),
+      plan.writes.last()
+    );
+    assert!(plan.writes.contains(&del("gbf:translation_review:Lvl 120 Medusa")));
+    let mut map = HashMap::new();
+    map.insert("Lvl 120 Medusa".to_owned(), "".to_owned());
+    plan.apply(&mut map);
+    assert_eq!("Lv120 メドゥーサ", map["Lvl 120 Medusa"]);
+
+    let plan = super::plan(&reject, &snapshot, DEFAULT_PREFIX, 3600).unwrap();
+    assert_eq!(vec![del("gbf:translation_review:Lvl 120 Medusa")], plan.writes);
+    assert_eq!(vec!["Lvl 120 Medusa".to_owned()], plan.removed);
+
+    // The match is paired with another name meanwhile.
+    snapshot
+      .partners
+      .insert("Lv120 メドゥーサ".into(), "Lvl 120 Medusa (Impossible)".into());
+    assert!(matches!(
+      super::plan(&accept, &snapshot, DEFAULT_PREFIX, 3600),
+      Err(HttpError::TranslationConflictError)
+    ));
+  }
+
+  #[test]
+  fn test_entries() {
+    let mut map = snapshot(&[("Lv200 アーカーシャ", "Lvl 200 Akasha")]).partners;
+    map.insert("Lvl 120 Medusa".into(), "".into());
+    let names = vec![
+      "Lvl 200 Akasha".to_owned(),
+      "Lvl 120 Medusa".to_owned(),
+      "Lv120 メドゥーサ".to_owned(),
+    ];
+    let entries = entries(&map, &names);
+    assert_eq!(None, entries["Lv120 メドゥーサ"]);
+    assert!(has_entries(&map, &entries));
+
+    // Another change of the names meanwhile.
+    let mut changed = map.clone();
+    Plan {
+      removed: vec!["Lvl 120 Medusa".into()],
+      inserted: vec![("Lv120 メドゥーサ".into(), "Lvl 120 Medusa".into())],
+      ..Plan::default()
+    }
+    .apply(&mut changed);
+    assert!(!has_entries(&changed, &entries));
+    restore_entries(&mut changed, &entries);
+    assert_eq!(map, changed);
+  }
+
+  #[test]
+  fn test_pairs() {
+    let snapshot = snapshot(&[
+      ("Lv200 アーカーシャ", "Lvl 200 Akasha"),
+      ("Lv150 Proto", "Lvl 150 Proto"),
+    ]);
+    let mut map = snapshot.partners;
+    map.insert("Lv100 ジ・オーダー・グランデ".into(), "".into());
+    let locked = vec!["Lv200 アーカーシャ".to_owned()].into_iter().collect();
+    let jp_names = vec!["Lv150 Proto".to_owned()].into_iter().collect();
+    let mut translations = HashMap::new();
+    translations.insert(
+      "Lv200 アーカーシャ".to_owned(),
+      Translation {
+        score: 0.01,
+        margin: 0.4,
+        ..Translation::default()
+      },
+    );
+
+    assert_eq!(
+      vec![
+        pair("Lv150 Proto", "Lvl 150 Proto"),
+        TranslationPair {
+          locked: true,
+          score: Some(0.01),
+          margin: Some(0.4),
+          ..pair("Lv200 アーカーシャ", "Lvl 200 Akasha")
+        },
+      ],
+      pairs(&map, &locked, &jp_names, &translations)
+    );
+  }
+}
@@ -0,0 +1,367 @@
+// Built-in web UI of the raid finder, it only talks to the server which serves it.
+(function () {
+  "use strict";
+
+  var STORAGE_SELECTED = "raid-finder.selected";
+  var STORAGE_LEVEL = "raid-finder.level";
+  var STORAGE_API_KEY = "raid-finder.api-key";
+  var STORAGE_AUTO_COPY = "raid-finder.auto-copy";
+  var MAX_RAIDS = 100;
+  var PING_INTERVAL_MS = 30000;
+  var MAX_RECONNECT_DELAY_MS = 30000;
+  // New bosses are found while the finder is running.
+  var REFRESH_BOSSES_INTERVAL_MS = 5 * 60 * 1000;
+
+  var elements = {
+    status: document.getElementById("status"),
+    level: document.getElementById("level"),
+    search: document.getElementById("search"),
+    bossList: document.getElementById("boss-list"),
+    apiKey: document.getElementById("api-key"),
+    autoCopy: document.getElementById("auto-copy"),
+    message: document.getElementById("message"),
+    empty: document.getElementById("empty"),
+    raidList: document.getElementById("raid-list"),
+    bossTemplate: document.getElementById("boss-template"),
+    raidTemplate: document.getElementById("raid-template"),
+  };
+
+  var state = {
+    bosses: [],
+    // Keys of selected bosses, see `bossKey`.
+    selected: new Set(load(STORAGE_SELECTED, [])),
+    // The last tweet id of each boss name, sent as `since` markers when reconnecting.
+    lastSeen: {},
+    socket: null,
+    reconnectDelay: 1000,
+  };
+
+  function load(key, fallback) {
+    try {
+      var value = localStorage.getItem(key);
+      return value === null ? fallback : JSON.parse(value);
+    } catch (e) {
+      return fallback;
+    }
+  }
+
+  function save(key, value) {
+    try {
+      localStorage.setItem(key, JSON.stringify(value));
+    } catch (e) {
+      // Private browsing may not have local storage, selections are kept for this page only.
+    }
+  }
+
+  function bossKey(boss) {
+    return boss.jp_name || boss.en_name;
+  }
+
+  // Tweets are matched by the exact boss name, which could be either the japanese or the english one.
+  function selectedBossNames() {
+    var names = [];
+    state.bosses.forEach(function (boss) {
+      if (state.selected.has(bossKey(boss))) {
+        [boss.jp_name, boss.en_name].forEach(function (name) {
+          if (name) names.push(name);
+        });
+      }
+    });
+    return names;
+  }
+
+  function showMessage(text) {
+    elements.message.textContent = text || "";
+    elements.message.hidden = !text;
+  }
+
+  function setStatus(text, online) {
+    elements.status.textContent = text;
+    elements.status.classList.toggle("online", online);
+  }
+
+  // ---- Raid tweets are protobuf `RaidTweet` messages, see `proto/messages/raid_tweet.proto`. ----
+
+  var utf8 = new TextDecoder("utf-8");
+
+  function decodeRaidTweet(buffer) {
+    var bytes = new Uint8Array(buffer);
+    var offset = 0;
+    var tweet = { tweet_id: "0", screen_name: "", boss_name: "", raid_id: "", text: "", created: 0, language: "" };
+
+    function varint() {
+      var result = BigInt(0);
+      var shift = BigInt(0);
+      for (;;) {
+        if (offset >= bytes.length) throw new Error("truncated varint");
+        var byte = bytes[offset++];
+        result |= BigInt(byte & 0x7f) << shift;
+        if ((byte & 0x80) === 0) return result;
+        shift += BigInt(7);
+      }
+    }
+
+    function string() {
+      var length = Number(varint());
+      var value = utf8.decode(bytes.subarray(offset, offset + length));
+      offset += length;
+      return value;
+    }
+
+    while (offset < bytes.length) {
+      var key = Number(varint());
+      var field = key >>> 3;
+      var wireType = key & 7;
+      if (wireType === 0) {
+        var number = varint();
+        if (field === 1) tweet.tweet_id = number.toString();
+        else if (field === 6) tweet.created = Number(number);
+      } else if (wireType === 2) {
+        var value = string();
+        if (field === 2) tweet.screen_name = value;
+        else if (field === 3) tweet.boss_name = value;
+        else if (field === 4) tweet.raid_id = value;
+        else if (field === 5) tweet.text = value;
+        else if (field === 7) tweet.language = value;
+      } else if (wireType === 1) {
+        offset += 8;
+      } else if (wireType === 5) {
+        offset += 4;
+      } else {
+        throw new Error("unsupported wire type " + wireType);
+      }
+    }
+    return tweet;
+  }
+
+  // ---- Bosses ----
+
+  function authHeaders(headers) {
+    var apiKey = elements.apiKey.value.trim();
+    if (apiKey) headers["x-api-key"] = apiKey;
+    return headers;
+  }
+
+  function fetchBosses() {
+    return fetch("/get_bosses", {
+      method: "POST",
+      headers: authHeaders({ "content-type": "application/json", accept: "application/json" }),
+      body: JSON.stringify({ level: 0 }),
+    }).then(function (response) {
+      return response.json().then(function (body) {
+        if (!response.ok) throw new Error(body.error ? body.error.message : response.statusText);
+        return body;
+      });
+    });
+  }
+
+  function renderLevels() {
+    var current = String(load(STORAGE_LEVEL, 0));
+    var levels = Array.from(
+      new Set(
+        state.bosses.map(function (boss) {
+          return boss.level;
+        })
+      )
+    ).sort(function (a, b) {
+      return b - a;
+    });
+    elements.level.length = 1;
+    levels.forEach(function (level) {
+      elements.level.add(new Option("Lv" + level, String(level)));
+    });
+    elements.level.value = levels.map(String).indexOf(current) >= 0 ? current : "0";
+  }
+
+  function renderBosses() {
+    var level = Number(elements.level.value);
+    var search = elements.search.value.trim().toLowerCase();
+    var fragment = document.createDocumentFragment();
+    state.bosses
+      .filter(function (boss) {
+        var matchesLevel = level === 0 || boss.level === level;
+        var matchesSearch = !search || (boss.en_name + " " + boss.jp_name).toLowerCase().indexOf(search) >= 0;
+        // Selected bosses are always listed, so they could be unselected.
+        return state.selected.has(bossKey(boss)) || (matchesLevel && matchesSearch);
+      })
+      .forEach(function (boss) {
+        var item = elements.bossTemplate.content.firstElementChild.cloneNode(true);
+        var checkbox = item.querySelector("input");
+        checkbox.checked = state.selected.has(bossKey(boss));
+        checkbox.addEventListener("change", function () {
+          if (checkbox.checked) state.selected.add(bossKey(boss));
+          else state.selected.delete(bossKey(boss));
+          save(STORAGE_SELECTED, Array.from(state.selected));
+          subscribe();
+        });
+        var image = item.querySelector("img");
+        if (boss.image) image.src = boss.image;
+        else image.removeAttribute("src");
+        item.querySelector(".en").textContent = boss.en_name || boss.jp_name;
+        item.querySelector(".jp").textContent = boss.en_name ? boss.jp_name : "";
+        fragment.appendChild(item);
+      });
+    elements.bossList.replaceChildren(fragment);
+    elements.empty.hidden = state.selected.size > 0;
+  }
+
+  function refreshBosses() {
+    return fetchBosses()
+      .then(function (bosses) {
+        state.bosses = bosses.sort(function (a, b) {
+          return b.level - a.level || bossKey(a).localeCompare(bossKey(b));
+        });
+        renderLevels();
+        renderBosses();
+        showMessage("");
+      })
+      .catch(function (error) {
+        showMessage("Cannot load bosses: " + error.message);
+      });
+  }
+
+  // ---- Raids ----
+
+  function copy(text) {
+    if (navigator.clipboard && window.isSecureContext) {
+      return navigator.clipboard.writeText(text);
+    }
+    // Clipboard API is only available on https and localhost.
+    var textarea = document.createElement("textarea");
+    textarea.value = text;
+    textarea.style.position = "fixed";
+    textarea.style.opacity = "0";
+    document.body.appendChild(textarea);
+    textarea.select();
+    var copied = document.execCommand("copy");
+    document.body.removeChild(textarea);
+    return copied ? Promise.resolve() : Promise.reject(new Error("copy is not supported"));
+  }
+
+  function copyRaid(item, raidId) {
+    copy(raidId).then(
+      function () {
+        item.classList.add("copied");
+      },
+      function (error) {
+        showMessage("Cannot copy battle ID: " + error.message);
+      }
+    );
+  }
+
+  function formatTime(created) {
+    var seconds = Math.max(0, Math.round((Date.now() - created) / 1000));
+    if (seconds < 60) return seconds + "s ago";
+    if (seconds < 3600) return Math.floor(seconds / 60) + "m ago";
+    return new Date(created).toLocaleTimeString();
+  }
+
+  function addRaid(tweet) {
+    if (!tweet.raid_id) return;
+    state.lastSeen[tweet.boss_name] = tweet.tweet_id;
+    var item = elements.raidTemplate.content.firstElementChild.cloneNode(true);
+    var button = item.querySelector(".raid-id");
+    button.textContent = tweet.raid_id;
+    button.addEventListener("click", function () {
+      copyRaid(item, tweet.raid_id);
+    });
+    item.querySelector(".boss-name").textContent = tweet.boss_name;
+    item.querySelector(".text").textContent = tweet.text;
+    item.querySelector(".screen-name").textContent = "@" + tweet.screen_name;
+    var time = item.querySelector("time");
+    time.dataset.created = String(tweet.created);
+    time.dateTime = new Date(tweet.created).toISOString();
+    time.textContent = formatTime(tweet.created);
+    elements.raidList.prepend(item);
+    while (elements.raidList.children.length > MAX_RAIDS) {
+      elements.raidList.lastElementChild.remove();
+    }
+    if (elements.autoCopy.checked) copyRaid(item, tweet.raid_id);
+  }
+
+  setInterval(function () {
+    elements.raidList.querySelectorAll("time").forEach(function (time) {
+      time.textContent = formatTime(Number(time.dataset.created));
+    });
+  }, 5000);
+
+  // ---- Stream ----
+
+  function subscribe() {
+    var socket = state.socket;
+    if (!socket || socket.readyState !== WebSocket.OPEN) return;
+    var bossNames = selectedBossNames();
+    var since = {};
+    bossNames.forEach(function (name) {
+      if (state.lastSeen[name]) since[name] = state.lastSeen[name];
+    });
+    socket.send(JSON.stringify({ boss_names: bossNames, since: since }));
+    elements.empty.hidden = bossNames.length > 0;
+  }
+
+  function connect() {
+    var url = new URL("/stream_bosses", location.href);
+    url.protocol = location.protocol === "https:" ? "wss:" : "ws:";
+    // The API key is sent as a subprotocol, so it is not written to access logs like a query parameter.
+    var apiKey = elements.apiKey.value.trim();
+    var protocols = apiKey ? ["raid-finder", "raid-finder.api-key." + apiKey] : [];
+
+    var socket = new WebSocket(url.toString(), protocols);
+    var ping = null;
+    socket.binaryType = "arraybuffer";
+    state.socket = socket;
+
+    socket.addEventListener("open", function () {
+      state.reconnectDelay = 1000;
+      setStatus("Online", true);
+      subscribe();
+      ping = setInterval(function () {
+        socket.send("__PING__");
+      }, PING_INTERVAL_MS);
+    });
+    socket.addEventListener("message", function (event) {
+      if (typeof event.data === "string") return;
+      try {
+        addRaid(decodeRaidTweet(event.data));
+      } catch (error) {
+        console.error("Cannot decode raid tweet", error);
+      }
+    });
+    socket.addEventListener("close", function (event) {
+      clearInterval(ping);
+      if (state.socket !== socket) return;
+      if (event.code === 1008) showMessage("Disconnected by the server: " + (event.reason || "policy violation"));
+      setStatus("Reconnecting...", false);
+      setTimeout(connect, state.reconnectDelay);
+      state.reconnectDelay = Math.min(state.reconnectDelay * 2, MAX_RECONNECT_DELAY_MS);
+    });
+  }
+
+  function reconnect() {
+    var socket = state.socket;
+    state.socket = null;
+    if (socket) socket.close();
+    connect();
+  }
+
+  // ---- Settings ----
+
+  elements.apiKey.value = load(STORAGE_API_KEY, "");
+  elements.autoCopy.checked = load(STORAGE_AUTO_COPY, false);
+  elements.apiKey.addEventListener("change", function () {
+    save(STORAGE_API_KEY, elements.apiKey.value.trim());
+    refreshBosses();
+    reconnect();
+  });
+  elements.autoCopy.addEventListener("change", function () {
+    save(STORAGE_AUTO_COPY, elements.autoCopy.checked);
+  });
+  elements.level.addEventListener("change", function () {
+    save(STORAGE_LEVEL, Number(elements.level.value));
+    renderBosses();
+  });
+  elements.search.addEventListener("input", renderBosses);
+
+  refreshBosses().then(connect);
+  setInterval(refreshBosses, REFRESH_BOSSES_INTERVAL_MS);
+})();
@@ -1,3 +1,4 @@
 pub mod tweet;
 pub mod websocket;
+pub mod publisher;
 mod translator;
@@ -0,0 +1,73 @@
+use crate::{
+  client::redis::Redis,
+  common::{redis::gbf_raid_tweets_channel, shutdown::Shutdown},
+  config::Config,
+  error,
+  proto::{raid_tweet::RaidTweet, MessageExt},
+  tasks::websocket::sending_message_to_websocket_client,
+  FinderClients, Result,
+};
+use futures::StreamExt;
+use futures_retry::{FutureRetry, RetryPolicy};
+use log::{error, info};
+use prost::Message;
+use std::sync::Arc;
+
+///
+/// Publish the raid tweet to `{prefix}:raid_tweets`, every `Role::Server` process will fan it out to its clients.
+///
+/// # Arguments
+/// * `raid_tweet` - raid tweet which is already persisted.
+/// * `config` - provides redis namespace.
+/// * `redis` - a redis client.
+/// * `shutdown` - waits for the raid tweet to be published.
+///
+pub fn publish_raid_tweet(raid_tweet: RaidTweet, config: Arc<Config>, redis: Arc<Redis>, shutdown: &Shutdown) {
+  shutdown.spawn(async move {
+    let result = match raid_tweet.to_bytes() {
+      Ok(bytes) => redis.publish(gbf_raid_tweets_channel(&config.redis_prefix), bytes).await,
+      Err(error) => Err(error),
+    };
+
+    if let Err(error) = result {
+      error!("Cannot publish raid tweet {}, error: {:?}", raid_tweet.tweet_id, error);
+    }
+  });
+}
+
+///
+/// Subscribe `{prefix}:raid_tweets` and forward every raid tweet to local websocket clients.
+/// Subscription will be restarted if redis connection is closed.
+///
+/// # Arguments
+/// * `config` - provides redis namespace.
+/// * `redis` - a redis client.
+/// * `clients` - websocket clients of this process.
+///
+pub async fn subscribe_raid_tweets(config: Arc<Config>, redis: Arc<Redis>, clients: FinderClients) -> Result<()> {
+  let channel = gbf_raid_tweets_channel(&config.redis_prefix);
+
+  FutureRetry::new(
+    || async {
+      let stream = redis.subscribe(channel.as_str()).await?;
+      info!("Subscribed to redis channel {}!", channel);
+      tokio::pin!(stream);
+
+      while let Some(bytes) = stream.next().await {
+        match RaidTweet::decode(bytes.as_slice()) {
+          Ok(raid_tweet) => sending_message_to_websocket_client(raid_tweet, clients.clone()).await,
+          Err(error) => error!("Cannot decode raid tweet from {}, error: {}", channel, error),
+        }
+      }
+
+      Err::<(), error::Error>(error::Error::RedisSubscriptionClosed)
+    },
+    |e: error::Error| {
+      info!("Redis subscription get error: {:?}, will resubscribe in 1 second.", e);
+      RetryPolicy::WaitRetry(std::time::Duration::from_secs(1))
+    },
+  )
+  .await
+  .map(|result| result.0)
+  .map_err(|error| error.0)
+}
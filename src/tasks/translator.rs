@@ -1,42 +1,59 @@
 use crate::{
-  common::redis::{gbf_get_possible_boss_name, gbf_raid_boss_key},
-  image::Comparison,
+  client::redis::RedisWrite,
+  common::redis::{
+    gbf_get_possible_boss_name, gbf_raid_boss_key, gbf_translation_key, gbf_translation_review_key,
+    gbf_translator_key, get_translation_reviews,
+  },
+  config::Config,
+  image::{Comparison, ImageCache, Verdict},
   models::Language,
-  proto::{raid_boss::RaidBoss, raid_boss_raw::RaidBossRaw},
-  resources::redis::{BOSS_EXPIRE_IN_30_DAYS_TTL, GBF_TRANSLATOR_KEY},
-  Redis, Result,
+  proto::{
+    raid_boss::{RaidBoss, RaidBossExt},
+    raid_boss_raw::RaidBossRaw,
+    translation::TranslationExt,
+    MessageExt,
+  },
+  Redis, Result, TranslatorMap,
 };
 
 use log::{error, info};
-use std::{collections::HashMap, str::FromStr, sync::Arc};
-use tokio::sync::RwLock;
+use std::{
+  collections::{HashMap, HashSet},
+  str::FromStr,
+  sync::Arc,
+};
 
 /// An independent translation task
 ///
 /// # Specification
 /// 1. Get all possible boss names (a possible boss means it level is same as the given boss).
-/// 2. Remove the boss which is already translated from possible bosses.
+/// 2. Remove the boss which is already translated or waiting for review from possible bosses.
 /// 3. Mget all possible boss.
-/// 4. Use `image::Comparison` to get the correspond boss.
-/// - If result from 4. is None(translated boss not found), return emtpy string.
+/// 4. Use `image::Comparison` to score possible bosses, their images are cached in `{prefix}:image:{url}`.
+/// - If the best one is a clear winner, pair the names and keep the scores in `{prefix}:translation:{jp_name}`.
+/// - If it is ambiguous, queue it in `{prefix}:translation_review:{name}`, the name stays pending until it is reviewed.
+/// - Otherwise the boss is not translated, return emtpy string.
 /// 5. Get translated name, it will be empty string or a real value.
-/// 6. If translated name is not empty set the boss in redis.
+/// 6. Set the boss in redis with the translated name.
+/// - If either name is paired by the admin APIs meanwhile, their pair is kept and nothing is written.
 ///
 /// # Arguments
 /// * `raid_boss` - a RaidBoss that you want to translate.
+/// * `config` - provides redis namespace and boss TTL.
 /// * `redis` - a redis client.
 /// * `map` - an actor map to memoize translation result.
 pub async fn translator_tasks(
   raid_boss_raw: RaidBossRaw,
+  config: Arc<Config>,
   redis: Arc<Redis>,
-  map: Arc<RwLock<HashMap<String, String>>>,
+  map: TranslatorMap,
 ) -> Result<()> {
-  let boss_name = raid_boss_raw.get_boss_name();
-  let from_language = Language::from_str(raid_boss_raw.get_language()).unwrap();
+  let boss_name = raid_boss_raw.boss_name.as_str();
+  let from_language = Language::from_str(raid_boss_raw.language.as_str()).unwrap();
   let to_language = from_language.opposite();
 
   // Get current translation map
-  let readable_map = map.read().await;
+  let readable_map = map.pairs.read().await;
   // Get the name map which is already translated.
   let paired_keys = readable_map
     .clone()
@@ -45,88 +62,173 @@ pub async fn translator_tasks(
     .map(|s| (s.0))
     .collect::<Vec<_>>();
   drop(readable_map);
+  // Names of other queued reviews and their matches are not matched again until they are reviewed.
+  let reviewed_names = get_translation_reviews(&config.redis_prefix, &redis)
+    .await?
+    .into_iter()
+    .filter(|review| review.boss_name != boss_name)
+    .flat_map(|review| vec![review.boss_name, review.translated_name])
+    .collect::<HashSet<_>>();
 
   // Get redis-cli keys for possible_boss
   // ex. gbf:jp:200.*
-  let possible_name = gbf_get_possible_boss_name(&raid_boss_raw, to_language);
+  let possible_name = gbf_get_possible_boss_name(&config.redis_prefix, &raid_boss_raw, to_language);
 
-  // filter out the possible_name which is already translated.
+  // filter out the possible_name which is already translated or waiting for review.
   let possible_boss_keys = redis
     .keys(possible_name)
     .await?
     .into_iter()
     .filter(|possible_key| match possible_key.split('.').last() {
-      Some(last) => !paired_keys.iter().any(|key| key == last),
+      Some(last) => !paired_keys.iter().any(|key| key == last) && !reviewed_names.contains(last),
       None => false,
     })
     .collect::<Vec<_>>();
 
   let possible_bosses = redis.mget_protobuf(possible_boss_keys).await?;
 
-  let comparison = Comparison::new(raid_boss_raw.clone(), possible_bosses);
-
-  let translated_name: String = match comparison.compare().await? {
-    Some(matched) => {
-      let translated_name = matched.get_boss_name();
-      let mut writable_map = map.write().await;
-      writable_map.insert(boss_name.into(), translated_name.into());
-      writable_map.insert(translated_name.into(), boss_name.into());
-      // Drop write lock before writing to redis, it will prevent map from getting lock during redis setting operation.
-      drop(writable_map);
-      let map_2_redis = vec![
-        (format!("{}:{}", GBF_TRANSLATOR_KEY, boss_name), translated_name),
-        (format!("{}:{}", GBF_TRANSLATOR_KEY, translated_name), boss_name),
+  let comparison = Comparison::new(raid_boss_raw.clone(), possible_bosses, config.translation.clone())
+    .with_cache(ImageCache::new(redis.clone(), &config.redis_prefix, config.image_ttl));
+
+  let ranking = comparison.compare().await?;
+  let verdict = ranking.verdict(&config.translation);
+  let translation = ranking.translation(&raid_boss_raw);
+  let is_paired = |map: &HashMap<String, String>, name: &str| matches!(map.get(name), Some(name) if !name.is_empty());
+
+  // Compare and set the map before writing redis, so the admin APIs cannot change the pair in between.
+  // Redis writes of the pairs are serialized with the admin APIs from here until the boss is saved.
+  let _writes = map.writes.lock().await;
+  let mut writable_map = map.pairs.write().await;
+  // The admin APIs may pair the boss while it is matched, keep their pair.
+  if is_paired(&writable_map, boss_name) {
+    info!("Translation of {} is changed while matching it, keep the current one.", boss_name);
+    return Ok(());
+  }
+  match (&verdict, &translation) {
+    (Verdict::Accept, Some(translation)) | (Verdict::Review, Some(translation))
+      if is_paired(&writable_map, &translation.translated_name) =>
+    {
+      info!(
+        "{} is paired while matching it with {}, keep the current one.",
+        translation.translated_name, boss_name
+      );
+      writable_map.remove(boss_name);
+
+      return Ok(());
+    }
+    (Verdict::Accept, Some(translation)) => {
+      writable_map.insert(boss_name.into(), translation.translated_name.clone());
+      writable_map.insert(translation.translated_name.clone(), boss_name.into());
+    }
+    // The name stays pending until it is reviewed, so its tweets do not match it again.
+    (Verdict::Review, Some(_)) => {}
+    _ => {
+      writable_map.remove(boss_name);
+    }
+  }
+  // Drop write lock before writing to redis, it will prevent map from getting lock during redis setting operation.
+  drop(writable_map);
+
+  let translated_name: String = match (verdict, translation) {
+    (Verdict::Accept, Some(translation)) => {
+      let translated_name = translation.translated_name.clone();
+      info!(
+        "Translate {} name to {} complete! score: {}, margin: {}. Writing to redis...",
+        boss_name, translated_name, translation.score, translation.margin
+      );
+      let writes = vec![
+        RedisWrite::Set {
+          key: gbf_translator_key(&config.redis_prefix, boss_name),
+          value: translated_name.as_bytes().to_vec(),
+          ttl: 0,
+        },
+        RedisWrite::Set {
+          key: gbf_translator_key(&config.redis_prefix, &translated_name),
+          value: boss_name.as_bytes().to_vec(),
+          ttl: 0,
+        },
+        RedisWrite::Set {
+          key: gbf_translation_key(&config.redis_prefix, translation.names().0),
+          value: translation.to_bytes()?,
+          ttl: 0,
+        },
+        RedisWrite::Del {
+          key: gbf_translation_review_key(&config.redis_prefix, boss_name),
+        },
+        RedisWrite::Del {
+          key: gbf_translation_review_key(&config.redis_prefix, &translated_name),
+        },
       ];
+      if let Err(error) = redis.transaction(writes).await {
+        // Unpair the names if they are not changed meanwhile, so the boss is matched again on its next tweet.
+        let mut writable_map = map.pairs.write().await;
+        if writable_map.get(boss_name) == Some(&translated_name)
+          && writable_map.get(&translated_name).map(String::as_str) == Some(boss_name)
+        {
+          writable_map.remove(boss_name);
+          writable_map.remove(&translated_name);
+        }
+        return Err(error);
+      }
+
+      Some(translated_name)
+    }
+    (Verdict::Review, Some(translation)) => {
       info!(
-        "Translate {} name to {} complete! Writing to redis...",
-        boss_name, translated_name
+        "{} may be {}, score: {}, margin: {} against {}. Queue it for review.",
+        boss_name, translation.translated_name, translation.score, translation.margin, translation.runner_up
       );
-      redis.set_multiple_string(map_2_redis).await?;
+      redis
+        .set_bytes(
+          gbf_translation_review_key(&config.redis_prefix, boss_name),
+          translation.to_bytes()?,
+          0,
+        )
+        .await?;
 
-      Some(translated_name.into())
+      None
     }
-    None => {
+    _ => {
       error!(
         "Cannot translate {}, maybe other language raid_boss is not exist",
         boss_name
       );
-      let mut writable_map = map.write().await;
-      writable_map.remove(boss_name);
 
       None
     }
   }
   .unwrap_or_else(|| "".into());
 
-  save_translated_raid_boss(&raid_boss_raw, translated_name.as_str(), redis).await
+  save_translated_raid_boss(&raid_boss_raw, translated_name.as_str(), &config, redis).await
 }
 
 pub async fn save_translated_raid_boss(
   raid_boss_raw: &RaidBossRaw,
   translated_name: &str,
+  config: &Config,
   redis: Arc<Redis>,
 ) -> Result<()> {
-  let from_language = Language::from_str(raid_boss_raw.get_language()).unwrap();
-  let mut names: (&str, &str) = (translated_name, raid_boss_raw.get_boss_name());
+  let from_language = Language::from_str(raid_boss_raw.language.as_str()).unwrap();
+  let mut names: (&str, &str) = (translated_name, raid_boss_raw.boss_name.as_str());
 
   if from_language == Language::English {
     // The first argument of RaidBoss::apply_args will always be en_name, if from_language is english en_name should be its name.
-    names = (raid_boss_raw.get_boss_name(), translated_name);
+    names = (raid_boss_raw.boss_name.as_str(), translated_name);
   }
 
-  let raid_boss = RaidBoss::apply_args(names.0, names.1, raid_boss_raw.get_level(), raid_boss_raw.get_image());
+  let raid_boss = RaidBoss::apply_args(names.0, names.1, raid_boss_raw.level, raid_boss_raw.image.as_str());
 
   // Raid Finder always chose japanese name as redis key
-  let redis_key_name = raid_boss.get_jp_name();
+  let redis_key_name = raid_boss.jp_name.as_str();
 
   match redis_key_name.is_empty() {
     true => Ok(()),
     false => {
       redis
         .set_protobuf(
-          gbf_raid_boss_key(Language::Japanese, &raid_boss),
+          gbf_raid_boss_key(&config.redis_prefix, Language::Japanese, &raid_boss),
           raid_boss,
-          BOSS_EXPIRE_IN_30_DAYS_TTL,
+          config.boss_ttl,
         )
         .await
     }
@@ -1,22 +1,28 @@
 use crate::{
   client::redis::Redis,
-  common::redis::{gbf_persistence_raid_tweet_key, gbf_raid_boss_jp_key_from_raw, gbf_raid_boss_raw_key},
+  common::{
+    redis::{
+      gbf_persistence_raid_tweet_key, gbf_raid_boss_jp_key_from_raw, gbf_raid_boss_raw_key, gbf_raid_dedup_key,
+      gbf_raid_tweets_stream_key,
+    },
+    health::Health,
+    shutdown::Shutdown,
+    stats::Stats,
+  },
+  config::Config,
   error,
   models::{Language, TranslatorResult, Tweet},
   parsers::status::StatusParser,
-  proto::{raid_boss_raw::RaidBossRaw, raid_tweet::RaidTweet},
-  resources::{
-    redis::{BOSS_EXPIRE_IN_30_DAYS_TTL, TWEET_PERSISTENCE_ONLY_2_HOURS_TTL},
-    GRANBLUE_FANTASY_SOURCE,
-  },
+  proto::{raid_boss_raw::RaidBossRaw, raid_tweet::RaidTweet, MessageExt},
+  resources::{redis::STREAM_TWEET_FIELD, GRANBLUE_FANTASY_SOURCE},
   tasks::translator,
-  Result,
+  Result, TranslatorMap,
 };
 
 use futures::TryFutureExt;
 use log::{debug, error};
-use std::{collections::HashMap, str::FromStr, sync::Arc};
-use tokio::sync::{mpsc, oneshot, RwLock};
+use std::{str::FromStr, sync::Arc};
+use tokio::sync::{mpsc, oneshot};
 
 enum TweetActorMessage {
   ///
@@ -58,7 +64,17 @@ enum TweetActorMessage {
     respond_to: oneshot::Sender<Result<RaidTweet>>,
   },
   ///
-  /// Persist the raid tweet into redis.
+  /// Drop the raid tweet if the same battle id of the same boss is already posted in `Config.raid_dedup_ttl`.
+  /// English tweets should be translated first, so that JP and EN tweets of the same raid are paired.
+  ///
+  /// # Arguments
+  /// * `raid_tweet` - raid_tweet which should be checked.
+  DeduplicateRaidTweet {
+    raid_tweet: RaidTweet,
+    respond_to: oneshot::Sender<Result<RaidTweet>>,
+  },
+  ///
+  /// Persist the raid tweet into redis, and append it to the raid tweet stream.
   ///
   /// # Arguments
   /// * `raid_tweet` - raid_tweet which should be persisted.
@@ -70,16 +86,32 @@ enum TweetActorMessage {
 
 struct TweetActor {
   receiver: mpsc::Receiver<TweetActorMessage>,
+  config: Arc<Config>,
   redis: Arc<Redis>,
-  map: Arc<RwLock<HashMap<String, String>>>,
+  map: TranslatorMap,
+  stats: Arc<Stats>,
+  health: Arc<Health>,
+  shutdown: Arc<Shutdown>,
 }
 
 impl TweetActor {
-  pub fn new(receiver: mpsc::Receiver<TweetActorMessage>, redis: Arc<Redis>, map: HashMap<String, String>) -> Self {
+  pub fn new(
+    receiver: mpsc::Receiver<TweetActorMessage>,
+    config: Arc<Config>,
+    redis: Arc<Redis>,
+    map: TranslatorMap,
+    stats: Arc<Stats>,
+    health: Arc<Health>,
+    shutdown: Arc<Shutdown>,
+  ) -> Self {
     TweetActor {
       receiver,
+      config,
       redis,
-      map: Arc::new(RwLock::new(map)),
+      map,
+      stats,
+      health,
+      shutdown,
     }
   }
 
@@ -97,12 +129,14 @@ impl TweetActor {
         // Only process tweet from granblue fantasy source
         GRANBLUE_FANTASY_SOURCE => {
           if let Some((raid_bow_raw, raid_tweet)) = StatusParser::parse(tweet) {
-            let redis_key = gbf_raid_boss_raw_key(&raid_bow_raw);
-            // Each boss will only have 30 days ttl
+            let redis_key = gbf_raid_boss_raw_key(&self.config.redis_prefix, &raid_bow_raw);
+            // Each boss will only live for `Config.boss_ttl`
             self
               .redis
-              .set_protobuf(&redis_key, raid_bow_raw.clone(), BOSS_EXPIRE_IN_30_DAYS_TTL)
+              .set_protobuf(&redis_key, raid_bow_raw.clone(), self.config.boss_ttl)
               .await?;
+            Stats::increase(&self.stats.parsed_tweets);
+            self.health.tweet_parsed();
             let _ = respond_to.send(Ok((raid_bow_raw, raid_tweet)));
           }
 
@@ -119,21 +153,22 @@ impl TweetActor {
         raid_boss_raw,
         respond_to,
       } => {
-        let translate_map = self.map.read().await;
+        let translate_map = self.map.pairs.read().await;
         // Return directly if boss_name is already translated.
-        match translate_map.get(raid_boss_raw.get_boss_name()) {
+        match translate_map.get(raid_boss_raw.boss_name.as_str()) {
           Some(translated) => {
             // If value in map is an empty string, it indicate that the translation process is processing.
             match translated.is_empty() {
               true => {
-                debug!("Translating task of {} is pending...", raid_boss_raw.get_boss_name());
+                debug!("Translating task of {} is pending...", raid_boss_raw.boss_name);
                 let _ = respond_to.send(TranslatorResult::Pending);
               }
               false => {
                 // Should update redis expiration of gbf:boss:{level}:{name}.
-                let from_language = Language::from_str(raid_boss_raw.get_language()).unwrap();
-                let redis_key = gbf_raid_boss_jp_key_from_raw(from_language, &raid_boss_raw, translated);
-                self.redis.expire(redis_key, BOSS_EXPIRE_IN_30_DAYS_TTL).await?;
+                let from_language = Language::from_str(raid_boss_raw.language.as_str()).unwrap();
+                let redis_key =
+                  gbf_raid_boss_jp_key_from_raw(&self.config.redis_prefix, from_language, &raid_boss_raw, translated);
+                self.redis.expire(redis_key, self.config.boss_ttl).await?;
                 let _ = respond_to.send(TranslatorResult::Success {
                   result: translated.to_string(),
                 });
@@ -145,21 +180,22 @@ impl TweetActor {
           None => {
             // Drop map RwLock before translating
             drop(translate_map);
-            let mut writable_map = self.map.write().await;
+            let mut writable_map = self.map.pairs.write().await;
             // Write an empty string to `map` means that translation is pending.
-            writable_map.insert(raid_boss_raw.get_boss_name().into(), "".into());
+            writable_map.insert(raid_boss_raw.boss_name.clone(), "".into());
             drop(writable_map);
             // Response to handler before processing translation tasks.
             let _ = respond_to.send(TranslatorResult::Pending);
-            debug!("Find new boss {}. Translating...", raid_boss_raw.get_boss_name());
+            debug!("Find new boss {}. Translating...", raid_boss_raw.boss_name);
 
             // Prepare for translation task.
             let map = self.map.clone();
+            let config = self.config.clone();
             let redis = self.redis.clone();
 
-            // Do translation parallel
-            tokio::spawn(async move {
-              translator::translator_tasks(raid_boss_raw, redis, map).await?;
+            // Do translation parallel, shutdown waits for it so the result is saved into redis.
+            self.shutdown.spawn(async move {
+              translator::translator_tasks(raid_boss_raw, config, redis, map).await?;
 
               Ok::<(), error::Error>(())
             });
@@ -174,18 +210,18 @@ impl TweetActor {
         translator_result,
         respond_to,
       } => {
-        let language = Language::from_str(raid_boss_raw.get_language()).unwrap();
+        let language = Language::from_str(raid_boss_raw.language.as_str()).unwrap();
         let translated_tweet = match language {
           // Only English boss name should be converted into Japanese
           Language::English => match translator_result {
             TranslatorResult::Pending => Err(error::Error::CannotTranslate {
-              name: raid_boss_raw.get_boss_name().into(),
+              name: raid_boss_raw.boss_name.clone(),
             }),
             TranslatorResult::Success {
               result: translated_name,
             } => {
               if language == Language::English {
-                raid_tweet.set_boss_name(translated_name);
+                raid_tweet.boss_name = translated_name;
               }
 
               Ok(raid_tweet)
@@ -198,18 +234,81 @@ impl TweetActor {
 
         Ok(())
       }
+      TweetActorMessage::DeduplicateRaidTweet { raid_tweet, respond_to } => {
+        if self.config.raid_dedup_ttl == 0 {
+          let _ = respond_to.send(Ok(raid_tweet));
+
+          return Ok(());
+        }
+
+        let redis_key = gbf_raid_dedup_key(
+          &self.config.redis_prefix,
+          raid_tweet.boss_name.as_str(),
+          raid_tweet.raid_id.as_str(),
+        );
+        // The first tweet of a raid takes the key, the others in the window are duplicated.
+        match self
+          .redis
+          .set_nx_ex(redis_key, raid_tweet.tweet_id.to_string(), self.config.raid_dedup_ttl)
+          .await
+        {
+          Ok(true) => {
+            let _ = respond_to.send(Ok(raid_tweet));
+
+            Ok(())
+          }
+          Ok(false) => {
+            Stats::increase(&self.stats.duplicated_tweets);
+            let _ = respond_to.send(Err(error::Error::DuplicatedRaidTweet {
+              boss_name: raid_tweet.boss_name,
+              raid_id: raid_tweet.raid_id,
+            }));
+
+            Ok(())
+          }
+          // Delivering a duplicated raid is better than dropping a new one when redis is unavailable.
+          Err(error) => {
+            let _ = respond_to.send(Ok(raid_tweet));
+
+            Err(error)
+          }
+        }
+      }
       TweetActorMessage::PersistRaidTweet { raid_tweet, respond_to } => {
         let _ = respond_to.send(Ok(raid_tweet.clone()));
 
+        let bytes = raid_tweet.to_bytes()?;
+        let config = self.config.clone();
         let redis = self.redis.clone();
 
-        // Persist raid_tweet parallel
-        tokio::spawn(async move {
+        // Persist raid_tweet parallel, shutdown waits for pending writes.
+        self.shutdown.spawn(async move {
           redis
             .set_protobuf(
-              gbf_persistence_raid_tweet_key(raid_tweet.get_boss_name(), raid_tweet.tweet_id, raid_tweet.created),
+              gbf_persistence_raid_tweet_key(
+                &config.redis_prefix,
+                raid_tweet.boss_name.as_str(),
+                raid_tweet.tweet_id,
+                raid_tweet.created,
+              ),
               raid_tweet,
-              TWEET_PERSISTENCE_ONLY_2_HOURS_TTL,
+              config.tweet_ttl,
+            )
+            .await?;
+
+          Ok::<(), error::Error>(())
+        });
+
+        // Append to the stream parallel as well, a slow XADD should not stall the tweets behind it.
+        let config = self.config.clone();
+        let redis = self.redis.clone();
+        self.shutdown.spawn(async move {
+          redis
+            .xadd_capped(
+              gbf_raid_tweets_stream_key(&config.redis_prefix),
+              config.tweet_stream_max_len,
+              STREAM_TWEET_FIELD,
+              bytes,
             )
             .await?;
 
@@ -227,10 +326,23 @@ pub struct TweetActorHandle {
 }
 
 impl TweetActorHandle {
-  pub fn new(redis: Arc<Redis>, map: HashMap<String, String>) -> Self {
+  pub fn new(
+    config: Arc<Config>,
+    redis: Arc<Redis>,
+    map: TranslatorMap,
+    stats: Arc<Stats>,
+    health: Arc<Health>,
+    shutdown: Arc<Shutdown>,
+  ) -> Self {
     let (sender, receiver) = mpsc::channel(1024);
-    let mut actor = TweetActor::new(receiver, redis, map);
-    let _ = tokio::spawn(async move { actor.run().await }).map_err(|e| {
+    let actor_guard = health.actor_guard();
+    let mut actor = TweetActor::new(receiver, config, redis, map, stats, health, shutdown);
+    let _ = tokio::spawn(async move {
+      // Report the actor as dead once it stops or panics.
+      let _actor_guard = actor_guard;
+      actor.run().await
+    })
+    .map_err(|e| {
       if e.is_panic() {
         error!("Actor task might get panic!, error: {}", e);
       } else if e.is_cancelled() {
@@ -290,6 +402,20 @@ impl TweetActorHandle {
     }
   }
 
+  pub async fn deduplicate_raid_tweet(&self, raid_tweet: RaidTweet) -> Result<RaidTweet> {
+    let (send, recv) = oneshot::channel();
+    let msg = TweetActorMessage::DeduplicateRaidTweet {
+      raid_tweet,
+      respond_to: send,
+    };
+    let _ = self.sender.send(msg).await;
+    let result = recv.await;
+    match result {
+      Ok(result) => result,
+      Err(e) => Err(error::Error::ActorTaskBeenKilled { error: e }),
+    }
+  }
+
   pub async fn persist_raid_tweet(&self, raid_tweet: RaidTweet) -> Result<RaidTweet> {
     let (send, recv) = oneshot::channel();
     let msg = TweetActorMessage::PersistRaidTweet {
@@ -309,11 +435,13 @@ impl TweetActorHandle {
 mod tests {
   use super::*;
   use crate::{
+    common::translator_map::TranslatorPairs,
     models::{Entity, Language, Media, User},
-    resources::redis::BOSS_EXPIRE_IN_30_DAYS_TTL,
+    proto::raid_boss_raw::RaidBossRawExt,
+    resources::redis::{DEFAULT_BOSS_TTL, DEFAULT_PREFIX},
     Result,
   };
-  use std::env;
+  use std::{collections::HashMap, env, time::Duration};
 
   lazy_static::lazy_static! {
     static ref JP_TWEET: Tweet = Tweet {
@@ -360,19 +488,26 @@ mod tests {
     let map: HashMap<String, String> = HashMap::new();
     redis
       .set_protobuf(
-        gbf_raid_boss_raw_key(&EN_RAID_BOSS_RAW),
+        gbf_raid_boss_raw_key(DEFAULT_PREFIX, &EN_RAID_BOSS_RAW),
         EN_RAID_BOSS_RAW.clone(),
-        BOSS_EXPIRE_IN_30_DAYS_TTL,
+        DEFAULT_BOSS_TTL,
       )
       .await?;
     redis
       .set_protobuf(
-        gbf_raid_boss_raw_key(&JP_RAID_BOSS_RAW),
+        gbf_raid_boss_raw_key(DEFAULT_PREFIX, &JP_RAID_BOSS_RAW),
         JP_RAID_BOSS_RAW.clone(),
-        BOSS_EXPIRE_IN_30_DAYS_TTL,
+        DEFAULT_BOSS_TTL,
       )
       .await?;
-    let actor = TweetActorHandle::new(redis, map);
+    let actor = TweetActorHandle::new(
+      Arc::new(Config::default()),
+      redis,
+      Arc::new(TranslatorPairs::new(map)),
+      Arc::new(Stats::new()),
+      Arc::new(Health::new()),
+      Arc::new(Shutdown::new(Duration::from_secs(5))),
+    );
     let (raid_boss_raw, _raid_tweet) = actor.parse_tweet(JP_TWEET.clone()).await.unwrap();
     assert_eq!(
       actor.translate_boss_name(raid_boss_raw.clone()).await.unwrap(),
@@ -404,7 +539,14 @@ mod tests {
     let mut map: HashMap<String, String> = HashMap::new();
     map.insert("Lv150 プロトバハムート".into(), "Lvl 150 Proto Bahamut".into());
     map.insert("Lvl 150 Proto Bahamut".into(), "Lv150 プロトバハムート".into());
-    let actor = TweetActorHandle::new(redis, map);
+    let actor = TweetActorHandle::new(
+      Arc::new(Config::default()),
+      redis,
+      Arc::new(TranslatorPairs::new(map)),
+      Arc::new(Stats::new()),
+      Arc::new(Health::new()),
+      Arc::new(Shutdown::new(Duration::from_secs(5))),
+    );
     let (raid_boss_raw, raid_tweet) = actor.parse_tweet(JP_TWEET.clone()).await.unwrap();
     assert_eq!(raid_boss_raw.boss_name, "Lv150 プロトバハムート");
     assert_eq!(raid_boss_raw.level, 150);
@@ -417,6 +559,40 @@ mod tests {
     Ok(())
   }
 
+  #[tokio::test]
+  async fn test_duplicated_raid_tweet() -> Result<()> {
+    let redis = Redis::new(REDIS_URL.clone())?;
+    let redis = Arc::new(redis);
+    let stats = Arc::new(Stats::new());
+    let actor = TweetActorHandle::new(
+      Arc::new(Config::default()),
+      redis,
+      TranslatorMap::default(),
+      stats.clone(),
+      Arc::new(Health::new()),
+      Arc::new(Shutdown::new(Duration::from_secs(5))),
+    );
+    let (_, mut jp_raid_tweet) = actor.parse_tweet(JP_TWEET.clone()).await.unwrap();
+    let (_, mut en_raid_tweet) = actor.parse_tweet(EN_TWEET.clone()).await.unwrap();
+    // Use a random raid id so that the test will not collide with previous runs.
+    let raid_id = nanoid::nanoid!(8);
+    jp_raid_tweet.raid_id = raid_id.clone();
+    en_raid_tweet.raid_id = raid_id;
+    en_raid_tweet.boss_name = jp_raid_tweet.boss_name.clone();
+    assert!(actor.deduplicate_raid_tweet(jp_raid_tweet.clone()).await.is_ok());
+    assert!(matches!(
+      actor.deduplicate_raid_tweet(jp_raid_tweet).await,
+      Err(error::Error::DuplicatedRaidTweet { .. })
+    ));
+    assert!(matches!(
+      actor.deduplicate_raid_tweet(en_raid_tweet).await,
+      Err(error::Error::DuplicatedRaidTweet { .. })
+    ));
+    assert_eq!(2, stats.snapshot().duplicated_tweets);
+
+    Ok(())
+  }
+
   #[tokio::test]
   async fn test_en_tweet_already_translated() -> Result<()> {
     let redis = Redis::new(REDIS_URL.clone())?;
@@ -424,7 +600,14 @@ mod tests {
     let mut map: HashMap<String, String> = HashMap::new();
     map.insert("Lv150 プロトバハムート".into(), "Lvl 150 Proto Bahamut".into());
     map.insert("Lvl 150 Proto Bahamut".into(), "Lv150 プロトバハムート".into());
-    let actor = TweetActorHandle::new(redis, map);
+    let actor = TweetActorHandle::new(
+      Arc::new(Config::default()),
+      redis,
+      Arc::new(TranslatorPairs::new(map)),
+      Arc::new(Stats::new()),
+      Arc::new(Health::new()),
+      Arc::new(Shutdown::new(Duration::from_secs(5))),
+    );
     let (raid_boss_raw, raid_tweet) = actor.parse_tweet(EN_TWEET.clone()).await.unwrap();
     assert_eq!(raid_boss_raw.boss_name, "Lvl 150 Proto Bahamut");
     assert_eq!(raid_boss_raw.level, 150);
@@ -1,15 +1,25 @@
-use crate::{proto::raid_tweet::RaidTweet, FinderClients};
+use crate::{
+  proto::{raid_tweet::RaidTweet, MessageExt},
+  FinderClients,
+};
+use bytes::Bytes;
+use log::error;
 
-pub fn sending_message_to_websocket_client(raid_tweet: RaidTweet, clients: FinderClients) {
-  tokio::spawn(async move {
-    let clients = clients.read().await;
-    clients.iter().for_each(move |(_, client)| {
-      if client.boss_names.contains(&raid_tweet.boss_name) {
-        if let Ok(bytes) = raid_tweet.to_bytes() {
-          let msg = warp::ws::Message::binary(bytes);
-          let _ = client.sender.send(Ok(msg));
-        }
-      }
-    });
-  });
+///
+/// Fan out a raid tweet to websocket clients which subscribe its boss.
+/// The raid tweet is encoded once and the bytes are shared by every client queue.
+/// Websocket consumers still copy the bytes into their own frame, since warp only accepts owned messages.
+///
+pub async fn sending_message_to_websocket_client(raid_tweet: RaidTweet, clients: FinderClients) {
+  let bytes = match raid_tweet.to_bytes() {
+    Ok(bytes) => Bytes::from(bytes),
+    Err(e) => {
+      error!("Cannot encode raid tweet {}, error: {:?}", raid_tweet.tweet_id, e);
+      return;
+    }
+  };
+  let clients = clients.read().await;
+  for client in clients.subscribers(&raid_tweet.boss_name) {
+    client.send_raid_tweet(raid_tweet.tweet_id, bytes.clone());
+  }
 }